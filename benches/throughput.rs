@@ -0,0 +1,317 @@
+//! throughput benchmarks, run with `cargo bench`.
+//!
+//! every sample gets a fresh unbounded ringbuf in the temp dir so receivers never
+//! lose messages to reclaimed pages, and the directory is removed again afterwards.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use disk_ringbuffer::ringbuf::{BackoffPolicy, Builder, DiskRing, ReaderBackoff, Receiver, Sender};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const MSG: &[u8; 16] = b"0123456789abcdef";
+const PRODUCERS: u64 = 4;
+
+struct BenchDir(PathBuf);
+
+impl BenchDir {
+    fn new(name: &str) -> BenchDir {
+        let path = std::env::temp_dir().join(format!("disk-ringbuffer-bench-{name}"));
+        let _ = std::fs::remove_dir_all(&path);
+
+        BenchDir(path)
+    }
+
+    fn open(&self) -> (DiskRing<Sender>, DiskRing<Receiver>) {
+        self.builder().build().unwrap()
+    }
+
+    fn builder(&self) -> Builder {
+        Builder::new(&self.0).max_qpages(0)
+    }
+}
+
+impl Drop for BenchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn pop_spin(rx: &mut DiskRing<Receiver>) {
+    while rx.pop().unwrap().is_none() {}
+}
+
+fn seq_push(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("seq", |b| {
+        b.iter_custom(|iters| {
+            let dir = BenchDir::new("seq-push");
+            let (mut tx, _rx) = dir.open();
+
+            let now = Instant::now();
+            for _ in 0..iters {
+                tx.push(MSG).unwrap();
+            }
+            now.elapsed()
+        })
+    });
+
+    // only differs from seq where the kernel can back the pages with huge pages
+    #[cfg(target_os = "linux")]
+    group.bench_function("seq_huge_pages", |b| {
+        b.iter_custom(|iters| {
+            let dir = BenchDir::new("seq-push-huge-pages");
+            let (mut tx, _rx) = dir.builder().huge_pages(true).build().unwrap();
+
+            let now = Instant::now();
+            for _ in 0..iters {
+                tx.push(MSG).unwrap();
+            }
+            now.elapsed()
+        })
+    });
+
+    group.finish();
+}
+
+/// pushes of messages from a single byte up, to see how much of a push is spent
+/// copying the message
+fn push_sizes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push_size");
+    group.throughput(Throughput::Elements(1));
+
+    for size in [1, 4, 8, 16, 17, 32, 64] {
+        let msg = vec![b'x'; size];
+        group.bench_with_input(BenchmarkId::from_parameter(size), &msg, |b, msg| {
+            b.iter_custom(|iters| {
+                let dir = BenchDir::new(&format!("push-size-{size}"));
+                let (mut tx, _rx) = dir.open();
+
+                let now = Instant::now();
+                for _ in 0..iters {
+                    tx.push(msg).unwrap();
+                }
+                now.elapsed()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// formatting numbers into a fresh string per push against the sender's reused buffer
+fn push_display(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push_display");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("to_string", |b| {
+        b.iter_custom(|iters| {
+            let dir = BenchDir::new("push-display-to-string");
+            let (mut tx, _rx) = dir.open();
+
+            let now = Instant::now();
+            for i in 0..iters {
+                tx.push(i.to_string()).unwrap();
+            }
+            now.elapsed()
+        })
+    });
+
+    group.bench_function("push_display", |b| {
+        b.iter_custom(|iters| {
+            let dir = BenchDir::new("push-display");
+            let (mut tx, _rx) = dir.open();
+
+            let now = Instant::now();
+            for i in 0..iters {
+                tx.push_display(i).unwrap();
+            }
+            now.elapsed()
+        })
+    });
+
+    group.finish();
+}
+
+fn pop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pop");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("owned", |b| {
+        b.iter_custom(|iters| {
+            let dir = BenchDir::new("pop-owned");
+            let (mut tx, mut rx) = dir.open();
+            for _ in 0..iters {
+                tx.push(MSG).unwrap();
+            }
+
+            let now = Instant::now();
+            for _ in 0..iters {
+                criterion::black_box(rx.pop().unwrap().unwrap());
+            }
+            now.elapsed()
+        })
+    });
+
+    group.bench_function("borrowed", |b| {
+        b.iter_custom(|iters| {
+            let dir = BenchDir::new("pop-borrowed");
+            let (mut tx, mut rx) = dir.open();
+            for _ in 0..iters {
+                tx.push(MSG).unwrap();
+            }
+
+            let now = Instant::now();
+            for _ in 0..iters {
+                criterion::black_box(rx.pop_cow().unwrap().unwrap());
+            }
+            now.elapsed()
+        })
+    });
+
+    group.finish();
+}
+
+fn spsc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("channel");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("spsc", |b| {
+        b.iter_custom(|iters| {
+            let dir = BenchDir::new("spsc");
+            let (mut tx, mut rx) = dir.open();
+
+            let now = Instant::now();
+            let producer = std::thread::spawn(move || {
+                for _ in 0..iters {
+                    tx.push(MSG).unwrap();
+                }
+            });
+
+            for _ in 0..iters {
+                pop_spin(&mut rx);
+            }
+            let elapsed = now.elapsed();
+
+            producer.join().unwrap();
+            elapsed
+        })
+    });
+
+    group.bench_function("mpsc", |b| {
+        b.iter_custom(|iters| {
+            let dir = BenchDir::new("mpsc");
+            let (tx, mut rx) = dir.open();
+            let per_producer = iters.div_ceil(PRODUCERS);
+
+            let now = Instant::now();
+            let producers: Vec<_> = (0..PRODUCERS)
+                .map(|_| {
+                    let mut tx = tx.clone();
+                    std::thread::spawn(move || {
+                        for _ in 0..per_producer {
+                            tx.push(MSG).unwrap();
+                        }
+                    })
+                })
+                .collect();
+
+            for _ in 0..per_producer * PRODUCERS {
+                pop_spin(&mut rx);
+            }
+            let elapsed = now.elapsed();
+
+            for producer in producers {
+                producer.join().unwrap();
+            }
+            elapsed
+        })
+    });
+
+    group.bench_function("mpsc_backoff", |b| {
+        b.iter_custom(|iters| {
+            let dir = BenchDir::new("mpsc-backoff");
+            // producers back off when they keep running into full pages
+            let (tx, mut rx) = dir
+                .builder()
+                .push_backoff(BackoffPolicy::default())
+                .build()
+                .unwrap();
+            let per_producer = iters.div_ceil(PRODUCERS);
+
+            let now = Instant::now();
+            let producers: Vec<_> = (0..PRODUCERS)
+                .map(|_| {
+                    let mut tx = tx.clone();
+                    std::thread::spawn(move || {
+                        for _ in 0..per_producer {
+                            tx.push(MSG).unwrap();
+                        }
+                    })
+                })
+                .collect();
+
+            for _ in 0..per_producer * PRODUCERS {
+                pop_spin(&mut rx);
+            }
+            let elapsed = now.elapsed();
+
+            for producer in producers {
+                producer.join().unwrap();
+            }
+            elapsed
+        })
+    });
+
+    group.finish();
+}
+
+/// round trips between two threads that block in `stream`, half of one is how long
+/// a waiting receiver takes to wake up for a new message. busy spinning only pays off
+/// with a free core for each thread, with fewer it starves the other side instead.
+fn wakeup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wakeup");
+
+    for (name, backoff) in [
+        ("cooperative", ReaderBackoff::default()),
+        ("busy_spin", ReaderBackoff::BusySpin),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                let ping_dir = BenchDir::new(&format!("wakeup-ping-{name}"));
+                let pong_dir = BenchDir::new(&format!("wakeup-pong-{name}"));
+                let (mut ping_tx, mut ping_rx) =
+                    ping_dir.builder().read_backoff(backoff).build().unwrap();
+                let (mut pong_tx, mut pong_rx) =
+                    pong_dir.builder().read_backoff(backoff).build().unwrap();
+
+                let echo = std::thread::spawn(move || {
+                    for msg in ping_rx.stream().take(iters as usize) {
+                        pong_tx.push(msg.unwrap()).unwrap();
+                    }
+                });
+
+                let mut pongs = pong_rx.stream();
+                let now = Instant::now();
+                for _ in 0..iters {
+                    ping_tx.push(MSG).unwrap();
+                    pongs.next().unwrap().unwrap();
+                }
+                let elapsed = now.elapsed();
+
+                echo.join().unwrap();
+                elapsed
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(5));
+    targets = seq_push, push_sizes, push_display, pop, spsc, wakeup
+}
+criterion_main!(benches);
@@ -0,0 +1,41 @@
+//! logs into a ringbuf from one thread and tails it from another, the way
+//! a separate log shipping process would.
+//!
+//! ```sh
+//! cargo run --example log_sink --features log
+//! ```
+
+use disk_ringbuffer::{logger::DiskRingLogger, ringbuf};
+use log::LevelFilter;
+use std::time::Duration;
+
+fn main() {
+    let (tx, mut rx) = ringbuf::new("example-log-sink").unwrap();
+    DiskRingLogger::new(tx)
+        .level(LevelFilter::Info)
+        .init()
+        .unwrap();
+
+    let shipper = std::thread::spawn(move || {
+        let mut shipped = 0;
+
+        while shipped < 5 {
+            match rx.pop().unwrap() {
+                Some(line) => {
+                    println!("shipped: {line}");
+                    shipped += 1;
+                }
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    });
+
+    for i in 0..5 {
+        log::info!("request {i} handled");
+    }
+    log::debug!("not logged");
+    log::logger().flush();
+
+    shipper.join().unwrap();
+    std::fs::remove_dir_all("example-log-sink").unwrap();
+}
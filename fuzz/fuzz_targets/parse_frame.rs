@@ -0,0 +1,42 @@
+#![no_main]
+
+use disk_ringbuffer::ringbuf::{parse_frame, ByteOrder, FrameFormat, LengthPrefix};
+use libfuzzer_sys::fuzz_target;
+
+// the first byte picks the framing, the rest is treated as committed page contents
+fuzz_target!(|data: &[u8]| {
+    let Some((&format, buf)) = data.split_first() else {
+        return;
+    };
+
+    let prefix = match format & 0b11 {
+        0 => LengthPrefix::U8,
+        1 => LengthPrefix::U16,
+        2 => LengthPrefix::U32,
+        _ => LengthPrefix::Varint,
+    };
+    let format = FrameFormat {
+        prefix,
+        byte_order: match format & 0b100 {
+            0 => ByteOrder::Little,
+            _ => ByteOrder::Big,
+        },
+        sync_markers: format & 0b1000 != 0,
+        keys: format & 0b10000 != 0,
+        split: format & 0b100000 != 0,
+    };
+
+    let mut start = 0;
+    while let Some(msg) = parse_frame(buf, start, format) {
+        let buf_range = buf.as_ptr_range();
+        let msg_range = msg.as_ptr_range();
+        assert!(buf_range.start <= msg_range.start && msg_range.end <= buf_range.end);
+
+        // every frame has a header so this always makes progress
+        let frame_len = format.frame_len(msg.len());
+        assert!(frame_len > msg.len());
+        assert!(start + frame_len <= buf.len());
+
+        start += frame_len;
+    }
+});
@@ -0,0 +1,213 @@
+//! a disk backed channel with the same shape as [`std::sync::mpsc`], so swapping one
+//! for the other is mostly a matter of changing the constructor.
+//!
+//! like its std counterpart it's meant for a single process: the ringbuf can outlive
+//! it, but only one process should have the channel open at a time (see [`channel`]).
+//!
+//! ```rust
+//! use disk_ringbuffer::disk_mpsc;
+//!
+//! let (tx, rx) = disk_mpsc::channel::<String>("test-disk-mpsc-doc").unwrap();
+//!
+//! let t = std::thread::spawn(move || {
+//!     for i in 0..10 {
+//!         tx.send(i.to_string()).unwrap();
+//!     }
+//! });
+//!
+//! assert_eq!(rx.iter().count(), 10);
+//! t.join().unwrap();
+//! # std::fs::remove_dir_all("test-disk-mpsc-doc").unwrap();
+//! ```
+
+use crate::ringbuf::{self, Backoff, DiskRing, FromRingbufBytes, Receiver, RingbufError, Sender};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// like [`std::sync::mpsc::SendError`], but with the reason the send failed
+#[derive(thiserror::Error, Debug)]
+#[error("sending on a disk channel failed: {cause}")]
+pub struct SendError<T> {
+    /// the message that couldn't be sent
+    pub msg: T,
+    /// [`RingbufError::Closed`] once the receiving side is gone for good
+    pub cause: RingbufError,
+}
+
+/// like [`std::sync::mpsc::RecvError`]
+#[derive(thiserror::Error, Debug)]
+pub enum RecvError {
+    #[error("receiving on a closed disk channel")]
+    Disconnected,
+    #[error(transparent)]
+    Ringbuf(#[from] RingbufError),
+}
+
+/// like [`std::sync::mpsc::TryRecvError`]
+#[derive(thiserror::Error, Debug)]
+pub enum TryRecvError {
+    #[error("receiving on an empty disk channel")]
+    Empty,
+    #[error("receiving on a closed disk channel")]
+    Disconnected,
+    #[error(transparent)]
+    Ringbuf(#[from] RingbufError),
+}
+
+/// opens the ringbuf at `path` as a channel of `T`s.
+///
+/// once every [`DiskTx`] from this process is dropped the ringbuf is
+/// [closed](DiskRing::close), so receivers see [`RecvError::Disconnected`] after
+/// reading everything sent before that. opening the channel again (e.g. after a
+/// restart) [reopens](DiskRing::reopen) it.
+///
+/// the channel only keeps track of the senders in this process, so it mustn't be
+/// open in more than one process at a time. the close is stored in the ringbuf, so
+/// the first process to drop all of its senders would disconnect every other
+/// process's receivers too, and opening it anywhere would undo another process's
+/// close. share a ringbuf across processes with [`ringbuf::new`] and
+/// [`DiskRing::close`] instead.
+pub fn channel<T>(path: impl AsRef<Path>) -> Result<(DiskTx<T>, DiskRx<T>), RingbufError> {
+    let (mut tx, rx) = ringbuf::new(path)?;
+    tx.reopen()?;
+
+    let tx = DiskTx {
+        tx: Mutex::new(tx),
+        senders: Arc::new(AtomicUsize::new(1)),
+        _msg: PhantomData,
+    };
+    let rx = DiskRx {
+        rx: Mutex::new(rx),
+        _msg: PhantomData,
+    };
+
+    Ok((tx, rx))
+}
+
+/// sending half of a [`channel`]
+pub struct DiskTx<T> {
+    tx: Mutex<DiskRing<Sender>>,
+    // clones of this sender that are still alive
+    senders: Arc<AtomicUsize>,
+    _msg: PhantomData<fn(T)>,
+}
+
+impl<T: AsRef<[u8]>> DiskTx<T> {
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        let mut tx = self.tx.lock().unwrap_or_else(|e| e.into_inner());
+
+        match tx.push(msg.as_ref()) {
+            Ok(_) => Ok(()),
+            Err(cause) => Err(SendError { msg, cause }),
+        }
+    }
+}
+
+impl<T> Clone for DiskTx<T> {
+    fn clone(&self) -> Self {
+        self.senders.fetch_add(1, Ordering::Relaxed);
+
+        DiskTx {
+            tx: Mutex::new(self.tx.lock().unwrap_or_else(|e| e.into_inner()).clone()),
+            senders: self.senders.clone(),
+            _msg: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for DiskTx<T> {
+    fn drop(&mut self) {
+        if self.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            let tx = self.tx.get_mut().unwrap_or_else(|e| e.into_inner());
+            tx.clone().close();
+        }
+    }
+}
+
+/// receiving half of a [`channel`]
+pub struct DiskRx<T> {
+    rx: Mutex<DiskRing<Receiver>>,
+    _msg: PhantomData<fn() -> T>,
+}
+
+impl<T: FromRingbufBytes> DiskRx<T> {
+    /// blocks until there's a message, or every sender is gone and
+    /// everything they sent has been read
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut backoff = Backoff::new();
+
+        loop {
+            match self.try_recv() {
+                Ok(msg) => return Ok(msg),
+                Err(TryRecvError::Empty) => backoff.snooze(),
+                Err(TryRecvError::Disconnected) => return Err(RecvError::Disconnected),
+                Err(TryRecvError::Ringbuf(e)) => return Err(e.into()),
+            }
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut rx = self.rx.lock().unwrap_or_else(|e| e.into_inner());
+
+        // has to be checked before popping, everything sent before the
+        // close is visible by then so coming up empty really is the end
+        let closed = rx.is_closed();
+
+        match rx.pop_as::<T>()? {
+            Some(msg) => Ok(msg),
+            None if closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// blocking iterator that ends once the channel is disconnected,
+    /// like [`std::sync::mpsc::Receiver::iter`]
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.recv().ok())
+    }
+}
+
+#[test]
+fn disconnect_test() {
+    let test_dir_path = "test-disk-mpsc";
+    let (tx, rx) = channel::<String>(test_dir_path).unwrap();
+    let tx2 = tx.clone();
+
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+    tx.send("hello".to_string()).unwrap();
+    drop(tx);
+
+    // one sender is still around
+    tx2.send("world".to_string()).unwrap();
+    assert_eq!(rx.recv().unwrap(), "hello");
+    assert!(!DiskRing::<Receiver>::new(test_dir_path)
+        .unwrap()
+        .is_closed());
+
+    let t = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        tx2.send("last".to_string()).unwrap();
+    });
+
+    assert_eq!(rx.recv().unwrap(), "world");
+    assert_eq!(rx.recv().unwrap(), "last");
+    t.join().unwrap();
+
+    assert!(matches!(rx.recv(), Err(RecvError::Disconnected)));
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+    drop(rx);
+
+    // a restarted process can use the channel again, its receiver starts over
+    // at the beginning of the page
+    let (tx, rx) = channel::<String>(test_dir_path).unwrap();
+    tx.send("again".to_string()).unwrap();
+    let msgs: Vec<_> = (0..4).map(|_| rx.recv().unwrap()).collect();
+    assert_eq!(msgs, ["hello", "world", "last", "again"]);
+    drop(tx);
+    assert!(matches!(rx.recv(), Err(RecvError::Disconnected)));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
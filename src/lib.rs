@@ -52,7 +52,62 @@ fn thread_example() {
     t.join().unwrap();
 }
 ```
+
+## Ordering
+
+every receiver sees messages in the order senders reserved space for them on a page,
+no matter in which order the senders finish writing. a receiver never reads past a
+message that's still being written, so it waits for a slow sender rather than skip
+ahead of it.
+
+## Platform support
+
+only 64 bit targets are supported. pages keep their writer count in the top byte of a
+`usize` next to the write offset, which doesn't leave enough room for a page on 32 bit
+targets, so building for one fails outright.
+
+## Tracing
+
+with the `tracing` feature enabled, senders and receivers emit [`tracing`](https://docs.rs/tracing)
+events under the `disk_ringbuffer` target:
+
+- `push` / `pop` (trace level) with the `qpage_no` and frame `bytes`
+- `page_flip` (debug level) with the `from` and `to` page numbers
+- `reclaim` (debug level) with the `qpage_no` of every deleted page
+
+without the feature none of this is compiled in.
+
+## Logging
+
+with the `log` feature enabled, [`logger::DiskRingLogger`] is a [`log`](https://docs.rs/log)
+logger that pushes every record into a ringbuf, so another process can tail it:
+```rust
+# #[cfg(feature = "log")]
+# fn main() {
+use disk_ringbuffer::{logger::DiskRingLogger, ringbuf};
+
+let (tx, mut rx) = ringbuf::new("test-logger-doc").unwrap();
+DiskRingLogger::new(tx).init().unwrap();
+
+log::info!("hello");
+assert!(rx.pop().unwrap().unwrap().ends_with("hello"));
+# std::fs::remove_dir_all("test-logger-doc").unwrap();
+# }
+# #[cfg(not(feature = "log"))]
+# fn main() {}
+```
 */
 
+// forwards to `tracing` when the feature is on and expands to nothing otherwise
+macro_rules! trace {
+    ($level:ident, $($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!(target: "disk_ringbuffer", $($arg)*);
+    }};
+}
+
+pub mod disk_mpsc;
+#[cfg(feature = "log")]
+pub mod logger;
 mod qpage;
 pub mod ringbuf;
@@ -0,0 +1,141 @@
+//! a [`log`] sink that pushes records into a ringbuf
+
+use crate::ringbuf::{DiskRing, Sender};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::io::Write;
+use std::sync::Mutex;
+
+/// appended to records that were cut off to fit into a single message
+pub const TRUNCATED: &str = " [truncated]";
+
+/// logger that pushes every record as a `<level> <target>: <message>` line
+/// into a ringbuf.
+///
+/// records that don't fit into a message are cut off and end in [`TRUNCATED`],
+/// [`Log::flush`] waits for everything logged so far to hit the disk.
+pub struct DiskRingLogger {
+    tx: Mutex<(DiskRing<Sender>, Vec<u8>)>,
+    level: LevelFilter,
+}
+
+impl DiskRingLogger {
+    /// logs everything down to [`LevelFilter::Trace`] unless told otherwise
+    pub fn new(tx: DiskRing<Sender>) -> DiskRingLogger {
+        DiskRingLogger {
+            tx: Mutex::new((tx, Vec::new())),
+            level: LevelFilter::Trace,
+        }
+    }
+
+    /// least severe level that still gets logged
+    pub fn level(mut self, level: LevelFilter) -> DiskRingLogger {
+        self.level = level;
+        self
+    }
+
+    /// installs this as the global logger
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl Log for DiskRingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut tx = self.tx.lock().unwrap_or_else(|e| e.into_inner());
+        let (tx, buf) = &mut *tx;
+
+        buf.clear();
+        let _ = write!(
+            buf,
+            "{} {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let max_msg_len = tx.max_msg_len();
+        if buf.len() > max_msg_len {
+            // the marker is left out if not even it fits
+            let marker = match TRUNCATED.len() <= max_msg_len {
+                true => TRUNCATED.as_bytes(),
+                false => &[],
+            };
+
+            // cut at a char boundary so the message stays valid utf-8
+            let mut end = max_msg_len - marker.len();
+            while end > 0 && (buf[end] & 0b1100_0000) == 0b1000_0000 {
+                end -= 1;
+            }
+
+            buf.truncate(end);
+            buf.extend_from_slice(marker);
+        }
+
+        // there's no one to tell about a failed push
+        let _ = tx.push(&buf);
+    }
+
+    fn flush(&self) {
+        let mut tx = self.tx.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = tx.0.flush();
+    }
+}
+
+#[test]
+fn logger_test() {
+    use crate::ringbuf::{Builder, LengthPrefix};
+    use log::Level;
+
+    let test_dir_path = "test-logger";
+    let (tx, mut rx) = Builder::new(test_dir_path)
+        .length_prefix(LengthPrefix::U8)
+        .build()
+        .unwrap();
+    let logger = DiskRingLogger::new(tx).level(LevelFilter::Info);
+
+    logger.log(
+        &Record::builder()
+            .level(Level::Warn)
+            .target("app")
+            .args(format_args!("disk is {}% full", 90))
+            .build(),
+    );
+    logger.log(
+        &Record::builder()
+            .level(Level::Debug)
+            .args(format_args!("filtered out"))
+            .build(),
+    );
+    logger.log(
+        &Record::builder()
+            .level(Level::Info)
+            .target("app")
+            .args(format_args!("{}", "ü".repeat(200)))
+            .build(),
+    );
+    logger.flush();
+
+    assert_eq!(
+        rx.pop().unwrap(),
+        Some("WARN app: disk is 90% full".to_string())
+    );
+
+    let truncated = rx.pop().unwrap().unwrap();
+    assert!(truncated.len() <= LengthPrefix::U8.max_msg_len());
+    assert!(truncated.starts_with("INFO app: ü"));
+    assert!(truncated.ends_with(TRUNCATED));
+    assert!(!truncated.contains('\u{FFFD}'));
+
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
@@ -1,8 +1,11 @@
 use core::slice;
 use std::cmp;
 use std::fmt::Display;
-use std::path::Path;
+use std::fs::File;
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 use mmap_wrapper::MmapMutWrapper;
 use static_assertions::const_assert;
@@ -15,19 +18,605 @@ const_assert!(DEFAULT_QUEUE_SIZE > DEFAULT_MAX_MSG_SIZE);
 const_assert!(DEFAULT_MAX_MSG_SIZE < MsgLengthType::MAX as usize);
 // 0000 0001 0000 ....
 const QUEUE_MAGIC_NUM: usize = 0b1 << (usize::BITS - 8);
-// 0000 0000 1111 ....
-const QUEUE_MAGIC_MASK: usize = QUEUE_MAGIC_NUM - 1;
+// 0000 0000 1000 ....
+const PAGE_CLOSED: usize = QUEUE_MAGIC_NUM >> 1;
+// 0000 0000 0111 ....
+const QUEUE_MAGIC_MASK: usize = PAGE_CLOSED - 1;
+// 1111 1111 0000 ....
+const WRITERS_MASK: usize = !(QUEUE_MAGIC_NUM - 1);
 
+// the top byte of write_idx_lock counts writers, which only leaves room for page
+// offsets on 64 bit targets. on 32 bit the count would start at 16MB, well inside a page
+#[cfg(not(target_pointer_width = "64"))]
+compile_error!("disk-ringbuffer only supports 64 bit targets");
+
+// every writer the count can hold may bump the index past the end of a full page
+// at once (each by up to a whole frame) before noticing, none of that can be
+// allowed to carry into the closed bit or the writer count
+const MAX_WRITERS: usize = usize::MAX >> (usize::BITS - 8);
+const_assert!(
+    DEFAULT_QUEUE_SIZE
+        + MAX_WRITERS * (SYNC_MARKER.len() + size_of::<MsgLengthType>() + DEFAULT_MAX_MSG_SIZE)
+        < PAGE_CLOSED
+);
+
+// reservations reaching this don't fit on the page, which keeps
+// at least its last byte free for the page done marker
+const PAGE_END: usize = DEFAULT_QUEUE_SIZE - 1;
+
+// the biggest message any format lets through, behind the biggest header and with
+// the padding a reservation keeps room for, has to fit on an empty page. otherwise
+// a sender would keep flipping to fresh pages that are all too small for it
+const fn fits_on_empty_page(prefix: LengthPrefix) -> bool {
+    let widest = FrameFormat {
+        prefix,
+        byte_order: ByteOrder::Little,
+        sync_markers: true,
+        keys: true,
+        split: true,
+        headers: true,
+    };
+
+    widest.frame_len(widest.max_msg_len()) + widest.min_padding_len() < PAGE_END
+}
+const_assert!(fits_on_empty_page(LengthPrefix::U32));
+const_assert!(fits_on_empty_page(LengthPrefix::Varint));
+
+/// size of a page file on disk
+pub const PAGE_FILE_SIZE: usize = size_of::<QPage>();
+
+/// where the message area starts in a page file, after the page's indices
+pub(crate) const BUF_OFFSET: usize = std::mem::offset_of!(QPage, buf);
+
+#[repr(C)]
 pub struct QPage {
     write_idx_lock: AtomicUsize,
     last_safe_write_idx: AtomicUsize,
     buf: [u8; DEFAULT_QUEUE_SIZE],
+    // index of the page done marker + 1 so a zeroed page reads as
+    // still having room. the marker byte alone is ambiguous since it
+    // can also be the first byte of a length prefix. it comes after
+    // buf so messages start where they did on pages without it
+    done_idx: AtomicUsize,
+}
+// the message area of pages written before done_idx existed
+const_assert!(std::mem::offset_of!(QPage, buf) == 2 * size_of::<usize>());
+
+/// encoding of the length prefix in front of every message on a page.
+///
+/// fixed width prefixes are written in the ringbuf's [`ByteOrder`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LengthPrefix {
+    U8,
+    U16,
+    #[default]
+    U32,
+    /// LEB128, 7 bits of length per byte so messages under 128 bytes
+    /// only pay a single byte of overhead
+    Varint,
+}
+
+/// byte order of fixed width length prefixes.
+///
+/// [`LengthPrefix::Varint`] is byte oriented and reads the same either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    Little,
+    /// network byte order
+    Big,
+}
+
+impl ByteOrder {
+    /// byte order of the machine this was compiled for
+    pub const fn native() -> ByteOrder {
+        if cfg!(target_endian = "big") {
+            ByteOrder::Big
+        } else {
+            ByteOrder::Little
+        }
+    }
+
+    // zero is reserved for "not set yet" in the info file
+    pub(crate) fn to_code(self) -> u8 {
+        match self {
+            ByteOrder::Little => 1,
+            ByteOrder::Big => 2,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<ByteOrder> {
+        match code {
+            1 => Some(ByteOrder::Little),
+            2 => Some(ByteOrder::Big),
+            _ => None,
+        }
+    }
+}
+
+// enough 7 bit groups for any message up to DEFAULT_MAX_MSG_SIZE
+const MAX_VARINT_LEN: usize =
+    (usize::BITS - DEFAULT_MAX_MSG_SIZE.leading_zeros()).div_ceil(7) as usize;
+
+impl LengthPrefix {
+    /// biggest message this prefix can frame
+    pub const fn max_msg_len(self) -> usize {
+        // all ones is the padding sentinel
+        let max = match self {
+            LengthPrefix::U8 => u8::MAX as usize - 1,
+            LengthPrefix::U16 => u16::MAX as usize - 1,
+            LengthPrefix::U32 => MsgLengthType::MAX as usize,
+            LengthPrefix::Varint => usize::MAX,
+        };
+
+        if max < DEFAULT_MAX_MSG_SIZE {
+            max
+        } else {
+            DEFAULT_MAX_MSG_SIZE
+        }
+    }
+
+    /// number of bytes the prefix of a `msg_len` long message takes up
+    pub const fn header_len(self, msg_len: usize) -> usize {
+        match self {
+            LengthPrefix::U8 => size_of::<u8>(),
+            LengthPrefix::U16 => size_of::<u16>(),
+            LengthPrefix::U32 => size_of::<MsgLengthType>(),
+            LengthPrefix::Varint => {
+                let bits = usize::BITS - msg_len.leading_zeros();
+                if bits == 0 {
+                    1
+                } else {
+                    bits.div_ceil(7) as usize
+                }
+            }
+        }
+    }
+
+    /// number of bytes a `msg_len` long message takes up on a page
+    pub const fn frame_len(self, msg_len: usize) -> usize {
+        self.header_len(msg_len) + msg_len
+    }
+
+    /// writes the prefix for a `msg_len` long message into the
+    /// start of `buf`, which has to be at least `header_len` long
+    pub fn encode(self, msg_len: usize, order: ByteOrder, buf: &mut [u8]) {
+        match (self, order) {
+            (LengthPrefix::U8, _) => buf[0] = msg_len as u8,
+            (LengthPrefix::U16, ByteOrder::Little) => {
+                buf[..2].copy_from_slice(&(msg_len as u16).to_le_bytes())
+            }
+            (LengthPrefix::U16, ByteOrder::Big) => {
+                buf[..2].copy_from_slice(&(msg_len as u16).to_be_bytes())
+            }
+            (LengthPrefix::U32, ByteOrder::Little) => {
+                buf[..4].copy_from_slice(&(msg_len as MsgLengthType).to_le_bytes())
+            }
+            (LengthPrefix::U32, ByteOrder::Big) => {
+                buf[..4].copy_from_slice(&(msg_len as MsgLengthType).to_be_bytes())
+            }
+            (LengthPrefix::Varint, _) => {
+                let header_len = self.header_len(msg_len);
+
+                for (i, b) in buf[..header_len].iter_mut().enumerate() {
+                    *b = (msg_len >> (7 * i)) as u8 & 0x7F;
+
+                    if i + 1 < header_len {
+                        *b |= 0x80;
+                    }
+                }
+            }
+        }
+    }
+
+    /// reads a prefix from the start of `buf`, returning the message length
+    /// and the length of the prefix itself or `None` if `buf` is too short
+    /// or doesn't hold a valid prefix
+    pub fn decode(self, order: ByteOrder, buf: &[u8]) -> Option<(usize, usize)> {
+        if self == LengthPrefix::Varint {
+            return Self::decode_varint(buf);
+        }
+
+        let header_len = self.header_len(0);
+        let header = buf.get(..header_len)?;
+
+        let msg_len = match (self, order) {
+            (LengthPrefix::U8, _) => header[0] as usize,
+            (LengthPrefix::U16, ByteOrder::Little) => {
+                u16::from_le_bytes(header.try_into().expect("byte slice conversion")) as usize
+            }
+            (LengthPrefix::U16, ByteOrder::Big) => {
+                u16::from_be_bytes(header.try_into().expect("byte slice conversion")) as usize
+            }
+            (LengthPrefix::U32, ByteOrder::Little) => stored_len(MsgLengthType::from_le_bytes(
+                header.try_into().expect("byte slice conversion"),
+            ))?,
+            (LengthPrefix::U32, ByteOrder::Big) => stored_len(MsgLengthType::from_be_bytes(
+                header.try_into().expect("byte slice conversion"),
+            ))?,
+            (LengthPrefix::Varint, _) => unreachable!(),
+        };
+
+        Some((msg_len, header_len))
+    }
+
+    fn decode_varint(buf: &[u8]) -> Option<(usize, usize)> {
+        let mut msg_len = 0;
+
+        for (i, &b) in buf.iter().take(MAX_VARINT_LEN).enumerate() {
+            msg_len |= ((b & 0x7F) as u64) << (7 * i);
+
+            if b & 0x80 == 0 {
+                // only the shortest encoding is valid, otherwise header_len
+                // wouldn't agree with what was actually on the page
+                if i > 0 && b == 0 {
+                    return None;
+                }
+
+                return Some((stored_len(msg_len)?, i + 1));
+            }
+        }
+
+        None
+    }
+
+    /// prefix that marks a padding frame instead of a message. it's all ones for
+    /// fixed width prefixes, which is never a valid length for any of them, and
+    /// a varint length way past [`DEFAULT_MAX_MSG_SIZE`]
+    pub const fn padding_sentinel(self) -> &'static [u8] {
+        match self {
+            LengthPrefix::U8 => &[0xFF],
+            LengthPrefix::U16 => &[0xFF; 2],
+            LengthPrefix::U32 => &[0xFF; 4],
+            LengthPrefix::Varint => &[0xFF, 0xFF, 0xFF, 0x7F],
+        }
+    }
+
+    // zero is reserved for "not set yet" in the info file
+    pub(crate) fn to_code(self) -> u8 {
+        match self {
+            LengthPrefix::U8 => 1,
+            LengthPrefix::U16 => 2,
+            LengthPrefix::U32 => 3,
+            LengthPrefix::Varint => 4,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<LengthPrefix> {
+        match code {
+            1 => Some(LengthPrefix::U8),
+            2 => Some(LengthPrefix::U16),
+            3 => Some(LengthPrefix::U32),
+            4 => Some(LengthPrefix::Varint),
+            _ => None,
+        }
+    }
+}
+
+/// written in front of every frame when sync markers are on. 0xFF and 0xFE never
+/// show up in utf-8, so text messages can't be mistaken for a frame start
+pub const SYNC_MARKER: [u8; 4] = [0xFF, b'S', b'Y', 0xFE];
+
+/// how messages are framed on a page
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameFormat {
+    pub prefix: LengthPrefix,
+    pub byte_order: ByteOrder,
+    /// whether every frame starts with [`SYNC_MARKER`]
+    pub sync_markers: bool,
+    /// whether every frame carries a `u64` key right in front of the message
+    pub keys: bool,
+    /// whether messages that don't fit on what's left of a page are split across
+    /// it, which takes a [`FrameKind`] byte after the length prefix of every frame
+    pub split: bool,
+    /// whether every message starts with a section of headers, see
+    /// [`FrameFormat::encode_headers`]. frames hold the section as part of the message,
+    /// receivers leave it out of the messages they hand out
+    pub headers: bool,
+}
+
+/// size of the message key in a frame, see [`FrameFormat::keys`]
+pub const KEY_LEN: usize = size_of::<u64>();
+
+/// size of the header count in front of a message, and of the lengths in front of
+/// every header key and value, see [`FrameFormat::headers`]
+pub const HEADER_LEN_LEN: usize = size_of::<u16>();
+
+/// what part of a message a frame holds, see [`FrameFormat::split`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameKind {
+    Whole,
+    /// the start of a message, fills up the rest of its page
+    Head,
+    /// the rest of a message, on a later page than its head. starts with the page
+    /// number and offset of the head frame as little endian `u64`s, see [`ORIGIN_LEN`]
+    Tail,
+}
+
+/// size of the head position in front of a [`FrameKind::Tail`]
+pub const ORIGIN_LEN: usize = 2 * size_of::<u64>();
+
+impl FrameKind {
+    const fn to_code(self) -> u8 {
+        match self {
+            FrameKind::Whole => 0,
+            FrameKind::Head => 1,
+            FrameKind::Tail => 2,
+        }
+    }
+
+    const fn from_code(code: u8) -> Option<FrameKind> {
+        match code {
+            0 => Some(FrameKind::Whole),
+            1 => Some(FrameKind::Head),
+            2 => Some(FrameKind::Tail),
+            _ => None,
+        }
+    }
+}
+
+impl FrameFormat {
+    /// biggest message a frame can hold
+    pub const fn max_msg_len(self) -> usize {
+        self.prefix.max_msg_len()
+    }
+
+    /// number of bytes in front of a `msg_len` long message
+    pub const fn header_len(self, msg_len: usize) -> usize {
+        let key_len = if self.keys { KEY_LEN } else { 0 };
+
+        self.marker_len() + self.prefix.header_len(msg_len) + self.kind_len() + key_len
+    }
+
+    const fn kind_len(self) -> usize {
+        if self.split {
+            size_of::<u8>()
+        } else {
+            0
+        }
+    }
+
+    const fn marker_len(self) -> usize {
+        if self.sync_markers {
+            SYNC_MARKER.len()
+        } else {
+            0
+        }
+    }
+
+    /// number of bytes a `msg_len` long message takes up on a page
+    pub const fn frame_len(self, msg_len: usize) -> usize {
+        self.header_len(msg_len) + msg_len
+    }
+
+    /// writes the header for a `msg_len` long message into the
+    /// start of `buf`, which has to be at least `header_len` long
+    pub fn encode(self, msg_len: usize, buf: &mut [u8]) {
+        self.encode_keyed(msg_len, 0, buf);
+    }
+
+    /// like [`FrameFormat::encode`] with a message key,
+    /// which is dropped if the format has none
+    pub fn encode_keyed(self, msg_len: usize, key: u64, buf: &mut [u8]) {
+        self.encode_fragment(msg_len, key, FrameKind::Whole, buf);
+    }
+
+    /// like [`FrameFormat::encode_keyed`] for a frame holding part of a message,
+    /// the kind is dropped unless the format [splits](FrameFormat::split) messages
+    pub fn encode_fragment(self, msg_len: usize, key: u64, kind: FrameKind, buf: &mut [u8]) {
+        let header_len = self.header_len(msg_len);
+
+        if self.split {
+            let key_len = if self.keys { KEY_LEN } else { 0 };
+            buf[header_len - key_len - 1] = kind.to_code();
+        }
+
+        if self.keys {
+            let key = match self.byte_order {
+                ByteOrder::Little => key.to_le_bytes(),
+                ByteOrder::Big => key.to_be_bytes(),
+            };
+            buf[header_len - KEY_LEN..header_len].copy_from_slice(&key);
+        }
+
+        let buf = if self.sync_markers {
+            buf[..SYNC_MARKER.len()].copy_from_slice(&SYNC_MARKER);
+            &mut buf[SYNC_MARKER.len()..]
+        } else {
+            buf
+        };
+
+        self.prefix.encode(msg_len, self.byte_order, buf);
+    }
+
+    /// reads a header from the start of `buf`, returning the message length and the
+    /// length of the header itself or `None` if `buf` doesn't start with a valid one
+    pub fn decode(self, buf: &[u8]) -> Option<(usize, usize)> {
+        let (msg_len, header_len) = self.decode_prefix(buf)?;
+        let key_len = if self.keys { KEY_LEN } else { 0 };
+
+        Some((msg_len, header_len + self.kind_len() + key_len))
+    }
+
+    // the marker and length prefix, without the kind or key
+    fn decode_prefix(self, buf: &[u8]) -> Option<(usize, usize)> {
+        if !self.sync_markers {
+            return self.prefix.decode(self.byte_order, buf);
+        }
+
+        let prefix = buf.strip_prefix(&SYNC_MARKER)?;
+        let (msg_len, prefix_len) = self.prefix.decode(self.byte_order, prefix)?;
+
+        Some((msg_len, SYNC_MARKER.len() + prefix_len))
+    }
+
+    /// reads the message key of the frame at the start of `buf`, `None` if there's no
+    /// valid header. frames without keys all have key 0
+    pub fn decode_key(self, buf: &[u8]) -> Option<u64> {
+        let (_, prefix_len) = self.decode_prefix(buf)?;
+
+        if !self.keys {
+            return Some(0);
+        }
+
+        let key_start = prefix_len + self.kind_len();
+        let key = buf.get(key_start..key_start + KEY_LEN)?;
+        let key = key.try_into().expect("byte slice conversion");
+
+        Some(match self.byte_order {
+            ByteOrder::Little => u64::from_le_bytes(key),
+            ByteOrder::Big => u64::from_be_bytes(key),
+        })
+    }
+
+    /// what part of a message the frame at the start of `buf` holds, `None` if there's
+    /// no valid header. without [splitting](FrameFormat::split) every frame is whole
+    pub fn decode_kind(self, buf: &[u8]) -> Option<FrameKind> {
+        let (_, prefix_len) = self.decode_prefix(buf)?;
+
+        if !self.split {
+            return Some(FrameKind::Whole);
+        }
+
+        FrameKind::from_code(*buf.get(prefix_len)?)
+    }
+
+    /// appends the headers section holding `headers` to `buf`: their count, then every
+    /// key and value each behind its own length, all as `u16`s. everything has to fit
+    /// in a `u16`, it's up to the caller to check
+    pub fn encode_headers<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        self,
+        headers: &[(K, V)],
+        buf: &mut Vec<u8>,
+    ) {
+        let put_u16 = |buf: &mut Vec<u8>, n: usize| {
+            let n = n as u16;
+            buf.extend_from_slice(&match self.byte_order {
+                ByteOrder::Little => n.to_le_bytes(),
+                ByteOrder::Big => n.to_be_bytes(),
+            });
+        };
+
+        put_u16(buf, headers.len());
+        for (key, value) in headers {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            put_u16(buf, key.len());
+            buf.extend_from_slice(key);
+            put_u16(buf, value.len());
+            buf.extend_from_slice(value);
+        }
+    }
+
+    /// splits a message written with [`FrameFormat::headers`] into its headers section
+    /// and the message itself, `None` if the section runs past the end of `msg`
+    pub fn split_headers(self, msg: &[u8]) -> Option<(&[u8], &[u8])> {
+        let mut at = 0;
+        let count = self.take_u16(msg, &mut at)?;
+
+        for _ in 0..2 * count {
+            let len = self.take_u16(msg, &mut at)?;
+            at = at.checked_add(len).filter(|&end| end <= msg.len())?;
+        }
+
+        Some(msg.split_at(at))
+    }
+
+    /// the message without its headers section if the format has
+    /// [headers](FrameFormat::headers), `None` if the section doesn't fit
+    pub fn strip_headers(self, msg: &[u8]) -> Option<&[u8]> {
+        match self.headers {
+            true => self.split_headers(msg).map(|(_, msg)| msg),
+            false => Some(msg),
+        }
+    }
+
+    /// the keys and values in a headers section returned by [`FrameFormat::split_headers`]
+    pub fn decode_headers(self, section: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut at = 0;
+        let count = self.take_u16(section, &mut at).unwrap_or_default();
+
+        let take = |at: &mut usize| {
+            let len = self.take_u16(section, at)?;
+            let field = section.get(*at..*at + len)?;
+            *at += len;
+            Some(field)
+        };
+
+        (0..count)
+            .map_while(|_| Some((take(&mut at)?, take(&mut at)?)))
+            .collect()
+    }
+
+    // reads the u16 at `at` and moves past it
+    fn take_u16(self, buf: &[u8], at: &mut usize) -> Option<usize> {
+        let bytes = buf.get(*at..*at + HEADER_LEN_LEN)?;
+        let bytes = bytes.try_into().expect("byte slice conversion");
+        *at += HEADER_LEN_LEN;
+
+        Some(match self.byte_order {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        } as usize)
+    }
+
+    /// smallest padding frame there is: the sentinel prefix followed
+    /// by the little endian `u32` length of the whole padding frame
+    pub const fn min_padding_len(self) -> usize {
+        self.marker_len() + self.prefix.padding_sentinel().len() + size_of::<u32>()
+    }
+
+    /// how much a push reserves for a `frame_len` long frame. a frame too short to pad
+    /// over gets room for padding behind it, so a push that gives up its reservation
+    /// never leaves behind anything readers would take for a message
+    pub const fn reserved_len(self, frame_len: usize) -> usize {
+        match frame_len < self.min_padding_len() {
+            true => frame_len + self.min_padding_len(),
+            false => frame_len,
+        }
+    }
+
+    /// writes a padding frame covering all of `buf`, which has
+    /// to be at least [`FrameFormat::min_padding_len`] long
+    pub fn encode_padding(self, buf: &mut [u8]) {
+        let padding_len = buf.len() as u32;
+
+        let buf = if self.sync_markers {
+            buf[..SYNC_MARKER.len()].copy_from_slice(&SYNC_MARKER);
+            &mut buf[SYNC_MARKER.len()..]
+        } else {
+            buf
+        };
+
+        let sentinel = self.prefix.padding_sentinel();
+        buf[..sentinel.len()].copy_from_slice(sentinel);
+        buf[sentinel.len()..sentinel.len() + size_of::<u32>()]
+            .copy_from_slice(&padding_len.to_le_bytes());
+    }
+
+    /// the length of the padding frame at the start of `buf`,
+    /// `None` if there's no (complete) padding header
+    pub fn decode_padding(self, buf: &[u8]) -> Option<usize> {
+        let buf = match self.sync_markers {
+            true => buf.strip_prefix(&SYNC_MARKER)?,
+            false => buf,
+        };
+
+        let len = buf.strip_prefix(self.prefix.padding_sentinel())?;
+        let len = len.get(..size_of::<u32>())?;
+
+        stored_len(u32::from_le_bytes(
+            len.try_into().expect("byte slice conversion"),
+        ))
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     WriteIdxLockOverflow,
     MsgTooLong,
+    FrameOutOfBounds,
+    WriterStalled,
+    Closed,
 }
 
 impl Display for Error {
@@ -36,164 +625,909 @@ impl Display for Error {
     }
 }
 
+/// the message of the frame starting at `start` in `buf`, which has to end where the
+/// committed frames end (e.g. the output of
+/// [`DiskRing::pop_batch_bytes`](crate::ringbuf::DiskRing::pop_batch_bytes)).
+///
+/// returns `None` if the header is invalid or the frame runs past the end of `buf`,
+/// never reading anything outside of it.
+pub fn parse_frame(buf: &[u8], start: usize, format: FrameFormat) -> Option<&[u8]> {
+    let (msg_len, header_len) = format.decode(buf.get(start..)?)?;
+
+    let msg_start = start + header_len;
+    buf.get(msg_start..msg_start.checked_add(msg_len)?)
+}
+
+/// what [`QPage::verify`] found on a page
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PageReport {
+    /// frames holding (part of) a message
+    pub frames: usize,
+    /// padding frames left by reservations that didn't use all of their space
+    pub padding_frames: usize,
+    /// where in the page the walk over the frames stopped
+    pub end: usize,
+    pub anomalies: Vec<PageAnomaly>,
+}
+
+impl PageReport {
+    pub fn is_healthy(&self) -> bool {
+        self.anomalies.is_empty()
+    }
+}
+
+/// something wrong with a page, offsets are from the start of the message area
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PageAnomaly {
+    /// the page file is only `len` bytes, shorter than a whole page
+    Truncated { len: u64 },
+    /// a frame whose header is invalid or that runs past what writers committed
+    FrameOutOfBounds { offset: usize },
+    /// a padding frame shorter than the smallest one there is or that runs past
+    /// what writers committed
+    BadPadding { offset: usize },
+    /// the page done marker isn't right after the last frame
+    MisplacedMarker { marker: usize, frames_end: usize },
+}
+
 pub enum PopResult<'a> {
     Msg(&'a [u8]),
+    /// a padding frame of this many bytes left by an aborted or shrunk
+    /// reservation, there's nothing to read in it
+    Padding(usize),
     NoNewMsgs,
     PageDone,
 }
 
 pub enum PushResult {
-    BytesWritten(usize),
+    BytesWritten {
+        written: usize,
+        /// bytes left on the page after this frame as of when it was reserved,
+        /// concurrent writers may have taken more of it since
+        page_remaining: usize,
+    },
     PageFull,
+    /// only the first `head_len` bytes of the message fit and were written to the end
+    /// of the page, in a frame starting at `start_idx`. the rest still has to be pushed
+    /// as a [`FrameKind::Tail`] on a later page
+    Split {
+        written: usize,
+        head_len: usize,
+        start_idx: usize,
+    },
+}
+
+/// outcome of [`QPage::reserve_or_cross`]
+enum Reserved {
+    At(usize),
+    /// the page ends inside the reservation. the writer is still registered and
+    /// everything from here to the end of the page is its alone
+    Crossing(usize),
+    /// the page was already full, the writer is released again
+    Full,
+}
+
+/// a writer registered on a page by [`QPage::reserve_guarded`]. dropping it before
+/// the frame is [finished](WriteGuard::finish) abandons the frame, so a writer that
+/// panics mid-push can't leave the page's readers waiting on it forever.
+pub(crate) struct WriteGuard<'a> {
+    qpage: &'a QPage,
+    start_idx: usize,
+    frame_len: usize,
+    // see FrameFormat::reserved_len
+    reserved_len: usize,
+    format: FrameFormat,
+    written: bool,
+}
+
+impl WriteGuard<'_> {
+    fn new(
+        qpage: &QPage,
+        start_idx: usize,
+        frame_len: usize,
+        format: FrameFormat,
+    ) -> WriteGuard<'_> {
+        WriteGuard {
+            qpage,
+            start_idx,
+            frame_len,
+            reserved_len: format.reserved_len(frame_len),
+            format,
+            written: false,
+        }
+    }
+
+    /// releases the writer once its frame is written
+    pub(crate) fn finish(mut self) {
+        if self.reserved_len > self.frame_len {
+            self.qpage.give_back(
+                self.start_idx,
+                self.reserved_len,
+                self.frame_len,
+                self.format,
+            );
+        }
+
+        self.written = true;
+    }
+}
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        if !self.written {
+            self.qpage
+                .abort_reserved(self.start_idx, self.reserved_len, self.format);
+        }
+
+        self.qpage.release();
+    }
 }
 
 impl QPage {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<MmapMutWrapper<QPage>, std::io::Error> {
-        let f = std::fs::File::options()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(path)?;
+    /// maps an already opened page file, which has to be opened for reading
+    /// and writing. the file can be closed afterwards, the mapping stays valid
+    pub fn from_file(f: File) -> Result<MmapMutWrapper<QPage>, std::io::Error> {
+        Self::allocate(&f)?;
+
+        let m = unsafe { memmap2::MmapMut::map_mut(&f)? };
+
+        Ok(unsafe { MmapMutWrapper::<QPage>::new(m) })
+    }
 
-        let _ = f.set_len(std::mem::size_of::<QPage>() as u64);
+    /// like [`QPage::from_file`], but advises the kernel to back the mapping with
+    /// transparent huge pages. also returns whether the kernel took the advice, a
+    /// kernel without huge page support refuses it and the mapping stays as it is.
+    #[cfg(target_os = "linux")]
+    pub fn from_file_huge(f: File) -> Result<(MmapMutWrapper<QPage>, bool), std::io::Error> {
+        Self::allocate(&f)?;
 
         let m = unsafe { memmap2::MmapMut::map_mut(&f)? };
+        let huge = m.advise(memmap2::Advice::HugePage).is_ok();
+
+        Ok((unsafe { MmapMutWrapper::<QPage>::new(m) }, huge))
+    }
+
+    /// maps a page file opened only for reading, without resizing it the way
+    /// [`QPage::from_file`] does. the file has to hold a whole page already. the
+    /// mapping is private, it follows what writers do to the file but must never be
+    /// written to itself
+    pub fn map_read_only(f: &File) -> Result<MmapMutWrapper<QPage>, std::io::Error> {
+        let m = unsafe { memmap2::MmapOptions::new().map_copy_read_only(f)? };
+        let m = m.make_mut()?;
 
         Ok(unsafe { MmapMutWrapper::<QPage>::new(m) })
     }
 
-    fn get_write_idx_spin(&self, start_byte: usize) -> usize {
+    /// grows a freshly created page file to its full size without mapping it
+    pub fn allocate(f: &File) -> Result<(), std::io::Error> {
+        extend_file(f, PAGE_FILE_SIZE as u64)
+    }
+
+    /// spins until no writer holds a reservation on the page.
+    ///
+    /// with a `stall_timeout`, gives up with [`Error::WriterStalled`] once `write_idx_lock`
+    /// hasn't changed at all for that long. live writers keep moving the index (reserving
+    /// or releasing), so a frozen index with writers still registered means one of them
+    /// died mid-push.
+    fn get_write_idx_spin(
+        &self,
+        start_byte: usize,
+        stall_timeout: Option<Duration>,
+    ) -> Result<usize, Error> {
         let end_byte = self.last_safe_write_idx.load(Ordering::Relaxed);
 
         let end_byte = match start_byte.cmp(&end_byte) {
-            cmp::Ordering::Greater | cmp::Ordering::Equal => loop {
-                let end_byte = self.write_idx_lock.load(Ordering::Acquire);
+            cmp::Ordering::Greater | cmp::Ordering::Equal => {
+                let mut stall = stall_timeout.map(|timeout| (timeout, Instant::now(), None));
 
-                if (end_byte & !QUEUE_MAGIC_MASK) == 0 {
-                    let _ = self
-                        .last_safe_write_idx
-                        .fetch_max(end_byte, Ordering::Relaxed);
+                loop {
+                    let end_byte = self.write_idx_lock.load(Ordering::Acquire);
 
-                    break end_byte;
-                }
+                    if (end_byte & WRITERS_MASK) == 0 {
+                        let end_byte = end_byte & QUEUE_MAGIC_MASK;
+                        let _ = self
+                            .last_safe_write_idx
+                            .fetch_max(end_byte, Ordering::Relaxed);
+
+                        break end_byte;
+                    }
+
+                    if let Some((timeout, since, last_seen)) = &mut stall {
+                        if *last_seen != Some(end_byte) {
+                            *last_seen = Some(end_byte);
+                            *since = Instant::now();
+                        } else if since.elapsed() >= *timeout {
+                            return Err(Error::WriterStalled);
+                        }
+                    }
 
-                core::hint::spin_loop();
-            },
+                    core::hint::spin_loop();
+                }
+            }
             _ => end_byte,
         };
 
-        end_byte.min(DEFAULT_QUEUE_SIZE)
+        Ok(end_byte.min(DEFAULT_QUEUE_SIZE))
+    }
+
+    /// whether writers have committed anything past `start_byte`. unlike
+    /// [`QPage::try_pop`] this never waits on in-flight writers, a page with
+    /// a push still in progress just reads as having nothing new yet
+    pub fn has_committed(&self, start_byte: usize) -> bool {
+        if start_byte < self.last_safe_write_idx.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        let end_byte = self.write_idx_lock.load(Ordering::Acquire);
+
+        (end_byte & WRITERS_MASK) == 0 && start_byte < (end_byte & QUEUE_MAGIC_MASK)
+    }
+
+    /// the end of what's safe to read on the page right now. unlike
+    /// [`QPage::committed_end`] this never waits on in-flight writers, so it
+    /// can lag behind what they're about to commit.
+    pub fn committed_head(&self) -> usize {
+        let idx = self.write_idx_lock.load(Ordering::Acquire);
+        let end_byte = match idx & WRITERS_MASK {
+            0 => idx & QUEUE_MAGIC_MASK,
+            _ => self.last_safe_write_idx.load(Ordering::Relaxed),
+        }
+        .min(DEFAULT_QUEUE_SIZE);
+
+        match self.done_idx.load(Ordering::Acquire) {
+            0 => end_byte,
+            done => end_byte.min(done - 1),
+        }
+    }
+
+    /// walks every committed frame on the page and checks that it stays in bounds and
+    /// that the page done marker, if there is one, comes right after the last frame.
+    /// only reads the page and never waits on writers in flight, whatever they haven't
+    /// committed yet isn't looked at.
+    pub fn verify(&self, format: FrameFormat) -> PageReport {
+        let end_byte = self.committed_head();
+        let committed = &self.buf[..end_byte];
+        let mut report = PageReport::default();
+        let mut start_byte = 0;
+
+        while start_byte < end_byte {
+            if let Some(padding_len) = format.decode_padding(&committed[start_byte..]) {
+                if padding_len >= format.min_padding_len() && padding_len <= end_byte - start_byte {
+                    report.padding_frames += 1;
+                    start_byte += padding_len;
+                    continue;
+                }
+
+                report
+                    .anomalies
+                    .push(PageAnomaly::BadPadding { offset: start_byte });
+            } else if let Some(msg) = parse_frame(committed, start_byte, format) {
+                report.frames += 1;
+                start_byte += format.frame_len(msg.len());
+                continue;
+            } else {
+                report
+                    .anomalies
+                    .push(PageAnomaly::FrameOutOfBounds { offset: start_byte });
+            }
+
+            // only sync markers tell where the next frame starts
+            match format.sync_markers {
+                true => match self.next_synced_frame(start_byte + 1, end_byte, format) {
+                    Some(next) => start_byte = next,
+                    None => break,
+                },
+                false => break,
+            }
+        }
+        report.end = start_byte;
+
+        // with writers in flight the frames don't reach the marker yet
+        let idle = self.write_idx_lock.load(Ordering::Acquire) & WRITERS_MASK == 0;
+        let done = self.done_idx.load(Ordering::Acquire);
+        if done != 0 && idle && report.anomalies.is_empty() {
+            let marker = done - 1;
+
+            if marker != start_byte || self.buf[marker] != 0xFD {
+                report.anomalies.push(PageAnomaly::MisplacedMarker {
+                    marker,
+                    frames_end: start_byte,
+                });
+            }
+        }
+
+        report
+    }
+
+    /// counts the messages committed from `start_byte` on, stopping at `limit`. like
+    /// [`QPage::has_committed`] this never waits on in-flight writers and only counts
+    /// what's already safe to read. also returns whether that's everything the page
+    /// will ever hold.
+    pub fn count_committed(
+        &self,
+        start_byte: usize,
+        limit: usize,
+        format: FrameFormat,
+    ) -> (usize, bool) {
+        let end_byte = self.committed_head();
+        let done = self.done_idx.load(Ordering::Acquire);
+
+        let committed = &self.buf[..end_byte];
+        let mut start_byte = start_byte;
+        let mut count = 0;
+
+        while count < limit && start_byte < end_byte {
+            if let Some(padding_len) = format.decode_padding(&committed[start_byte..]) {
+                if padding_len < format.min_padding_len() {
+                    break;
+                }
+
+                start_byte += padding_len;
+                continue;
+            }
+
+            let Some(msg) = parse_frame(committed, start_byte, format) else {
+                break;
+            };
+
+            // the message only counts once its tail is there
+            if format.decode_kind(&committed[start_byte..]) != Some(FrameKind::Head) {
+                count += 1;
+            }
+            start_byte += format.frame_len(msg.len());
+        }
+
+        (count, done != 0 && start_byte + 1 >= done)
+    }
+
+    /// the end of what writers have committed past `start_byte`, which always
+    /// lands on a frame boundary
+    pub fn committed_end(
+        &self,
+        start_byte: usize,
+        stall_timeout: Option<Duration>,
+    ) -> Result<usize, Error> {
+        let end_byte = self.get_write_idx_spin(start_byte, stall_timeout)?;
+
+        // a full page ends at its marker, writers that overflowed
+        // it leave the index somewhere past that
+        match self.done_idx.load(Ordering::Acquire) {
+            0 => Ok(end_byte),
+            done => Ok(end_byte.min(done - 1)),
+        }
+    }
+
+    /// the end of everything writers committed to the page, after waiting for
+    /// the ones still in flight
+    pub fn tail(&self, stall_timeout: Option<Duration>) -> Result<usize, Error> {
+        // starting at the very end always goes through the spin instead
+        // of trusting a possibly stale last_safe_write_idx
+        self.committed_end(DEFAULT_QUEUE_SIZE, stall_timeout)
     }
 
-    pub fn try_pop(&self, start_byte: usize) -> Result<PopResult, Error> {
-        let end_byte = self.get_write_idx_spin(start_byte);
+    /// key of the committed frame starting at `start_byte`, see [`FrameFormat::keys`]
+    pub fn frame_key(&self, start_byte: usize, format: FrameFormat) -> Option<u64> {
+        format.decode_key(self.buf.get(start_byte..)?)
+    }
+
+    /// message of the committed frame starting at `start_byte`, the same one
+    /// [`QPage::try_pop`] returned for it
+    pub fn frame_msg(&self, start_byte: usize, format: FrameFormat) -> Option<&[u8]> {
+        parse_frame(&self.buf, start_byte, format)
+    }
 
+    /// headers section of the committed frame starting at `start_byte`, see
+    /// [`FrameFormat::split_headers`]
+    pub fn frame_headers(&self, start_byte: usize, format: FrameFormat) -> Option<&[u8]> {
+        let msg = self.frame_msg(start_byte, format)?;
+
+        format.split_headers(msg).map(|(section, _)| section)
+    }
+
+    /// see [`FrameFormat::decode_kind`]
+    pub fn frame_kind(&self, start_byte: usize, format: FrameFormat) -> Option<FrameKind> {
+        format.decode_kind(self.buf.get(start_byte..)?)
+    }
+
+    /// start of the first frame in `start_byte..end_byte` that has a sync marker and
+    /// fits before `end_byte`, where `end_byte` is the [`QPage::committed_end`]
+    pub fn next_synced_frame(
+        &self,
+        start_byte: usize,
+        end_byte: usize,
+        format: FrameFormat,
+    ) -> Option<usize> {
+        let committed = &self.buf[..end_byte];
+
+        committed
+            .get(start_byte..)?
+            .windows(SYNC_MARKER.len())
+            .enumerate()
+            .filter(|(_, window)| *window == SYNC_MARKER)
+            .map(|(i, _)| start_byte + i)
+            .find(|&idx| parse_frame(committed, idx, format).is_some())
+    }
+
+    pub fn try_pop(
+        &self,
+        start_byte: usize,
+        stall_timeout: Option<Duration>,
+        format: FrameFormat,
+    ) -> Result<PopResult<'_>, Error> {
+        let end_byte = self.get_write_idx_spin(start_byte, stall_timeout)?;
+
+        // writers never leave the index behind a frame boundary, so only a corrupt
+        // frame or position could have put the reader past it
         if end_byte < start_byte {
-            unreachable!();
+            return Err(Error::FrameOutOfBounds);
         }
 
         if end_byte == start_byte {
             return Ok(PopResult::NoNewMsgs);
         }
 
-        if self.buf[start_byte] == 0xFD {
+        // nothing valid comes after the marker, only a corrupt frame
+        // could have sent a reader past it
+        let done = self.done_idx.load(Ordering::Acquire);
+        if done != 0 && start_byte + 1 >= done {
             return Ok(PopResult::PageDone);
         }
 
-        let msg_len = MsgLengthType::from_le_bytes(
-            self.buf[start_byte..start_byte + size_of::<MsgLengthType>()]
-                .try_into()
-                .expect("byte slice conversion"),
-        );
+        if let Some(padding_len) = format.decode_padding(&self.buf[start_byte..end_byte]) {
+            if padding_len < format.min_padding_len()
+                || start_byte
+                    .checked_add(padding_len)
+                    .is_none_or(|padding_end| padding_end > end_byte)
+            {
+                return Err(Error::FrameOutOfBounds);
+            }
+
+            return Ok(PopResult::Padding(padding_len));
+        }
+
+        // a torn or corrupt length prefix must never send us
+        // past what the writers have actually committed
+        match parse_frame(&self.buf[..end_byte], start_byte, format) {
+            Some(msg) => Ok(PopResult::Msg(msg)),
+            None => Err(Error::FrameOutOfBounds),
+        }
+    }
+
+    /// marks `idx` as the end of the page. only the lowest index
+    /// any writer overflowed at counts, the rest is never read
+    fn mark_done(&self, idx: usize) {
+        if idx >= DEFAULT_QUEUE_SIZE {
+            return;
+        }
+
+        // the marker byte isn't what readers go by anymore
+        // but it makes page files easier to make sense of
+        unsafe {
+            let super_scary_mut_buf = self.buf.as_ptr().cast_mut();
+            *super_scary_mut_buf.add(idx) = 0xFD;
+        }
+
+        let _ = self
+            .done_idx
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |done| {
+                (done == 0 || idx + 1 < done).then_some(idx + 1)
+            });
+    }
+
+    /// `key` is dropped unless the format has [message keys](FrameFormat::keys)
+    pub fn try_push(&self, msg: &[u8], key: u64, format: FrameFormat) -> Result<PushResult, Error> {
+        if msg.len() > format.max_msg_len() {
+            return Err(Error::MsgTooLong);
+        }
+
+        let frame_len = format.frame_len(msg.len());
+
+        let start_idx = match self.reserve_or_cross(format.reserved_len(frame_len))? {
+            Reserved::At(start_idx) => start_idx,
+            Reserved::Crossing(start_idx) => return Ok(self.cross(start_idx, msg, key, format)),
+            Reserved::Full => return Ok(PushResult::PageFull),
+        };
+        let guard = WriteGuard::new(self, start_idx, frame_len, format);
+
+        self.write_frame(start_idx, msg, key, format);
+        guard.finish();
 
-        let start_byte = start_byte + size_of::<MsgLengthType>();
-        let end_byte = start_byte + msg_len as usize;
+        Ok(PushResult::BytesWritten {
+            written: frame_len,
+            page_remaining: DEFAULT_QUEUE_SIZE - (start_idx + frame_len),
+        })
+    }
+
+    /// registers a writer and reserves `frame_len` bytes for it, returning where they
+    /// start. readers wait for the writer to [`QPage::release`] the page before reading
+    /// anything past what was already committed.
+    ///
+    /// `None` means the page is full, it's been marked done and the writer is already
+    /// released again.
+    pub fn reserve(&self, frame_len: usize) -> Result<Option<usize>, Error> {
+        match self.reserve_or_cross(frame_len)? {
+            Reserved::At(start_idx) => Ok(Some(start_idx)),
+            Reserved::Crossing(start_idx) => {
+                // adding marker that queue is full
+                self.mark_done(start_idx);
+
+                // subtracting number of writers
+                self.release();
 
-        Ok(PopResult::Msg(&self.buf[start_byte..end_byte]))
+                Ok(None)
+            }
+            Reserved::Full => Ok(None),
+        }
     }
 
-    pub fn try_push_raw(&mut self, msgs: &[u8]) -> Result<PushResult, Error> {
+    // like reserve, but leaves the page to the writer the end of the page fell to
+    fn reserve_or_cross(&self, frame_len: usize) -> Result<Reserved, Error> {
         let start_idx = self
             .write_idx_lock
-            .fetch_add(QUEUE_MAGIC_NUM + msgs.len(), Ordering::Relaxed);
+            .fetch_add(QUEUE_MAGIC_NUM + frame_len, Ordering::Relaxed);
 
-        if ((start_idx + QUEUE_MAGIC_NUM) & !QUEUE_MAGIC_MASK) == 0 {
+        if ((start_idx + QUEUE_MAGIC_NUM) & WRITERS_MASK) == 0 {
             return Err(Error::WriteIdxLockOverflow);
         }
 
+        if start_idx & PAGE_CLOSED != 0 {
+            self.write_idx_lock
+                .fetch_sub(QUEUE_MAGIC_NUM + frame_len, Ordering::Release);
+
+            return Err(Error::Closed);
+        }
+
         let start_idx = start_idx & QUEUE_MAGIC_MASK;
 
         // checking if the queue has enough space
-        if start_idx + msgs.len() >= DEFAULT_QUEUE_SIZE - 1 {
-            // adding marker that queue is full
-            // this only has to happen once
-            if start_idx < DEFAULT_QUEUE_SIZE {
-                self.buf[start_idx] = 0xFD;
+        if start_idx + frame_len >= PAGE_END {
+            // only one writer can straddle the end
+            if start_idx < PAGE_END {
+                return Ok(Reserved::Crossing(start_idx));
             }
 
-            // subtracting number of writers
-            self.write_idx_lock
-                .fetch_sub(QUEUE_MAGIC_NUM, Ordering::Release);
+            self.mark_done(start_idx);
+            self.release();
 
-            return Ok(PushResult::PageFull);
+            return Ok(Reserved::Full);
         }
 
-        self.buf[start_idx..start_idx + msgs.len()].copy_from_slice(msgs);
+        Ok(Reserved::At(start_idx))
+    }
+
+    // writes as much of `msg` as fits between `start_idx` and the end of the page as a
+    // head frame if the format splits messages, ends the page and releases the writer
+    fn cross(&self, start_idx: usize, msg: &[u8], key: u64, format: FrameFormat) -> PushResult {
+        let room = PAGE_END - start_idx;
+        let head_len = room
+            .saturating_sub(format.header_len(room))
+            .min(msg.len().saturating_sub(1));
 
-        self.write_idx_lock
-            .fetch_sub(QUEUE_MAGIC_NUM, Ordering::Release);
+        // the tail has to fit the head's position on top of the rest of the message
+        if !format.split || head_len <= ORIGIN_LEN {
+            self.mark_done(start_idx);
+            self.release();
+
+            return PushResult::PageFull;
+        }
+
+        let written = format.frame_len(head_len);
+        self.write_fragment(start_idx, &msg[..head_len], key, FrameKind::Head, format);
+        self.mark_done(start_idx + written);
+        self.release();
 
-        Ok(PushResult::BytesWritten(msgs.len()))
+        PushResult::Split {
+            written,
+            head_len,
+            start_idx,
+        }
     }
 
-    pub fn try_push(&self, msg: &[u8]) -> Result<PushResult, Error> {
-        if msg.len() > DEFAULT_MAX_MSG_SIZE {
+    /// pushes the `tail` of a message whose head didn't fit on the page before, never
+    /// splitting it any further. `origin` is the page and offset of the head frame
+    pub fn try_push_tail(
+        &self,
+        origin: (usize, usize),
+        tail: &[u8],
+        key: u64,
+        format: FrameFormat,
+    ) -> Result<PushResult, Error> {
+        let msg_len = ORIGIN_LEN + tail.len();
+        if msg_len > format.max_msg_len() {
             return Err(Error::MsgTooLong);
         }
 
-        let start_idx = self.write_idx_lock.fetch_add(
-            QUEUE_MAGIC_NUM + size_of::<MsgLengthType>() + msg.len(),
-            Ordering::Relaxed,
+        let Some(guard) = self.reserve_guarded(msg_len, format)? else {
+            return Ok(PushResult::PageFull);
+        };
+        let start_idx = guard.start_idx;
+        let frame_len = format.frame_len(msg_len);
+
+        let frame = self.reserved_mut(start_idx, frame_len);
+        format.encode_fragment(msg_len, key, FrameKind::Tail, frame);
+        let msg = &mut frame[frame_len - msg_len..];
+        msg[..8].copy_from_slice(&(origin.0 as u64).to_le_bytes());
+        msg[8..ORIGIN_LEN].copy_from_slice(&(origin.1 as u64).to_le_bytes());
+        msg[ORIGIN_LEN..].copy_from_slice(tail);
+        guard.finish();
+
+        Ok(PushResult::BytesWritten {
+            written: frame_len,
+            page_remaining: DEFAULT_QUEUE_SIZE - (start_idx + frame_len),
+        })
+    }
+
+    /// marks the page done wherever writers got to, so every push from here on
+    /// goes to the next page. frames that are already reserved still get written
+    pub fn seal(&self) -> Result<(), Error> {
+        // nothing fits a whole page, so this always ends up marking it done
+        match self.reserve(DEFAULT_QUEUE_SIZE)? {
+            None => Ok(()),
+            Some(_) => unreachable!("reserved a whole page"),
+        }
+    }
+
+    /// like [`QPage::reserve`] for the frame of a `msg_len` long message, but the writer
+    /// is released when the returned guard is dropped, even while unwinding from a panic
+    pub(crate) fn reserve_guarded(
+        &self,
+        msg_len: usize,
+        format: FrameFormat,
+    ) -> Result<Option<WriteGuard<'_>>, Error> {
+        let frame_len = format.frame_len(msg_len);
+        let Some(start_idx) = self.reserve(format.reserved_len(frame_len))? else {
+            return Ok(None);
+        };
+
+        Ok(Some(WriteGuard::new(self, start_idx, frame_len, format)))
+    }
+
+    /// writes the frame for `msg` at `start_idx`, which has to be the start of a
+    /// range [`QPage::reserve`]d for exactly that frame
+    pub fn write_frame(&self, start_idx: usize, msg: &[u8], key: u64, format: FrameFormat) {
+        self.write_fragment(start_idx, msg, key, FrameKind::Whole, format);
+    }
+
+    fn write_fragment(
+        &self,
+        start_idx: usize,
+        msg: &[u8],
+        key: u64,
+        kind: FrameKind,
+        format: FrameFormat,
+    ) {
+        let header_len = format.header_len(msg.len());
+        let frame_len = header_len + msg.len();
+
+        let (header, payload) = self
+            .reserved_mut(start_idx, frame_len)
+            .split_at_mut(header_len);
+        format.encode_fragment(msg.len(), key, kind, header);
+        payload.copy_from_slice(msg);
+    }
+
+    /// mutable view of `len` bytes at `start_idx`, which have to be
+    /// part of a range the caller [`QPage::reserve`]d
+    ///
+    /// every write to the message area goes through here, and it's only sound because
+    /// of how reservations work, not because of anything the borrow checker sees:
+    /// bumping `write_idx_lock` hands each writer a range of the buffer no other writer
+    /// gets, and receivers don't read past what was committed before the writer
+    /// [`QPage::release`]s the page. so for as long as the caller holds its reservation
+    /// nothing else reads or writes these bytes. only the range itself is turned into
+    /// a `&mut`, never the rest of the buffer other threads may be using meanwhile.
+    #[allow(clippy::mut_from_ref)]
+    pub fn reserved_mut(&self, start_idx: usize, len: usize) -> &mut [u8] {
+        debug_assert!(
+            start_idx.checked_add(len).is_some(),
+            "reserved range at {start_idx} of {len} bytes wraps around"
+        );
+        debug_assert!(
+            start_idx + len <= self.buf.len(),
+            "reserved range {start_idx}..{} is past the end of the page",
+            start_idx + len
         );
 
-        if ((start_idx + QUEUE_MAGIC_NUM) & !QUEUE_MAGIC_MASK) == 0 {
-            return Err(Error::WriteIdxLockOverflow);
+        unsafe { slice::from_raw_parts_mut(self.buf.as_ptr().add(start_idx).cast_mut(), len) }
+    }
+
+    /// finishes a reservation of `reserved_len` bytes at `start_idx` whose message area
+    /// (starting after the header for a `max_len` long message) holds an `actual_len`
+    /// long message. everything past the frame is handed back, or padded over when
+    /// someone else reserved after it. the writer still has to be released afterwards.
+    ///
+    /// the reservation has to be at least [`FrameFormat::min_padding_len`] longer than
+    /// the frame for `max_len`.
+    pub fn commit_reserved(
+        &self,
+        start_idx: usize,
+        reserved_len: usize,
+        max_len: usize,
+        actual_len: usize,
+        format: FrameFormat,
+    ) {
+        let reserved = self.reserved_mut(start_idx, reserved_len);
+
+        // a varint header can get shorter with the length
+        let max_header_len = format.header_len(max_len);
+        let header_len = format.header_len(actual_len);
+        if header_len < max_header_len {
+            reserved.copy_within(max_header_len..max_header_len + actual_len, header_len);
         }
 
-        let start_idx = start_idx & QUEUE_MAGIC_MASK;
+        format.encode(actual_len, reserved);
 
-        // checking if the queue has enough space
-        if start_idx + size_of::<MsgLengthType>() + msg.len() >= DEFAULT_QUEUE_SIZE - 1 {
-            // adding marker that queue is full
-            // this only has to happen once
-            if start_idx < DEFAULT_QUEUE_SIZE {
-                unsafe {
-                    let super_scary_mut_buf = self.buf.as_ptr().cast_mut();
-                    *super_scary_mut_buf.add(start_idx) = 0xFD;
-                }
-            }
+        self.give_back(start_idx, reserved_len, header_len + actual_len, format);
+    }
 
-            // subtracting number of writers
-            self.write_idx_lock
-                .fetch_sub(QUEUE_MAGIC_NUM, Ordering::Release);
+    /// gives up a reservation of `reserved_len` bytes at `start_idx` without writing
+    /// anything. the writer still has to be released afterwards
+    pub fn abort_reserved(&self, start_idx: usize, reserved_len: usize, format: FrameFormat) {
+        self.give_back(start_idx, reserved_len, 0, format);
+    }
 
-            return Ok(PushResult::PageFull);
+    // hands the unused tail of a reservation back to the page if nobody reserved after
+    // it yet, padding over it otherwise
+    fn give_back(&self, start_idx: usize, reserved_len: usize, used: usize, format: FrameFormat) {
+        let reserved_end = start_idx + reserved_len;
+        let unused = reserved_len - used;
+
+        let mut idx = self.write_idx_lock.load(Ordering::Relaxed);
+        while idx & QUEUE_MAGIC_MASK == reserved_end {
+            // we're still registered so no reader has looked at the index yet
+            match self.write_idx_lock.compare_exchange_weak(
+                idx,
+                idx - unused,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => idx = current,
+            }
         }
-        let super_scary_mutable_buf =
-            unsafe { slice::from_raw_parts_mut(self.buf.as_ptr().cast_mut(), self.buf.len()) };
 
-        super_scary_mutable_buf[start_idx..start_idx + size_of::<MsgLengthType>()]
-            .copy_from_slice(&(msg.len() as MsgLengthType).to_le_bytes());
-        super_scary_mutable_buf[start_idx + size_of::<MsgLengthType>()
-            ..start_idx + size_of::<MsgLengthType>() + msg.len()]
-            .copy_from_slice(msg);
+        format.encode_padding(self.reserved_mut(start_idx + used, unused));
+    }
 
+    /// unregisters a writer once its reservation is written
+    pub fn release(&self) {
         self.write_idx_lock
             .fetch_sub(QUEUE_MAGIC_NUM, Ordering::Release);
+    }
 
-        Ok(PushResult::BytesWritten(
-            msg.len() + size_of::<MsgLengthType>(),
-        ))
+    /// makes every reservation from here on fail with [`Error::Closed`]. writers that
+    /// already reserved still get to commit, wait for them with [`QPage::tail`].
+    pub fn close(&self) {
+        self.write_idx_lock.fetch_or(PAGE_CLOSED, Ordering::AcqRel);
+    }
+
+    /// undoes [`QPage::close`]
+    pub fn reopen(&self) {
+        self.write_idx_lock
+            .fetch_and(!PAGE_CLOSED, Ordering::AcqRel);
+    }
+
+    /// raw `write_idx_lock` if there are still writers registered on the page
+    pub fn registered_writers(&self) -> Option<usize> {
+        let idx = self.write_idx_lock.load(Ordering::Acquire);
+
+        ((idx & WRITERS_MASK) != 0).then_some(idx)
+    }
+
+    /// drops the writer registrations in `stuck` (a value previously returned by
+    /// [`QPage::registered_writers`]) and seals the page at the last index every
+    /// reader could safely see, since whatever the dead writers reserved past that
+    /// point may be half written.
+    ///
+    /// does nothing and returns false if the lock moved since `stuck` was observed.
+    pub fn seal_stuck_writers(&self, stuck: usize) -> bool {
+        // registering ourselves keeps readers off the page until it's sealed
+        if self
+            .write_idx_lock
+            .compare_exchange(
+                stuck,
+                stuck + QUEUE_MAGIC_NUM,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return false;
+        }
+
+        self.mark_done(self.last_safe_write_idx.load(Ordering::Relaxed));
+
+        let released = (stuck & WRITERS_MASK) + QUEUE_MAGIC_NUM;
+
+        let _ = self
+            .write_idx_lock
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |idx| {
+                let writers = (idx - released) & WRITERS_MASK;
+                let closed = idx & PAGE_CLOSED;
+                Some(writers | closed | (idx & QUEUE_MAGIC_MASK).max(DEFAULT_QUEUE_SIZE))
+            });
+
+        true
+    }
+
+    /// registers a writer that never finishes, as if its process died mid-push
+    #[cfg(test)]
+    pub(crate) fn leak_writer(&self) {
+        self.write_idx_lock
+            .fetch_add(QUEUE_MAGIC_NUM, Ordering::Relaxed);
+    }
+}
+
+/// a length read from a page as a `usize`, `None` if it's more than this platform can
+/// address (`isize::MAX`, the most any slice can hold) rather than truncating it. only
+/// a corrupt length gets anywhere near that on 32 and 64 bit targets, but on a 16 bit
+/// one any `u32` prefix past `u16::MAX` would wrap around.
+pub fn stored_len<T: TryInto<usize>>(len: T) -> Option<usize> {
+    len.try_into()
+        .ok()
+        .filter(|&len| len <= isize::MAX as usize)
+}
+
+/// sets the length of `f` to `len`, retrying when a signal interrupts it, and makes
+/// sure the file really is that long before anyone maps it. touching a mapping past
+/// the end of a short file is a SIGBUS rather than an error.
+///
+/// a file that's already long enough is fine even if resizing it fails, e.g. when it
+/// wasn't opened for writing.
+pub fn extend_file(f: &File, len: u64) -> Result<(), std::io::Error> {
+    let res = loop {
+        match f.set_len(len) {
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            res => break res,
+        }
+    };
+
+    let actual = f.metadata()?.len();
+    if actual >= len {
+        return Ok(());
+    }
+
+    let reason = match res {
+        Err(e) => e,
+        Ok(()) => std::io::Error::other("the new length didn't stick"),
+    };
+
+    Err(std::io::Error::new(
+        reason.kind(),
+        format!("couldn't extend a {actual} byte file to {len} bytes: {reason}"),
+    ))
+}
+
+/// maps `f` over the first `size_of::<T>()` bytes of `m` again, in place, so anything
+/// borrowed from the mapping stays valid. whatever was written to those bytes is
+/// thrown away and they read whatever the file holds now.
+///
+/// # Safety
+///
+/// `m` has to be a private mapping (see [`QPage::map_read_only`]) of a file just like
+/// `f`, and nothing may be holding a `&mut` into it.
+#[cfg(unix)]
+pub unsafe fn remap_private<T>(m: &mut MmapMutWrapper<T>, f: &File) -> Result<(), std::io::Error> {
+    let len = std::mem::size_of::<T>();
+    if f.metadata()?.len() < len as u64 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "the file got shorter than its mapping",
+        ));
+    }
+
+    let addr = (m.get_inner() as *mut T).cast::<libc::c_void>();
+    let res = libc::mmap(
+        addr,
+        len,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_PRIVATE | libc::MAP_FIXED,
+        f.as_raw_fd(),
+        0,
+    );
+
+    match res {
+        libc::MAP_FAILED => Err(std::io::Error::last_os_error()),
+        _ => Ok(()),
     }
 }
@@ -1,424 +1,9661 @@
-use crate::qpage::{self, PopResult, PushResult, QPage};
+use crate::qpage::{self, PopResult, PushResult, QPage, PAGE_FILE_SIZE};
+pub use crate::qpage::{
+    parse_frame, ByteOrder, FrameFormat, FrameKind, LengthPrefix, PageAnomaly, PageReport,
+    HEADER_LEN_LEN, KEY_LEN, ORIGIN_LEN, SYNC_MARKER,
+};
 use mmap_wrapper::MmapMutWrapper;
 use static_assertions::const_assert;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+#[cfg(unix)]
+use std::ffi::{CStr, CString, OsString};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, Read, Write};
 use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ops::ControlFlow;
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::RwLock;
+use std::slice;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock, TryLockError};
+use std::time::{Duration, Instant, SystemTime};
 
+/// default size of internal buffers, and of the chunks [`DiskRing::pop_chunk`] hands out
 pub const DEFAULT_INTERNAL_BUF_SIZE: usize = 4096;
 const_assert!(DEFAULT_INTERNAL_BUF_SIZE < qpage::DEFAULT_MAX_MSG_SIZE);
 
+/// how many pages a new ringbuf keeps around unless [`Builder::max_qpages`] (or
+/// [`Builder::max_bytes`]) says otherwise, 4GiB worth of pages
+pub const DEFAULT_MAX_QPAGES: usize = 16;
+
+/// how much user metadata fits in the info file, see [`DiskRing::set_metadata`]
+pub const MAX_METADATA_LEN: usize = 256;
+
+// layout version of the info file, bumped whenever fields get appended to it.
+// files from before there was a version read as zero and get upgraded in place
+const INFO_VERSION: u8 = 9;
+
+// start of every file written by `export`, the last byte is the archive version
+const ARCHIVE_MAGIC: [u8; 8] = *b"DRINGEX1";
+
 #[derive(thiserror::Error, Debug)]
 pub enum RingbufError {
     #[error("invalid read")]
     ReadError,
+    #[error("a writer stalled mid-push and never released its reservation")]
+    WriterStalled,
+    #[error("invalid config: {0}")]
+    InvalidConfig(&'static str),
+    #[error("the ringbuf was closed")]
+    Closed,
+    #[error("the ringbuf directory or its info file was deleted")]
+    Gone,
+    #[error("no ringbuf exists at the given path")]
+    NotFound,
+    #[error("not an archive written by export")]
+    InvalidArchive,
+    /// the receiver has already moved on to the oldest page that's left
+    #[error("the receiver fell behind, {0} pages were reclaimed before it got to them")]
+    Lagged(usize),
+    #[error("metadata is {0} bytes, at most {MAX_METADATA_LEN} fit")]
+    MetadataTooLong(usize),
+    /// the message wasn't popped, see [`DiskRing::pop_into_uninit`]
+    #[error("the buffer is too small for a {0} byte message")]
+    BufferTooSmall(usize),
+    #[error("couldn't decode message: {0}")]
+    DecodeError(Box<dyn std::error::Error + Send + Sync>),
     #[error(transparent)]
-    QError(#[from] crate::qpage::Error),
+    QError(crate::qpage::Error),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 }
 
-const PAGE_EXT: &str = "page.bin";
-const INFO_NAME: &str = ".info";
+impl From<qpage::Error> for RingbufError {
+    fn from(e: qpage::Error) -> Self {
+        match e {
+            qpage::Error::FrameOutOfBounds => RingbufError::ReadError,
+            qpage::Error::WriterStalled => RingbufError::WriterStalled,
+            qpage::Error::Closed => RingbufError::Closed,
+            e => RingbufError::QError(e),
+        }
+    }
+}
 
-#[derive(Clone)]
-pub struct Sender {}
-#[derive(Clone)]
-pub struct Receiver {}
+pub const PAGE_EXT: &str = "page.bin";
+pub const INFO_NAME: &str = ".info";
 
-#[derive(Clone)]
-pub struct DiskRing<T> {
-    _kind: PhantomData<T>,
-    path: PathBuf,
-    read_byte: usize,
-    qpage_no: usize,
-    qpage: MmapMutWrapper<QPage>,
-    diskring_info: MmapMutWrapper<DiskRingInfo>,
-}
+/// decodes a message popped with [`DiskRing::pop_as`] straight from the bytes on the page
+pub trait FromRingbufBytes: Sized {
+    type Error: Into<Box<dyn std::error::Error + Send + Sync>>;
 
-#[repr(C)]
-pub struct DiskRingInfo {
-    max_qpages: AtomicUsize,
-    qpage_count: RwLock<usize>,
+    fn from_ringbuf_bytes(bytes: &[u8]) -> Result<Self, Self::Error>;
 }
 
-impl DiskRingInfo {
-    fn new<P: AsRef<Path>>(path: P) -> Result<MmapMutWrapper<DiskRingInfo>, RingbufError> {
-        // fails when disk is full
-        // or when parent directories don't exist
-        let f = std::fs::File::options()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(path)?;
-
-        let _ = f.set_len(std::mem::size_of::<Self>() as u64);
-        let m = unsafe { memmap2::MmapMut::map_mut(&f)? };
+impl FromRingbufBytes for Vec<u8> {
+    type Error = std::convert::Infallible;
 
-        Ok(unsafe { MmapMutWrapper::<Self>::new(m) })
+    fn from_ringbuf_bytes(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(bytes.to_vec())
     }
 }
 
-pub fn get_or_update_max_qpage<P: AsRef<Path>>(path: P, val: usize) -> Result<usize, RingbufError> {
-    let mut diskring_info = DiskRingInfo::new(path.as_ref().join(INFO_NAME))?;
-
-    let curr_max_qpages = diskring_info.get_inner().max_qpages.load(Ordering::Relaxed);
+/// unlike [`DiskRing::pop`] invalid utf-8 is an error rather than replaced
+impl FromRingbufBytes for String {
+    type Error = std::str::Utf8Error;
 
-    if val == curr_max_qpages {
-        return Ok(val);
+    fn from_ringbuf_bytes(bytes: &[u8]) -> Result<Self, Self::Error> {
+        std::str::from_utf8(bytes).map(str::to_owned)
     }
-
-    set_max_qpage(path, val)
 }
 
-pub fn set_max_qpage<P: AsRef<Path>>(path: P, val: usize) -> Result<usize, RingbufError> {
-    let mut diskring_info = DiskRingInfo::new(path.as_ref().join(INFO_NAME))?;
+/// how receivers deal with corrupt frames, see [`Builder::on_corrupt`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnCorrupt {
+    /// fail with [`RingbufError::ReadError`], every further pop fails the same way
+    #[default]
+    Error,
+    /// skip to the next intact frame and carry on from there.
+    ///
+    /// with [sync markers](Builder::sync_markers) that's the next marker followed by a
+    /// valid frame. without them nothing marks where the next frame starts, so this skips
+    /// to the end of what's been committed to the page so far and every message that was
+    /// already committed after the corrupt one is dropped along with it.
+    ///
+    /// skips are counted by [`DiskRing::corrupt_skips`]
+    Skip,
+}
 
-    let _qpage_count_lock = diskring_info
-        .get_inner()
-        .qpage_count
-        .write()
-        .expect("unpoisoned lock");
+/// when a receiver with a [cursor](Builder::cursor) writes its position to disk
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommitPolicy {
+    /// only on [`DiskRing::commit`]
+    #[default]
+    Manual,
+    /// after every this many popped messages
+    EveryMessages(usize),
+    /// on the first pop once this long passed since the last commit
+    Every(Duration),
+}
 
-    Ok(diskring_info
-        .get_inner()
-        .max_qpages
-        .swap(val, Ordering::Relaxed))
+/// how a sender waits before retrying a push that didn't fit on its page,
+/// see [`Builder::push_backoff`]. every retry spins for twice as long as the one
+/// before, then yields its time slice, then sleeps for twice as long each time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackoffPolicy {
+    /// retries that spin before yielding starts
+    pub spin_limit: u32,
+    /// retries up to which the thread yields before sleeping starts
+    pub yield_limit: u32,
+    /// longest a single sleep gets
+    pub max_sleep: Duration,
 }
 
-pub fn new<P: AsRef<Path>>(
-    path: P,
-) -> Result<(DiskRing<Sender>, DiskRing<Receiver>), RingbufError> {
-    std::fs::create_dir_all(path.as_ref())?;
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        BackoffPolicy {
+            spin_limit: 6,
+            yield_limit: 10,
+            max_sleep: Duration::from_millis(10),
+        }
+    }
+}
 
-    let qpage_no = get_qpage_count_static(&path);
-    let qpage = QPage::new(
-        path.as_ref()
-            .join(qpage_no.to_string())
-            .with_extension(PAGE_EXT),
-    )?;
+/// how a receiver waits for new messages in its blocking calls ([`DiskRing::stream`],
+/// [`DiskRing::wait_for`]), see [`Builder::read_backoff`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReaderBackoff {
+    /// spins, then yields, then sleeps, so a waiting receiver doesn't take a core away
+    /// from other threads
+    Cooperative(BackoffPolicy),
+    /// only ever spins, never yielding or sleeping, so new messages are picked up as
+    /// soon as they're committed. it keeps a whole core at 100% even while there's
+    /// nothing to read, so it only makes sense for a receiver pinned to a core of
+    /// its own.
+    BusySpin,
+}
 
-    let diskring_info = DiskRingInfo::new(path.as_ref().join(INFO_NAME))?;
+impl Default for ReaderBackoff {
+    fn default() -> Self {
+        ReaderBackoff::Cooperative(BackoffPolicy::default())
+    }
+}
 
-    Ok((
-        DiskRing {
-            _kind: PhantomData,
-            path: path.as_ref().into(),
-            read_byte: 0,
-            diskring_info: diskring_info.clone(),
-            qpage: qpage.clone(),
-            qpage_no,
-        },
-        DiskRing {
-            _kind: PhantomData,
-            path: path.as_ref().into(),
-            read_byte: 0,
-            diskring_info,
-            qpage,
-            qpage_no,
-        },
-    ))
+/// where handles get the current time from, see [`Builder::with_clock`].
+///
+/// clocks should be monotonic like [`SystemClock`]. one that steps backward (e.g. one
+/// derived from wall clock time that NTP adjusts) is tolerated: intervals are measured
+/// from the earlier point again instead of waiting for the clock to catch back up.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
 }
 
-impl Iterator for DiskRing<Receiver> {
-    type Item = Result<Option<String>, RingbufError>;
+/// the real monotonic clock, used unless the builder is given another one
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        Some(self.pop())
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
     }
 }
 
-impl DiskRing<Receiver> {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<DiskRing<Receiver>, RingbufError> {
-        let qpage_no = get_qpage_count_static(&path);
-        let qpage = QPage::new(
-            path.as_ref()
-                .join(qpage_no.to_string())
-                .with_extension(PAGE_EXT),
-        )?;
+/// a clock that only moves when it's [advanced](ManualClock::advance), for testing
+/// time based behavior like [`CommitPolicy::Every`] without sleeping
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>,
+}
+
+impl ManualClock {
+    /// starts out at the current time and stays there
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Mutex::new(Instant::now()),
+        }
+    }
 
-        let diskring_info = DiskRingInfo::new(path.as_ref().join(INFO_NAME))?;
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().expect("unpoisoned lock") += by;
+    }
 
-        Ok(DiskRing {
-            _kind: PhantomData,
-            path: path.as_ref().into(),
-            read_byte: 0,
-            diskring_info: diskring_info.clone(),
-            qpage: qpage.clone(),
-            qpage_no,
-        })
+    /// steps the clock back, like a clock that isn't monotonic
+    pub fn rewind(&self, by: Duration) {
+        *self.now.lock().expect("unpoisoned lock") -= by;
     }
+}
 
-    fn page_flip(&mut self) -> Result<(), RingbufError> {
-        let max_qpages = self
-            .diskring_info
-            .get_inner()
-            .max_qpages
-            .load(Ordering::Relaxed);
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        if max_qpages > 0 {
-            let qpage_count = self
-                .diskring_info
-                .get_inner()
-                .qpage_count
-                .read()
-                .expect("unpoisoned lock");
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().expect("unpoisoned lock")
+    }
+}
 
-            self.qpage_no =
-                std::cmp::max(self.qpage_no + 1, qpage_count.saturating_sub(max_qpages));
-        } else {
-            self.qpage_no += 1;
-        }
+/// outcome of [`DiskRing::pop_status`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum PopStatus {
+    Message(String),
+    /// there's nothing left to read right now
+    CaughtUp,
+    /// the receiver finished a page and moved on to the next one
+    FlippedPage,
+}
 
-        self.read_byte = 0;
-        self.qpage = QPage::new(
-            self.path
-                .join(self.qpage_no.to_string())
-                .with_extension(PAGE_EXT),
-        )?;
+/// outcome of [`DiskRing::pop_no_flip`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum PopNoFlipStatus {
+    Message(String),
+    /// there's nothing left to read right now
+    CaughtUp,
+    /// the receiver read everything on its page, it stays there
+    /// until [`DiskRing::advance_page`] is called
+    AtPageBoundary,
+}
 
-        Ok(())
-    }
+/// part of a message, see [`DiskRing::pop_chunk`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Chunk {
+    pub bytes: Vec<u8>,
+    /// whether the rest of the message comes in later chunks
+    pub more: bool,
+}
 
-    pub fn pop(&mut self) -> Result<Option<String>, RingbufError> {
-        loop {
-            match self.qpage.get_inner().try_pop(self.read_byte)? {
-                PopResult::Msg(m) => {
-                    self.read_byte += m.len() + size_of::<qpage::MsgLengthType>();
-                    return Ok(Some(String::from_utf8_lossy(m).to_string()));
-                }
-                PopResult::NoNewMsgs => return Ok(None),
-                PopResult::PageDone => {}
-            };
+/// where the last message of a finished ringbuf ends, see [`DiskRing::finish`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FinalPosition {
+    qpage_no: usize,
+    write_byte: usize,
+}
 
-            self.page_flip()?;
-        }
+impl FinalPosition {
+    /// the last page anything was written to
+    pub fn qpage_no(&self) -> usize {
+        self.qpage_no
+    }
+
+    /// end of the last frame on that page
+    pub fn write_byte(&self) -> usize {
+        self.write_byte
     }
 }
 
-impl DiskRing<Sender> {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<DiskRing<Sender>, RingbufError> {
-        let qpage_no = get_qpage_count_static(&path);
-        let qpage = QPage::new(
-            path.as_ref()
-                .join(qpage_no.to_string())
-                .with_extension(PAGE_EXT),
-        )?;
+/// where a message starts in the ringbuf, see [`DiskRing::read_position`].
+///
+/// positions order by page and then by offset on the page, so a later position is
+/// further along the ringbuf, e.g. to tell which of two receivers is ahead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    qpage_no: usize,
+    read_byte: usize,
+}
 
-        let diskring_info = DiskRingInfo::new(path.as_ref().join(INFO_NAME))?;
+impl Position {
+    /// the page the message is on
+    pub fn qpage_no(&self) -> usize {
+        self.qpage_no
+    }
 
-        Ok(DiskRing {
-            _kind: PhantomData,
-            path: path.as_ref().into(),
-            read_byte: 0,
-            diskring_info: diskring_info.clone(),
-            qpage: qpage.clone(),
-            qpage_no,
-        })
+    /// offset of the message's frame in its page
+    pub fn read_byte(&self) -> usize {
+        self.read_byte
     }
+}
 
-    fn page_flip(&mut self) -> Result<(), std::io::Error> {
-        let qpage_count = self
-            .diskring_info
-            .get_inner()
-            .qpage_count
-            .read()
-            .expect("unpoisoned lock");
+/// where a message physically lives, see [`DiskRing::frame_info`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameInfo {
+    /// number of the page file the frame is in
+    pub page: usize,
+    /// offset of the frame from the start of the page file
+    pub offset: usize,
+    /// sync marker, length prefix and key in front of the payload
+    pub header_len: usize,
+    pub payload_len: usize,
+}
 
-        if self.qpage_no < *qpage_count {
-            self.qpage_no += 1;
-            return Ok(());
-        }
+/// what a receiver was up to when it got close to losing its page to reclamation,
+/// see [`DiskRing::on_reclaim_pressure`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReclaimInfo {
+    /// the page the receiver is reading, the oldest one that's still retained
+    pub page: usize,
+    /// the newest page, once a sender starts the one after it `page` goes
+    pub newest_page: usize,
+    pub max_qpages: usize,
+}
 
-        if self.qpage_no == *qpage_count {
-            drop(qpage_count);
+/// what a single push did, see [`DiskRing::push_detailed`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PushDetails {
+    /// bytes the message takes up, framing included, like [`DiskRing::push`] returns
+    pub written: usize,
+    /// bytes left on the page the message ended on, like [`DiskRing::push_with_remaining`]
+    pub page_remaining: usize,
+    /// how many pages the message is spread over, 2 if it was
+    /// [split](Builder::split_messages) and 1 otherwise
+    pub spanned_pages: usize,
+}
 
-            let mut qpage_count = self
-                .diskring_info
-                .get_inner()
-                .qpage_count
-                .write()
-                .expect("unpoisoned lock");
+/// heads of messages split across pages waiting for their tails,
+/// see [`Builder::split_messages`]
+#[derive(Clone, Debug, Default)]
+struct Stitcher {
+    heads: HashMap<Position, Vec<u8>>,
+    // the last message put back together, at the position of its tail
+    stitched: Option<(Position, Vec<u8>)>,
+}
 
-            if self.qpage_no < *qpage_count {
-                self.qpage_no += 1;
-                return Ok(());
+impl Stitcher {
+    /// the message the frame at `pos` completes, if any
+    fn stitch<'a>(
+        &'a mut self,
+        pos: Position,
+        kind: FrameKind,
+        fragment: &'a [u8],
+    ) -> Option<&'a [u8]> {
+        match kind {
+            FrameKind::Whole => Some(fragment),
+            FrameKind::Head => {
+                self.heads.insert(pos, fragment.to_vec());
+                None
             }
+            FrameKind::Tail => {
+                if self.stitched.as_ref().is_none_or(|(p, _)| *p != pos) {
+                    let origin = fragment.get(..ORIGIN_LEN)?;
+                    let origin = Position {
+                        qpage_no: u64::from_le_bytes(origin[..8].try_into().unwrap()) as usize,
+                        read_byte: u64::from_le_bytes(origin[8..].try_into().unwrap()) as usize,
+                    };
 
-            *qpage_count += 1;
-            self.qpage_no += 1;
-
-            let max_qpages = self
-                .diskring_info
-                .get_inner()
-                .max_qpages
-                .load(Ordering::Relaxed);
+                    // no head when reading started after it
+                    let mut msg = self.heads.remove(&origin)?;
+                    msg.extend_from_slice(&fragment[ORIGIN_LEN..]);
+                    self.stitched = Some((pos, msg));
+                }
 
-            // setting max_total_pages to zero implies an unbounded ringbuf / queue
-            if max_qpages == 0 {
-                return Ok(());
+                self.stitched.as_ref().map(|(_, msg)| msg.as_slice())
             }
+        }
+    }
 
-            if *qpage_count >= max_qpages {
-                std::fs::remove_file(
-                    self.path
-                        .join((*qpage_count - max_qpages).to_string())
-                        .with_extension(PAGE_EXT),
-                )?;
-            }
+    /// drops the heads whose tails senders most likely reclaimed already, which would
+    /// otherwise wait for them forever. a tail goes on the page after its head unless
+    /// that one fills up first, so that's once both of those pages are gone.
+    fn forget_reclaimed(&mut self, info: &DiskRingInfo) {
+        let max_qpages = info.max_qpages.load(Ordering::Acquire);
+        if self.heads.is_empty() || max_qpages == 0 {
+            return;
         }
 
-        Ok(())
+        let qpage_count = info.qpage_count.load(Ordering::Acquire);
+        let oldest = (qpage_count + 1).saturating_sub(max_qpages);
+        self.heads.retain(|pos, _| pos.qpage_no + 1 >= oldest);
     }
+}
 
-    pub fn push<T: AsRef<[u8]>>(&mut self, input: T) -> Result<usize, RingbufError> {
-        loop {
-            match self.qpage.get_inner().try_push(input.as_ref())? {
-                PushResult::BytesWritten(x) => return Ok(x),
-                PushResult::PageFull => {}
-            }
+/// what [`DiskRing::try_pop`] found. padding is skipped over, and a message that was
+/// [split](Builder::split_messages) across pages comes back put together and owned
+enum Popped<'a> {
+    Msg(&'a [u8]),
+    NoNewMsgs,
+    PageDone,
+}
 
-            self.page_flip()?;
+// what DiskRing::find_next came across, the message only by where it is so the
+// receiver can still be used before DiskRing::popped_msg borrows it
+enum Found {
+    Msg(MsgAt),
+    NoNewMsgs,
+    PageDone,
+}
 
-            self.qpage = QPage::new(
-                self.path
-                    .join(self.qpage_no.to_string())
-                    .with_extension(PAGE_EXT),
-            )?;
-        }
-    }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MsgAt {
+    // the whole message is in the frame starting at this byte of the page
+    Frame(usize),
+    // it was split, the stitcher put it back together
+    Stitched,
 }
 
-fn get_qpage_count_static<P: AsRef<Path>>(path: P) -> usize {
-    let Ok(mut diskring_info) = DiskRingInfo::new(path.as_ref().join(INFO_NAME)) else {
-        return 0;
-    };
+/// the last few messages a receiver decoded, least recently used first
+#[derive(Clone, Debug, Default)]
+struct ReadCache {
+    capacity: usize,
+    entries: VecDeque<(Position, String)>,
+}
 
-    let qpage_count = diskring_info
-        .get_inner()
-        .qpage_count
-        .read()
-        .expect("unpoisoned lock");
+impl ReadCache {
+    fn get(&mut self, pos: Position) -> Option<String> {
+        let i = self.entries.iter().position(|(p, _)| *p == pos)?;
+        let entry = self.entries.remove(i).expect("index in bounds");
+        let msg = entry.1.clone();
+        self.entries.push_back(entry);
 
-    *qpage_count
-}
+        Some(msg)
+    }
 
-#[test]
-fn seq_test() {
-    let test_dir_path = "test-seq";
-    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    fn insert(&mut self, pos: Position, msg: &str) {
+        if self.capacity == 0 {
+            return;
+        }
 
-    let now = std::time::Instant::now();
-    for i in 0..50_000_000 {
-        tx.push(i.to_string()).unwrap();
+        self.entries.retain(|(p, _)| *p != pos);
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((pos, msg.to_string()));
     }
 
-    for i in 0..50_000_000 {
-        let m = rx.pop().unwrap();
-        assert_eq!(m, Some(i.to_string()));
+    fn invalidate_page(&mut self, qpage_no: usize) {
+        self.entries.retain(|(p, _)| p.qpage_no != qpage_no);
     }
+}
 
-    eprintln!("took {} ms", now.elapsed().as_millis());
+type PageFullHook = Arc<Mutex<dyn FnMut(usize) + Send>>;
+type ReclaimHook = Arc<Mutex<dyn FnMut(ReclaimInfo) + Send>>;
 
-    std::fs::remove_dir_all(test_dir_path).unwrap();
+#[derive(Clone, Debug)]
+pub struct Sender {}
+#[derive(Clone, Debug)]
+pub struct Receiver {}
+
+#[derive(Clone)]
+pub struct DiskRing<T> {
+    _kind: PhantomData<T>,
+    dir: RingDir,
+    config: Arc<Config>,
+    read_byte: usize,
+    corrupt_skips: usize,
+    format: FrameFormat,
+    fmt_buf: Vec<u8>,
+    read_cache: ReadCache,
+    // how far into the message at the position pop_chunk got
+    chunk_progress: Option<(Position, usize)>,
+    stitcher: Stitcher,
+    // page space taken up by the message try_pop returned last
+    popped_frame_len: usize,
+    commits: CommitState,
+    // when this sender last flushed, see Builder::flush_interval
+    last_flush: Instant,
+    // only ever set on senders, see Builder::flush_on_drop
+    flush_on_drop: bool,
+    // see DiskRing::on_page_full, shared with clones
+    on_page_full: Option<PageFullHook>,
+    // see DiskRing::on_reclaim_pressure, and the page it last fired for
+    on_reclaim_pressure: Option<ReclaimHook>,
+    reclaim_warned: Option<usize>,
+    // a receiver with private mappings it remaps itself, see Builder::polling_read
+    polling: bool,
+    // the info file, locked for as long as the handle and its clones are open so gc
+    // leaves the ringbuf alone, see lock_info_file
+    _info_lock: Option<Arc<File>>,
+    qpage_no: usize,
+    qpage: MmapMutWrapper<QPage>,
+    diskring_info: MmapMutWrapper<DiskRingInfo>,
 }
 
-#[test]
-fn seq_buffered_test() {
-    let test_dir_path = "test-seq-buf";
-    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+// the mappings themselves are far too big to print, only where the handle is
+impl<T> std::fmt::Debug for DiskRing<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskRing")
+            .field("kind", &self._kind)
+            .field("dir", &self.dir)
+            .field("qpage_no", &self.qpage_no)
+            .field("read_byte", &self.read_byte)
+            .field(
+                "max_qpages",
+                &self
+                    .diskring_info
+                    .clone()
+                    .get_inner()
+                    .max_qpages
+                    .load(Ordering::Relaxed),
+            )
+            .finish_non_exhaustive()
+    }
+}
 
-    let now = std::time::Instant::now();
-    for i in 0..50_000_000 {
-        tx.push(i.to_string()).unwrap();
+/// options set through the [`Builder`].
+///
+/// the file naming scheme isn't persisted anywhere, so every handle opened
+/// on the same directory has to be built with the same scheme.
+#[derive(Clone, Debug)]
+struct Config {
+    page_extension: String,
+    info_name: String,
+    writer_stall_timeout: Option<Duration>,
+    length_prefix: Option<LengthPrefix>,
+    byte_order: Option<ByteOrder>,
+    sync_markers: Option<bool>,
+    keys: Option<bool>,
+    split: Option<bool>,
+    headers: Option<bool>,
+    preallocate_pages: usize,
+    max_reader_lag: Option<usize>,
+    max_bytes: Option<u64>,
+    max_qpages: Option<usize>,
+    clock: Arc<dyn Clock>,
+    on_corrupt: OnCorrupt,
+    read_cache: usize,
+    chunk_size: usize,
+    cursor: Option<String>,
+    commit_policy: CommitPolicy,
+    push_backoff: Option<BackoffPolicy>,
+    flush_interval: Option<Duration>,
+    flush_on_drop: bool,
+    start_page: Option<usize>,
+    file_mode: Option<u32>,
+    max_flips_per_pop: Option<usize>,
+    read_backoff: ReaderBackoff,
+    #[cfg(target_os = "linux")]
+    huge_pages: bool,
+    #[cfg(unix)]
+    polling_read: bool,
+}
+
+/// bookkeeping for the [`CommitPolicy`] of a receiver
+#[derive(Clone, Debug)]
+struct CommitState {
+    since_commit: usize,
+    last_commit: Instant,
+    committed: Option<Position>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            page_extension: PAGE_EXT.to_string(),
+            info_name: INFO_NAME.to_string(),
+            writer_stall_timeout: None,
+            length_prefix: None,
+            byte_order: None,
+            sync_markers: None,
+            keys: None,
+            split: None,
+            headers: None,
+            preallocate_pages: 0,
+            max_reader_lag: None,
+            max_bytes: None,
+            max_qpages: None,
+            clock: Arc::new(SystemClock),
+            on_corrupt: OnCorrupt::Error,
+            read_cache: 0,
+            chunk_size: DEFAULT_INTERNAL_BUF_SIZE,
+            cursor: None,
+            commit_policy: CommitPolicy::Manual,
+            push_backoff: None,
+            flush_interval: None,
+            flush_on_drop: false,
+            start_page: None,
+            file_mode: None,
+            max_flips_per_pop: None,
+            read_backoff: ReaderBackoff::default(),
+            #[cfg(target_os = "linux")]
+            huge_pages: false,
+            #[cfg(unix)]
+            polling_read: false,
+        }
     }
+}
 
-    for i in 0..50_000_000 {
-        let m = rx.pop().unwrap();
-        assert_eq!(m, Some(i.to_string()));
+impl Config {
+    /// the page limit a ringbuf created with this config starts out with. a byte cap
+    /// brings its own limit, so it doesn't get the default on top of it, and whichever
+    /// of the two allows fewer pages wins.
+    fn initial_max_qpages(&self) -> usize {
+        let within_bytes = self
+            .max_bytes
+            .map(|max_bytes| self.qpages_within(max_bytes));
+
+        match (self.max_qpages, within_bytes) {
+            (Some(0) | None, Some(within_bytes)) => within_bytes,
+            (Some(max_qpages), Some(within_bytes)) => max_qpages.min(within_bytes),
+            (Some(max_qpages), None) => max_qpages,
+            (None, None) => DEFAULT_MAX_QPAGES,
+        }
     }
 
-    eprintln!("took {} ms", now.elapsed().as_millis());
+    // how many pages fit in `max_bytes` next to the info file and the pages
+    // preallocated ahead of the newest one
+    fn qpages_within(&self, max_bytes: u64) -> usize {
+        let info_len = size_of::<DiskRingInfo>() as u64;
+        let preallocated = (self.preallocate_pages as u64).saturating_mul(PAGE_FILE_SIZE as u64);
 
-    std::fs::remove_dir_all(test_dir_path).unwrap();
-}
+        (max_bytes
+            .saturating_sub(info_len)
+            .saturating_sub(preallocated)
+            / PAGE_FILE_SIZE as u64) as usize
+    }
 
-#[test]
-fn spsc_test() {
-    let test_dir_path = "test-spsc";
-    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    fn validate(&self) -> Result<(), RingbufError> {
+        if self.page_extension.is_empty() || self.page_extension.contains(std::path::is_separator) {
+            return Err(RingbufError::InvalidConfig(
+                "page extension must be non-empty and can't contain path separators",
+            ));
+        }
 
-    let now = std::time::Instant::now();
-    let t = std::thread::spawn(move || {
-        for i in 0..50_000_000 {
-            tx.push(i.to_string()).unwrap();
+        if self.info_name.is_empty()
+            || self.info_name == "."
+            || self.info_name == ".."
+            || self.info_name.contains(std::path::is_separator)
+        {
+            return Err(RingbufError::InvalidConfig(
+                "info name must be a plain file name",
+            ));
         }
-    });
 
-    let mut i = 0;
-    loop {
-        if i == 50_000_000 {
-            break;
+        if self.chunk_size == 0 {
+            return Err(RingbufError::InvalidConfig("chunk size can't be zero"));
         }
 
-        let m = match rx.pop().unwrap() {
-            Some(m) => m,
-            None => continue,
-        };
+        if let Some(cursor) = &self.cursor {
+            if cursor.is_empty() || cursor.contains(std::path::is_separator) {
+                return Err(RingbufError::InvalidConfig(
+                    "cursor name must be non-empty and can't contain path separators",
+                ));
+            }
+        }
 
-        assert_eq!(m, i.to_string());
-        i += 1;
-    }
+        if self
+            .max_bytes
+            .is_some_and(|max_bytes| self.qpages_within(max_bytes) == 0)
+        {
+            return Err(RingbufError::InvalidConfig(
+                "max_bytes has to leave room for at least one page",
+            ));
+        }
 
-    t.join().unwrap();
+        if self.split == Some(true) && self.headers == Some(true) {
+            return Err(RingbufError::InvalidConfig(
+                "message headers can't be combined with splitting messages",
+            ));
+        }
 
-    eprintln!("took {} ms", now.elapsed().as_millis());
+        if self.max_flips_per_pop == Some(0) {
+            return Err(RingbufError::InvalidConfig(
+                "pop has to be allowed to flip at least one page",
+            ));
+        }
 
-    std::fs::remove_dir_all(test_dir_path).unwrap();
-}
+        if self.commit_policy == CommitPolicy::EveryMessages(0) {
+            return Err(RingbufError::InvalidConfig(
+                "commit interval can't be zero messages",
+            ));
+        }
 
-#[test]
-fn mpsc_test() {
-    let test_dir_path = "test-mpsc";
-    let num_threads = 4;
-    let mut threads = Vec::new();
+        Ok(())
+    }
 
-    let (tx, mut rx) = new(test_dir_path).unwrap();
+    fn page_name(&self, qpage_no: usize) -> PathBuf {
+        Path::new(&qpage_no.to_string()).with_extension(&self.page_extension)
+    }
 
-    let now = std::time::Instant::now();
+    /// opens (creating it if needed) and maps page `qpage_no` the way the builder asked for
+    fn open_page(
+        &self,
+        dir: &RingDir,
+        qpage_no: usize,
+    ) -> Result<MmapMutWrapper<QPage>, std::io::Error> {
+        let f = dir.open_mode(&self.page_name(qpage_no), self.file_mode)?;
 
-    for _ in 0..num_threads {
-        let mut tx_clone = tx.clone();
-        threads.push(std::thread::spawn(move || {
-            for i in 0..50_000_000 / num_threads {
-                tx_clone.push(i.to_string()).unwrap();
+        #[cfg(target_os = "linux")]
+        if self.huge_pages {
+            let (qpage, huge) = QPage::from_file_huge(f)?;
+            if !huge {
+                trace!(warn, qpage_no, "huge_pages_unavailable");
             }
-        }));
+
+            return Ok(qpage);
+        }
+
+        QPage::from_file(f)
     }
 
-    drop(tx);
+    /// maps page `qpage_no` for a [polling](Builder::polling_read) receiver, which never
+    /// creates or grows it. a page its sender hasn't finished creating yet is `NotFound`
+    fn open_page_read_only(
+        &self,
+        dir: &RingDir,
+        qpage_no: usize,
+    ) -> Result<MmapMutWrapper<QPage>, std::io::Error> {
+        let f = dir.open_read(&self.page_name(qpage_no))?;
 
-    let mut i = 0;
-    loop {
-        if i == 50_000_000 {
-            break;
+        if f.metadata()?.len() < PAGE_FILE_SIZE as u64 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "the page isn't fully created yet",
+            ));
         }
 
-        let _m = match rx.pop().unwrap() {
-            Some(_m) => _m,
-            None => continue,
+        QPage::map_read_only(&f)
+    }
+
+    fn cursor_name(&self) -> Option<PathBuf> {
+        let cursor = self.cursor.as_ref()?;
+
+        Some(Path::new(cursor).with_extension("cursor"))
+    }
+
+    fn is_page_name(&self, name: &std::ffi::OsStr) -> bool {
+        name.to_str()
+            .and_then(|name| name.strip_suffix(&self.page_extension))
+            .and_then(|name| name.strip_suffix('.'))
+            .is_some_and(|qpage_no| qpage_no.parse::<usize>().is_ok())
+    }
+
+    fn info_path(&self, path: &Path) -> PathBuf {
+        path.join(&self.info_name)
+    }
+
+    /// maps the info file in `dir`, creating it if needed
+    fn open_info(&self, dir: &RingDir) -> Result<MmapMutWrapper<DiskRingInfo>, RingbufError> {
+        DiskRingInfo::from_file(dir.open_mode(Path::new(&self.info_name), self.file_mode)?)
+    }
+
+    /// like [`Config::open_info`], but `NotFound` if there's no ringbuf in `dir`
+    fn open_existing_info(
+        &self,
+        dir: &RingDir,
+    ) -> Result<MmapMutWrapper<DiskRingInfo>, RingbufError> {
+        if !dir.exists(Path::new(&self.info_name))? {
+            return Err(RingbufError::NotFound);
+        }
+
+        self.open_info(dir)
+    }
+
+    /// deletes every page that fell out of the `max_qpages` window ending at
+    /// `qpage_count`, walking down from the newest stale page until it finds
+    /// one that's already gone.
+    ///
+    /// has to be called with the info lock held for writing. the deleted pages stay
+    /// mapped so their messages can be counted towards the dropped total with
+    /// [`Reclaimed::count`] once the lock is released.
+    fn reclaim_pages(
+        &self,
+        dir: &RingDir,
+        qpage_count: usize,
+        max_qpages: usize,
+    ) -> Result<Reclaimed, std::io::Error> {
+        let mut reclaimed = Reclaimed::default();
+
+        // setting max_total_pages to zero implies an unbounded ringbuf / queue
+        if max_qpages == 0 || qpage_count < max_qpages {
+            return Ok(reclaimed);
+        }
+
+        for qpage_no in (0..=qpage_count - max_qpages).rev() {
+            match self.open_page_read_only(dir, qpage_no) {
+                Ok(page) => reclaimed.pages.push(page),
+                // a short page has nothing committed on it worth counting
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    if !dir.exists(&self.page_name(qpage_no))? {
+                        break;
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+
+            dir.remove(&self.page_name(qpage_no))?;
+        }
+
+        Ok(reclaimed)
+    }
+}
+
+/// pages deleted by [`Config::reclaim_pages`] whose messages haven't been counted yet
+#[derive(Default)]
+#[must_use]
+struct Reclaimed {
+    pages: Vec<MmapMutWrapper<QPage>>,
+}
+
+impl Reclaimed {
+    /// adds the messages committed to the reclaimed pages to the dropped total of
+    /// `info`. walks every frame, so it's done without holding the info lock.
+    /// `format` is `None` when no handle ever set up the ringbuf, so there's nothing
+    /// to count.
+    fn count(mut self, info: &DiskRingInfo, format: Option<FrameFormat>) {
+        let Some(format) = format else {
+            return;
         };
 
-        i += 1;
+        let mut dropped = 0;
+        for page in self.pages.iter_mut() {
+            dropped += page.get_inner().count_committed(0, usize::MAX, format).0;
+        }
+
+        info.dropped.fetch_add(dropped as u64, Ordering::Relaxed);
+        trace!(debug, pages = self.pages.len(), dropped, "reclaim");
     }
+}
 
-    for t in threads {
-        t.join().unwrap();
+/// directory holding the pages and info file of a ringbuf
+#[derive(Clone, Debug)]
+enum RingDir {
+    Path(PathBuf),
+    /// files are opened relative to the descriptor, so the directory
+    /// doesn't have to be reachable by path at all
+    #[cfg(unix)]
+    Fd(Arc<OwnedFd>),
+}
+
+impl RingDir {
+    /// opens (creating it if needed) the file `name` for reading and writing
+    fn open(&self, name: &Path) -> Result<File, std::io::Error> {
+        self.open_mode(name, None)
+    }
+
+    /// opens the existing file `name` for reading only
+    fn open_read(&self, name: &Path) -> Result<File, std::io::Error> {
+        match self {
+            RingDir::Path(path) => File::open(path.join(name)),
+            #[cfg(unix)]
+            RingDir::Fd(fd) => {
+                let name = CString::new(name.as_os_str().as_bytes())?;
+                let flags = libc::O_RDONLY | libc::O_CLOEXEC;
+
+                match unsafe { libc::openat(fd.as_raw_fd(), name.as_ptr(), flags) } {
+                    -1 => Err(std::io::Error::last_os_error()),
+                    f => Ok(unsafe { File::from_raw_fd(f) }),
+                }
+            }
+        }
+    }
+
+    /// like [`open`](RingDir::open), but creates a missing file with the unix
+    /// permission bits `mode` (still subject to the umask). ignored elsewhere.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn open_mode(&self, name: &Path, mode: Option<u32>) -> Result<File, std::io::Error> {
+        match self {
+            RingDir::Path(path) => {
+                let mut options = File::options();
+                options.read(true).write(true).create(true).truncate(false);
+
+                #[cfg(unix)]
+                if let Some(mode) = mode {
+                    std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+                }
+
+                options.open(path.join(name))
+            }
+            #[cfg(unix)]
+            RingDir::Fd(fd) => {
+                let name = CString::new(name.as_os_str().as_bytes())?;
+                let flags = libc::O_RDWR | libc::O_CREAT | libc::O_CLOEXEC;
+                let mode = mode.unwrap_or(0o666) as libc::c_uint;
+
+                match unsafe { libc::openat(fd.as_raw_fd(), name.as_ptr(), flags, mode) } {
+                    -1 => Err(std::io::Error::last_os_error()),
+                    f => Ok(unsafe { File::from_raw_fd(f) }),
+                }
+            }
+        }
+    }
+
+    /// atomically replaces the contents of `name`, and waits for them to hit the
+    /// disk. `contents` has to be the same length every time since the temp file
+    /// it's written to first is never truncated
+    fn replace(&self, name: &Path, contents: &[u8]) -> Result<(), std::io::Error> {
+        let mut tmp_name = name.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_name = Path::new(&tmp_name);
+
+        let mut f = self.open(tmp_name)?;
+        f.write_all(contents)?;
+        f.sync_data()?;
+
+        match self {
+            RingDir::Path(path) => std::fs::rename(path.join(tmp_name), path.join(name)),
+            #[cfg(unix)]
+            RingDir::Fd(fd) => {
+                let tmp_name = CString::new(tmp_name.as_os_str().as_bytes())?;
+                let name = CString::new(name.as_os_str().as_bytes())?;
+
+                let fd = fd.as_raw_fd();
+                match unsafe { libc::renameat(fd, tmp_name.as_ptr(), fd, name.as_ptr()) } {
+                    -1 => Err(std::io::Error::last_os_error()),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    fn metadata(&self, name: &Path) -> Result<std::fs::Metadata, std::io::Error> {
+        match self {
+            RingDir::Path(path) => path.join(name).metadata(),
+            #[cfg(unix)]
+            RingDir::Fd(_) => self.open_read(name)?.metadata(),
+        }
+    }
+
+    fn exists(&self, name: &Path) -> Result<bool, std::io::Error> {
+        match self {
+            RingDir::Path(path) => path.join(name).try_exists(),
+            #[cfg(unix)]
+            RingDir::Fd(fd) => {
+                let name = CString::new(name.as_os_str().as_bytes())?;
+
+                match unsafe { libc::faccessat(fd.as_raw_fd(), name.as_ptr(), libc::F_OK, 0) } {
+                    0 => Ok(true),
+                    _ => match std::io::Error::last_os_error() {
+                        e if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+                        e => Err(e),
+                    },
+                }
+            }
+        }
+    }
+
+    /// names of all the files in the directory
+    fn list(&self) -> Result<Vec<OsString>, std::io::Error> {
+        match self {
+            RingDir::Path(path) => std::fs::read_dir(path)?
+                .map(|entry| Ok(entry?.file_name()))
+                .collect(),
+            #[cfg(unix)]
+            RingDir::Fd(fd) => {
+                // the stream takes the descriptor over and moves its offset, so it gets
+                // a fresh one on the same directory
+                let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+                let dir_fd = match unsafe { libc::openat(fd.as_raw_fd(), c".".as_ptr(), flags) } {
+                    -1 => return Err(std::io::Error::last_os_error()),
+                    dir_fd => dir_fd,
+                };
+
+                let stream = unsafe { libc::fdopendir(dir_fd) };
+                if stream.is_null() {
+                    let e = std::io::Error::last_os_error();
+                    unsafe { libc::close(dir_fd) };
+                    return Err(e);
+                }
+
+                let mut names = Vec::new();
+                loop {
+                    let entry = unsafe { libc::readdir(stream) };
+                    if entry.is_null() {
+                        break;
+                    }
+
+                    let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+                    names.push(std::ffi::OsStr::from_bytes(name.to_bytes()).to_owned());
+                }
+
+                unsafe { libc::closedir(stream) };
+                Ok(names)
+            }
+        }
+    }
+
+    fn remove(&self, name: &Path) -> Result<(), std::io::Error> {
+        match self {
+            RingDir::Path(path) => std::fs::remove_file(path.join(name)),
+            #[cfg(unix)]
+            RingDir::Fd(fd) => {
+                let name = CString::new(name.as_os_str().as_bytes())?;
+
+                match unsafe { libc::unlinkat(fd.as_raw_fd(), name.as_ptr(), 0) } {
+                    -1 => Err(std::io::Error::last_os_error()),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// locks the info file `f` of a ringbuf, shared for every open handle or exclusively
+/// for [`gc`] to make sure there are none. an exclusive lock doesn't wait, it's
+/// `false` if there's a handle. a shared one waits for `gc` to finish.
+#[cfg(unix)]
+fn lock_info_file(f: &File, exclusive: bool) -> Result<bool, std::io::Error> {
+    let op = match exclusive {
+        true => libc::LOCK_EX | libc::LOCK_NB,
+        false => libc::LOCK_SH,
+    };
+
+    loop {
+        match unsafe { libc::flock(f.as_raw_fd(), op) } {
+            0 => return Ok(true),
+            _ => match std::io::Error::last_os_error() {
+                e if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                e if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(false),
+                e => return Err(e),
+            },
+        }
+    }
+}
+
+/// builder for opening a ringbuf with non-default options.
+///
+/// ```rust
+/// use disk_ringbuffer::ringbuf::Builder;
+///
+/// let (mut tx, mut rx) = Builder::new("test-builder-doc")
+///     .page_extension("queue")
+///     .info_name("queue.info")
+///     .build()
+///     .unwrap();
+///
+/// tx.push("hello").unwrap();
+/// assert_eq!(rx.pop().unwrap(), Some("hello".to_string()));
+/// # std::fs::remove_dir_all("test-builder-doc").unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Builder {
+    dir: RingDir,
+    config: Config,
+}
+
+impl Builder {
+    pub fn new<P: AsRef<Path>>(path: P) -> Builder {
+        Builder {
+            dir: RingDir::Path(path.as_ref().into()),
+            config: Config::default(),
+        }
+    }
+
+    /// opens the ringbuf in the directory behind `fd` instead of a path, for sandboxed
+    /// processes that are handed an already open directory (e.g. over `SCM_RIGHTS`).
+    ///
+    /// every page and the info file are opened relative to the descriptor, so the
+    /// directory has to exist already. the descriptor is shared by all handles opened
+    /// from it and closed once the last one is dropped. to hand over a raw fd it has to
+    /// be wrapped with [`OwnedFd::from_raw_fd`], after which nothing else may use or
+    /// close it.
+    ///
+    /// [`OwnedFd::from_raw_fd`]: std::os::fd::FromRawFd::from_raw_fd
+    #[cfg(unix)]
+    pub fn from_dir_fd(fd: OwnedFd) -> Builder {
+        Builder {
+            dir: RingDir::Fd(Arc::new(fd)),
+            config: Config::default(),
+        }
+    }
+
+    /// extension of the page files, pages are named `<n>.<ext>`
+    /// (defaults to [`PAGE_EXT`])
+    pub fn page_extension(mut self, ext: &str) -> Builder {
+        self.config.page_extension = ext.to_string();
+        self
+    }
+
+    /// name of the info file inside of the ringbuf directory
+    /// (defaults to [`INFO_NAME`])
+    pub fn info_name(mut self, name: &str) -> Builder {
+        self.config.info_name = name.to_string();
+        self
+    }
+
+    /// how long a receiver waits on a writer that stopped making progress
+    /// before giving up with [`RingbufError::WriterStalled`].
+    ///
+    /// by default receivers wait forever, which wedges them if a producer
+    /// process dies in the middle of a push.
+    pub fn writer_stall_timeout(mut self, timeout: Duration) -> Builder {
+        self.config.writer_stall_timeout = Some(timeout);
+        self
+    }
+
+    /// width of the length prefix in front of every message, narrower prefixes save
+    /// space for tiny messages but cap how big a message can get
+    /// (see [`LengthPrefix::max_msg_len`]).
+    ///
+    /// the prefix is recorded in the info file when the ringbuf is first created,
+    /// reopening it with a different one fails. handles opened without setting it
+    /// just use whatever the ringbuf was created with.
+    pub fn length_prefix(mut self, prefix: LengthPrefix) -> Builder {
+        self.config.length_prefix = Some(prefix);
+        self
+    }
+
+    /// byte order of fixed width length prefixes, defaults to little endian.
+    /// [`ByteOrder::Big`] lets frames handed out by [`DiskRing::pop_batch_bytes`]
+    /// go straight onto the wire in network byte order.
+    ///
+    /// recorded in the info file just like the [`length_prefix`](Builder::length_prefix),
+    /// so pages read the same no matter which machine opens them.
+    pub fn byte_order(mut self, order: ByteOrder) -> Builder {
+        self.config.byte_order = Some(order);
+        self
+    }
+
+    /// starts every frame with [`SYNC_MARKER`], costing 4 bytes per message. receivers
+    /// built with [`OnCorrupt::Skip`] then resync at the next intact frame after a
+    /// corrupt one instead of dropping everything behind it.
+    ///
+    /// recorded in the info file just like the [`length_prefix`](Builder::length_prefix).
+    pub fn sync_markers(mut self, enabled: bool) -> Builder {
+        self.config.sync_markers = Some(enabled);
+        self
+    }
+
+    /// gives every frame a `u64` key, costing [`KEY_LEN`] bytes per message. senders set
+    /// it with [`DiskRing::push_keyed`] and receivers read selected keys with
+    /// [`DiskRing::pop_filtered`], multiplexing sub-streams over a single ringbuf.
+    ///
+    /// recorded in the info file just like the [`length_prefix`](Builder::length_prefix).
+    pub fn message_keys(mut self, enabled: bool) -> Builder {
+        self.config.keys = Some(enabled);
+        self
+    }
+
+    /// starts every message with a section of string headers, e.g. a content type or a
+    /// trace id, costing [`HEADER_LEN_LEN`] bytes per message plus the headers. senders
+    /// set them with [`DiskRing::push_with_headers`] and receivers read them with
+    /// [`DiskRing::pop_with_headers`], every other push and pop leaves them empty or
+    /// skips them. frames can't be [reserved](DiskRing::reserve) with headers, nor
+    /// can messages be [split](Builder::split_messages).
+    ///
+    /// recorded in the info file just like the [`length_prefix`](Builder::length_prefix).
+    pub fn message_headers(mut self, enabled: bool) -> Builder {
+        self.config.headers = Some(enabled);
+        self
+    }
+
+    /// splits a message that doesn't fit on what's left of a page across the page
+    /// boundary instead of pushing all of it to the next page, so big messages don't
+    /// leave up to a whole message worth of space unused at the end of every page.
+    /// receivers put the two halves back together, it costs a byte per frame.
+    ///
+    /// the second half can end up behind messages other senders pushed to the next
+    /// page in the meantime. a receiver that starts reading after the first half of a
+    /// message (e.g. [`DiskRing::open_live`]), or only gets to it after the page it's on
+    /// was reclaimed, never sees that message. messages written in place with
+    /// [`DiskRing::reserve`] are never split.
+    ///
+    /// recorded in the info file just like the [`length_prefix`](Builder::length_prefix).
+    pub fn split_messages(mut self, enabled: bool) -> Builder {
+        self.config.split = Some(enabled);
+        self
+    }
+
+    /// numbers the pages of a new ringbuf from `n` on instead of 0, e.g. to give every
+    /// shard of a sharded deployment its own range of page numbers.
+    ///
+    /// recorded in the info file just like the [`length_prefix`](Builder::length_prefix).
+    pub fn start_page(mut self, n: usize) -> Builder {
+        self.config.start_page = Some(n);
+        self
+    }
+
+    /// creates the next `n` page files up front when the ringbuf is opened, so
+    /// the first `n` page flips don't have to create and size a file on the hot path.
+    ///
+    /// preallocated pages aren't part of the ringbuf until a sender flips onto them,
+    /// so they never count towards `max_qpages`.
+    pub fn preallocate_pages(mut self, n: usize) -> Builder {
+        self.config.preallocate_pages = n;
+        self
+    }
+
+    /// lets receivers that fell behind skip pages instead of reading everything.
+    ///
+    /// whenever a receiver finishes a page and finds the newest page is more than
+    /// `pages` ahead of it, it jumps straight to the page `pages` behind the newest one
+    /// without mapping anything in between. with zero it always jumps to the newest page.
+    /// by default receivers read every page that's still retained.
+    pub fn max_reader_lag(mut self, pages: usize) -> Builder {
+        self.config.max_reader_lag = Some(pages);
+        self
     }
 
-    eprintln!("took {} ms", now.elapsed().as_millis());
+    /// caps the disk space taken up by pages at `bytes`, reclaiming the oldest pages
+    /// once more would be needed.
+    ///
+    /// pages are always full size on disk, so this is turned into a page limit of
+    /// however many pages fit in `bytes` next to the info file and the
+    /// [preallocated](Builder::preallocate_pages) pages, and has to fit at least one.
+    /// if [`max_qpages`](Builder::max_qpages) is set too, whichever of the two allows
+    /// fewer pages wins.
+    ///
+    /// like `max_qpages` it's only used when the ringbuf is created, an existing one
+    /// keeps the limit it has. change that with [`reconfigure`] and
+    /// [`RuntimeConfig::set_max_bytes`].
+    pub fn max_bytes(mut self, bytes: u64) -> Builder {
+        self.config.max_bytes = Some(bytes);
+        self
+    }
+
+    /// the number of pages a new ringbuf keeps around before reclaiming the oldest one,
+    /// zero means unbounded. defaults to [`DEFAULT_MAX_QPAGES`] (or to whatever
+    /// [`max_bytes`](Builder::max_bytes) allows if that's set).
+    ///
+    /// only used when the ringbuf is created, an existing one keeps the limit it has,
+    /// including an unbounded one. change that with [`set_max_qpage`], which can also
+    /// set it ahead of creating the ringbuf (and then takes precedence over this).
+    pub fn max_qpages(mut self, n: usize) -> Builder {
+        self.config.max_qpages = Some(n);
+        self
+    }
+
+    /// what receivers do when they hit a frame with a length prefix that can't be right,
+    /// defaults to [`OnCorrupt::Error`]
+    pub fn on_corrupt(mut self, on_corrupt: OnCorrupt) -> Builder {
+        self.config.on_corrupt = on_corrupt;
+        self
+    }
+
+    /// bounds how many pages a single [`DiskRing::pop`] flips through without finding
+    /// a message, so a receiver that fell far behind a lot of empty or sealed pages
+    /// doesn't block its caller for long. once it hits the cap `pop` returns `Ok(None)`
+    /// even though there may be more to read, [`DiskRing::has_data`] tells the two apart
+    /// and the next `pop` carries on from there. by default `pop` flips as often as it
+    /// has to.
+    pub fn max_flips_per_pop(mut self, flips: usize) -> Builder {
+        self.config.max_flips_per_pop = Some(flips);
+        self
+    }
+
+    /// keeps the last `entries` messages a receiver decoded around in memory, so
+    /// [`DiskRing::re_read`]ing them doesn't have to parse the page again.
+    /// off by default.
+    pub fn read_cache(mut self, entries: usize) -> Builder {
+        self.config.read_cache = entries;
+        self
+    }
+
+    /// gives the receiver a persistent cursor stored as `<name>.cursor` in the ringbuf
+    /// directory. a receiver built with the same cursor name later resumes from the
+    /// last position committed to it, according to [`Builder::commit_policy`].
+    pub fn cursor(mut self, name: &str) -> Builder {
+        self.config.cursor = Some(name.to_string());
+        self
+    }
+
+    /// the clock handles go by for time based behavior, like [`CommitPolicy::Every`].
+    /// defaults to [`SystemClock`], tests can pass a [`ManualClock`] to control time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Builder {
+        self.config.clock = clock;
+        self
+    }
+
+    /// how often a receiver with a [cursor](Builder::cursor) commits its position,
+    /// defaults to [`CommitPolicy::Manual`]
+    pub fn commit_policy(mut self, policy: CommitPolicy) -> Builder {
+        self.config.commit_policy = policy;
+        self
+    }
+
+    /// most bytes a single [`DiskRing::pop_chunk`] copies out
+    /// (defaults to [`DEFAULT_INTERNAL_BUF_SIZE`])
+    pub fn chunk_size(mut self, bytes: usize) -> Builder {
+        self.config.chunk_size = bytes;
+        self
+    }
+
+    /// backs off between retries of a push that keeps finding its page full, e.g.
+    /// because lots of senders are racing to fill up fresh pages, instead of retrying
+    /// right away. it saves cpu on busy multi producer setups at the cost of some
+    /// latency, so by default senders don't back off at all.
+    pub fn push_backoff(mut self, policy: BackoffPolicy) -> Builder {
+        self.config.push_backoff = Some(policy);
+        self
+    }
+
+    /// makes senders [flush](DiskRing::flush) as part of a push once `interval` has
+    /// passed since they last flushed, so a crash of the whole machine loses at most
+    /// about that much of what they pushed without paying for a flush on every push.
+    ///
+    /// nothing flushes in the background: what a sender pushed last only hits the disk
+    /// with its next push after the interval, or an explicit flush. every clone keeps
+    /// its own schedule. a push that has to flush fails with the flush's error even
+    /// though the message itself made it onto the page. by default senders leave
+    /// flushing to the os.
+    pub fn flush_interval(mut self, interval: Duration) -> Builder {
+        self.config.flush_interval = Some(interval);
+        self
+    }
+
+    /// makes the sender [flush](DiskRing::flush) when it's dropped, so whatever a short
+    /// lived producer pushed last is on disk by the time it's gone instead of whenever
+    /// the os gets around to writing it back. every clone flushes when it's dropped.
+    ///
+    /// dropping it can't fail, so a failed flush is only traced (`flush_on_drop`) and
+    /// the drop waits for the disk however long that takes. a sender that's never
+    /// dropped, e.g. in a process that exits or crashes without unwinding, never
+    /// flushes either. by default dropping a sender does nothing.
+    pub fn flush_on_drop(mut self, enabled: bool) -> Builder {
+        self.config.flush_on_drop = enabled;
+        self
+    }
+
+    /// how receivers wait in blocking calls like [`DiskRing::stream`], see
+    /// [`ReaderBackoff`]. defaults to the cooperative [`BackoffPolicy::default`].
+    pub fn read_backoff(mut self, backoff: ReaderBackoff) -> Builder {
+        self.config.read_backoff = backoff;
+        self
+    }
+
+    /// asks the kernel to back page mappings with (2MB) transparent huge pages, which
+    /// takes a lot of pressure off the TLB for pages this big. whether it does depends
+    /// on the kernel and the filesystem the ringbuf is on, e.g. a tmpfs mounted with
+    /// `huge=advise`, and if it can't pages are mapped as usual.
+    ///
+    /// `MAP_HUGETLB` itself only works for anonymous mappings, for page files on a
+    /// hugetlbfs mount nothing needs to be turned on.
+    #[cfg(target_os = "linux")]
+    pub fn huge_pages(mut self, enabled: bool) -> Builder {
+        self.config.huge_pages = enabled;
+        self
+    }
+
+    /// makes the receiver [`build`](Builder::build) returns poll the files for new data
+    /// instead of relying on its mappings to follow them, for reading a ringbuf on a
+    /// networked filesystem like NFS, where a mapping on one host doesn't see writes
+    /// from another one in any timely fashion (or at all). see
+    /// [`DiskRing::open_polling`] to open just the receiver.
+    ///
+    /// the receiver only opens the files for reading and maps them privately, so
+    /// nothing it does ever reaches them. whenever it's caught up it opens them again,
+    /// which gets an NFS client to check its cache against the server (close-to-open
+    /// consistency), and maps them afresh. that's a couple of syscalls and page faults
+    /// on every poll that comes up empty, so back off between polls with
+    /// [`Builder::read_backoff`] rather than spinning.
+    ///
+    /// caveats:
+    /// - the senders still need one coherent view of the files, i.e. all of them on
+    ///   the same host (or a filesystem that keeps mappings coherent across hosts).
+    /// - new messages show up once the client's cache catches up, which can take as
+    ///   long as its attribute cache timeout (`actimeo`), not as soon as they're pushed.
+    /// - it has to be an existing ringbuf with an up to date info file, it can't be
+    ///   created or upgraded read-only.
+    /// - committing a [`cursor`](Builder::cursor) still writes the cursor file.
+    ///
+    /// the sender it's built alongside is a regular one.
+    #[cfg(unix)]
+    pub fn polling_read(mut self, enabled: bool) -> Builder {
+        self.config.polling_read = enabled;
+        self
+    }
+
+    /// unix permission bits (e.g. `0o640`) for the page and info files this ringbuf
+    /// creates, instead of the default `0o666`. like any newly created file they're
+    /// still masked by the process umask, and files that already exist keep theirs.
+    ///
+    /// a no-op on platforms other than unix.
+    pub fn file_mode(mut self, mode: u32) -> Builder {
+        self.config.file_mode = Some(mode);
+        self
+    }
+
+    pub fn build(self) -> Result<(DiskRing<Sender>, DiskRing<Receiver>), RingbufError> {
+        self.build_created().map(|(tx, rx, _)| (tx, rx))
+    }
+
+    // like build, but also tells whether the ringbuf was created by it, see open_or_create
+    fn build_created(self) -> Result<(DiskRing<Sender>, DiskRing<Receiver>, bool), RingbufError> {
+        self.config.validate()?;
+
+        if let RingDir::Path(path) = &self.dir {
+            std::fs::create_dir_all(path)?;
+        }
+
+        let config = Arc::new(self.config);
+        let (mut tx, created) = DiskRing::<Sender>::open_created(self.dir.clone(), config.clone())?;
+        tx.flush_on_drop = config.flush_on_drop;
+        #[cfg(unix)]
+        let mut rx = match config.polling_read {
+            true => DiskRing::open_polled(self.dir.clone(), config.clone())?,
+            false => DiskRing::open(self.dir.clone(), config.clone())?,
+        };
+        #[cfg(not(unix))]
+        let mut rx: DiskRing<Receiver> = DiskRing::open(self.dir.clone(), config.clone())?;
+        rx.restore_cursor()?;
+
+        for qpage_no in tx.qpage_no + 1..=tx.qpage_no + config.preallocate_pages {
+            QPage::allocate(
+                &self
+                    .dir
+                    .open_mode(&config.page_name(qpage_no), config.file_mode)?,
+            )?;
+        }
+
+        Ok((tx, rx, created))
+    }
+}
+
+/// lock in the info file guarding what's documented as guarded by it. std's locks
+/// only wake up waiters in the same process, so this one spins instead, yielding to
+/// whoever holds it. a writer waiting for readers to leave keeps new ones out.
+#[repr(transparent)]
+struct InfoLock(AtomicUsize);
+
+const INFO_LOCK_WRITER: usize = 1 << (usize::BITS - 1);
+
+impl InfoLock {
+    fn read(&self) -> InfoLockGuard<'_> {
+        loop {
+            let state = self.0.load(Ordering::Relaxed);
+            if state & INFO_LOCK_WRITER == 0
+                && self
+                    .0
+                    .compare_exchange_weak(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return InfoLockGuard {
+                    lock: &self.0,
+                    held: 1,
+                };
+            }
+
+            std::thread::yield_now();
+        }
+    }
+
+    fn write(&self) -> InfoLockGuard<'_> {
+        while self.0.fetch_or(INFO_LOCK_WRITER, Ordering::Acquire) & INFO_LOCK_WRITER != 0 {
+            std::thread::yield_now();
+        }
+
+        while self.0.load(Ordering::Acquire) != INFO_LOCK_WRITER {
+            std::thread::yield_now();
+        }
+
+        InfoLockGuard {
+            lock: &self.0,
+            held: INFO_LOCK_WRITER,
+        }
+    }
+
+    fn is_write_locked(&self) -> bool {
+        self.0.load(Ordering::Acquire) & INFO_LOCK_WRITER != 0
+    }
+}
+
+struct InfoLockGuard<'a> {
+    lock: &'a AtomicUsize,
+    held: usize,
+}
+
+impl Drop for InfoLockGuard<'_> {
+    fn drop(&mut self) {
+        self.lock.fetch_sub(self.held, Ordering::Release);
+    }
+}
+
+#[repr(C)]
+pub struct DiskRingInfo {
+    max_qpages: AtomicUsize,
+    // the page count of versions before 9, only read to upgrade from them. std's
+    // lock doesn't work across processes
+    legacy_qpage_count: RwLock<usize>,
+    length_prefix: AtomicU8,
+    byte_order: AtomicU8,
+    closed: AtomicBool,
+    sync_markers: AtomicU8,
+    // everything from here on was added in version 1
+    version: AtomicU8,
+    // guarded by the info lock
+    metadata_len: AtomicU16,
+    metadata: [u8; MAX_METADATA_LEN],
+    // version 2, both guarded by the info lock. the write byte
+    // is stored + 1 so a zeroed info file reads as not finished
+    final_qpage_no: AtomicUsize,
+    final_write_byte: AtomicUsize,
+    // version 3
+    keys: AtomicU8,
+    // version 4, messages on reclaimed pages. ones reclaimed by older versions
+    // weren't counted
+    dropped: AtomicU64,
+    // version 5
+    split: AtomicU8,
+    // version 6, number of the first page + 1 so a zeroed info file reads as not
+    // set yet. set along with `qpage_count` while holding the info lock
+    start_page: AtomicUsize,
+    // version 7
+    headers: AtomicU8,
+    // version 8, whether a page limit was set with `reconfigure` before the ringbuf
+    // was created, so the first opener keeps it. guarded by the info lock
+    max_qpages_set: AtomicBool,
+    // version 9, the newest page. senders move it on with a compare exchange, so
+    // only one of them gets to allocate each page
+    qpage_count: AtomicUsize,
+    lock: InfoLock,
+}
+
+impl DiskRingInfo {
+    /// a freshly created info file is all zeros, which is a valid info on its own: no
+    /// pages allocated yet (`qpage_count` 0 and an unlocked lock), unbounded until
+    /// the first opener sets the initial page limit, and every format setting unset.
+    /// so handles racing to open a new ringbuf all see the same thing, whoever gets to
+    /// grow the file first. growing it again is a no-op, and a file only ever grows to
+    /// the same size, so nothing one handle wrote is undone by another one opening it.
+    fn from_file(f: File) -> Result<MmapMutWrapper<DiskRingInfo>, RingbufError> {
+        qpage::extend_file(&f, std::mem::size_of::<Self>() as u64)?;
+        let m = unsafe { memmap2::MmapMut::map_mut(&f)? };
+
+        Ok(unsafe { MmapMutWrapper::<Self>::new(m) })
+    }
+
+    /// maps an info file opened only for reading privately, the way
+    /// [`QPage::map_read_only`] maps pages. unlike [`DiskRingInfo::from_file`] it can't
+    /// grow a file written by an older version.
+    #[cfg(unix)]
+    fn map_read_only(f: &File) -> Result<MmapMutWrapper<DiskRingInfo>, RingbufError> {
+        if f.metadata()?.len() < std::mem::size_of::<Self>() as u64 {
+            return Err(RingbufError::InvalidConfig(
+                "info file is too old to be read without upgrading it",
+            ));
+        }
+
+        let m = unsafe { memmap2::MmapOptions::new().map_copy_read_only(f)? };
+
+        Ok(unsafe { MmapMutWrapper::<Self>::new(m.make_mut()?) })
+    }
+
+    /// the length prefix of the ringbuf, recording `requested` (or the default)
+    /// if this is the first time anyone opened it
+    fn init_length_prefix(
+        &self,
+        requested: Option<LengthPrefix>,
+    ) -> Result<LengthPrefix, RingbufError> {
+        let stored = Self::init_code(&self.length_prefix, requested.unwrap_or_default().to_code());
+
+        let stored = LengthPrefix::from_code(stored).ok_or(RingbufError::InvalidConfig(
+            "unknown length prefix in info file",
+        ))?;
+
+        match requested {
+            Some(requested) if requested != stored => Err(RingbufError::InvalidConfig(
+                "length prefix doesn't match the existing ringbuf",
+            )),
+            _ => Ok(stored),
+        }
+    }
+
+    /// same as [`DiskRingInfo::init_length_prefix`] for the byte order
+    fn init_byte_order(&self, requested: Option<ByteOrder>) -> Result<ByteOrder, RingbufError> {
+        let stored = Self::init_code(&self.byte_order, requested.unwrap_or_default().to_code());
+
+        let stored = ByteOrder::from_code(stored).ok_or(RingbufError::InvalidConfig(
+            "unknown byte order in info file",
+        ))?;
+
+        match requested {
+            Some(requested) if requested != stored => Err(RingbufError::InvalidConfig(
+                "byte order doesn't match the existing ringbuf",
+            )),
+            _ => Ok(stored),
+        }
+    }
+
+    /// same as [`DiskRingInfo::init_length_prefix`] for sync markers
+    fn init_sync_markers(&self, requested: Option<bool>) -> Result<bool, RingbufError> {
+        Self::init_flag(
+            &self.sync_markers,
+            requested,
+            "unknown sync marker setting in info file",
+            "sync markers don't match the existing ringbuf",
+        )
+    }
+
+    /// same as [`DiskRingInfo::init_length_prefix`] for message keys
+    fn init_keys(&self, requested: Option<bool>) -> Result<bool, RingbufError> {
+        Self::init_flag(
+            &self.keys,
+            requested,
+            "unknown message key setting in info file",
+            "message keys don't match the existing ringbuf",
+        )
+    }
+
+    /// [`DiskRingInfo::format`] of a ringbuf some handle already set up, `None` if
+    /// there was none yet. unlike `format` it doesn't pin the default format into a
+    /// fresh info file, so a builder can still pick its own later.
+    fn existing_format(&self) -> Result<Option<FrameFormat>, RingbufError> {
+        if self.length_prefix.load(Ordering::Acquire) == 0 {
+            return Ok(None);
+        }
+
+        self.format().map(Some)
+    }
+
+    /// the frame format of an existing ringbuf, for handling its pages
+    /// without opening a sender or receiver
+    fn format(&self) -> Result<FrameFormat, RingbufError> {
+        self.init_version()?;
+
+        Ok(FrameFormat {
+            prefix: self.init_length_prefix(None)?,
+            byte_order: self.init_byte_order(None)?,
+            sync_markers: self.init_sync_markers(None)?,
+            keys: self.init_keys(None)?,
+            split: self.init_split(None)?,
+            headers: self.init_headers(None)?,
+        })
+    }
+
+    /// same as [`DiskRingInfo::init_length_prefix`] for splitting messages across pages
+    fn init_split(&self, requested: Option<bool>) -> Result<bool, RingbufError> {
+        Self::init_flag(
+            &self.split,
+            requested,
+            "unknown message splitting setting in info file",
+            "message splitting doesn't match the existing ringbuf",
+        )
+    }
+
+    /// same as [`DiskRingInfo::init_length_prefix`] for message headers
+    fn init_headers(&self, requested: Option<bool>) -> Result<bool, RingbufError> {
+        Self::init_flag(
+            &self.headers,
+            requested,
+            "unknown message header setting in info file",
+            "message headers don't match the existing ringbuf",
+        )
+    }
+
+    /// same as [`DiskRingInfo::init_length_prefix`] for the number of the first page,
+    /// which a new ringbuf starts counting its pages from. whoever records it also sets
+    /// the page limit the new ringbuf starts out with to `max_qpages`, unless one was
+    /// already set with [`reconfigure`].
+    ///
+    /// returns whether it was recorded just now, i.e. this opener created the ringbuf
+    fn init_start_page(
+        &self,
+        requested: Option<usize>,
+        max_qpages: usize,
+    ) -> Result<bool, RingbufError> {
+        let mut created = false;
+        let stored = match self.start_page.load(Ordering::Acquire) {
+            0 => {
+                let _lock = self.lock.write();
+
+                // someone else might have gotten here first
+                match self.start_page.load(Ordering::Acquire) {
+                    0 => {
+                        let start_page = requested.unwrap_or_default();
+                        self.qpage_count.store(start_page, Ordering::Release);
+                        // the first opener is the one creating the ringbuf, so it gets
+                        // to set the initial page limit before anyone can see it
+                        if !self.max_qpages_set.load(Ordering::Acquire) {
+                            self.max_qpages.store(max_qpages, Ordering::Release);
+                        }
+                        self.start_page.store(start_page + 1, Ordering::Release);
+                        created = true;
+
+                        start_page
+                    }
+                    stored => stored - 1,
+                }
+            }
+            stored => stored - 1,
+        };
+
+        match requested {
+            Some(requested) if requested != stored => Err(RingbufError::InvalidConfig(
+                "start page doesn't match the existing ringbuf",
+            )),
+            _ => Ok(created),
+        }
+    }
+
+    /// the number of the first page there ever was
+    fn start_page(&self) -> usize {
+        self.start_page.load(Ordering::Acquire).saturating_sub(1)
+    }
+
+    fn init_flag(
+        field: &AtomicU8,
+        requested: Option<bool>,
+        unknown: &'static str,
+        mismatch: &'static str,
+    ) -> Result<bool, RingbufError> {
+        let stored = match Self::init_code(field, requested.unwrap_or_default() as u8 + 1) {
+            1 => false,
+            2 => true,
+            _ => return Err(RingbufError::InvalidConfig(unknown)),
+        };
+
+        match requested {
+            Some(requested) if requested != stored => Err(RingbufError::InvalidConfig(mismatch)),
+            _ => Ok(stored),
+        }
+    }
+
+    /// upgrades an info file from an older version, refusing
+    /// ones written by a newer version of the crate
+    fn init_version(&self) -> Result<(), RingbufError> {
+        // the page count moved out of the lock of older versions. copied before the
+        // version is bumped, so nobody opening it afterwards sees it without
+        if self.version.load(Ordering::Acquire) < 9 {
+            let legacy = loop {
+                match self.legacy_qpage_count.try_read() {
+                    Ok(qpage_count) => break *qpage_count,
+                    Err(TryLockError::Poisoned(qpage_count)) => break *qpage_count.into_inner(),
+                    Err(TryLockError::WouldBlock) => std::thread::yield_now(),
+                }
+            };
+            let _ =
+                self.qpage_count
+                    .compare_exchange(0, legacy, Ordering::AcqRel, Ordering::Acquire);
+        }
+
+        let prev = self.version.fetch_max(INFO_VERSION, Ordering::AcqRel);
+
+        if prev > INFO_VERSION {
+            return Err(RingbufError::InvalidConfig(
+                "info file was written by a newer version",
+            ));
+        }
+
+        // anything opened before (it has a length prefix) by a version without message
+        // keys has frames without them, no matter what this opener asks for
+        let existing = prev != 0 || self.length_prefix.load(Ordering::Acquire) != 0;
+        if prev < 3 && existing {
+            Self::init_code(&self.keys, false as u8 + 1);
+        }
+        // same for splitting messages across pages
+        if prev < 5 && existing {
+            Self::init_code(&self.split, false as u8 + 1);
+        }
+        // and for page numbers, which always started at 0 before
+        if prev < 6 && existing {
+            let _ = self
+                .start_page
+                .compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire);
+        }
+        // and for message headers
+        if prev < 7 && existing {
+            Self::init_code(&self.headers, false as u8 + 1);
+        }
+
+        Ok(())
+    }
+
+    /// the page limit as [`reconfigure`] sees it. before the ringbuf is created that's
+    /// the default it would start out with, unless a limit was set already.
+    fn runtime_max_qpages(&self) -> usize {
+        match self.start_page.load(Ordering::Acquire) == 0
+            && !self.max_qpages_set.load(Ordering::Acquire)
+        {
+            true => DEFAULT_MAX_QPAGES,
+            false => self.max_qpages.load(Ordering::Acquire),
+        }
+    }
+
+    fn metadata(&self) -> Vec<u8> {
+        let _lock = self.lock.read();
+
+        self.read_metadata()
+    }
+
+    // has to be called with the info lock held
+    fn read_metadata(&self) -> Vec<u8> {
+        let len = self.metadata_len.load(Ordering::Relaxed) as usize;
+        self.metadata[..len.min(MAX_METADATA_LEN)].to_vec()
+    }
+
+    fn set_metadata(&self, metadata: &[u8]) -> Result<(), RingbufError> {
+        if metadata.len() > MAX_METADATA_LEN {
+            return Err(RingbufError::MetadataTooLong(metadata.len()));
+        }
+
+        let _lock = self.lock.write();
+        self.write_metadata(metadata);
+
+        Ok(())
+    }
+
+    // has to be called with the info lock held for writing, and with
+    // metadata that fits
+    fn write_metadata(&self, metadata: &[u8]) {
+        let super_scary_mutable_metadata = unsafe {
+            slice::from_raw_parts_mut(self.metadata.as_ptr().cast_mut(), MAX_METADATA_LEN)
+        };
+        super_scary_mutable_metadata[..metadata.len()].copy_from_slice(metadata);
+        self.metadata_len
+            .store(metadata.len() as u16, Ordering::Relaxed);
+    }
+
+    fn final_position(&self) -> Option<FinalPosition> {
+        let _lock = self.lock.read();
+
+        match self.final_write_byte.load(Ordering::Acquire) {
+            0 => None,
+            write_byte => Some(FinalPosition {
+                qpage_no: self.final_qpage_no.load(Ordering::Relaxed),
+                write_byte: write_byte - 1,
+            }),
+        }
+    }
+
+    // stores `code` unless an earlier opener already stored one
+    fn init_code(field: &AtomicU8, code: u8) -> u8 {
+        match field.compare_exchange(0, code, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => code,
+            Err(stored) => stored,
+        }
+    }
+}
+
+pub fn get_or_update_max_qpage<P: AsRef<Path>>(path: P, val: usize) -> Result<usize, RingbufError> {
+    Builder::new(path).get_or_update_max_qpage(val)
+}
+
+/// sets the number of pages the ringbuf keeps around (zero means unbounded) and returns the
+/// previous limit.
+///
+/// the new limit is published while holding the page allocation lock, so it's seen by
+/// every handle (in any process) from the next page flip on: senders reclaim against it
+/// as soon as one of them allocates a new page and receivers skip ahead based on it the
+/// next time they finish a page.
+///
+/// lowering the limit reclaims every page that's now over it right away. receivers
+/// that are in the middle of a reclaimed page keep their mapping of it (on unix) and
+/// skip ahead once they're done with it.
+pub fn set_max_qpage<P: AsRef<Path>>(path: P, val: usize) -> Result<usize, RingbufError> {
+    Builder::new(path).set_max_qpage(val)
+}
+
+/// the options of a ringbuf that can change while it's in use, see [`reconfigure`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    /// number of pages the ringbuf keeps around, zero means unbounded.
+    /// see [`set_max_qpage`]
+    pub max_qpages: usize,
+    /// up to [`MAX_METADATA_LEN`] bytes, see [`DiskRing::set_metadata`]
+    pub metadata: Vec<u8>,
+}
+
+impl RuntimeConfig {
+    /// keeps as many pages as fit in `bytes`, but at least one. unlike
+    /// [`Builder::max_bytes`] it only counts the pages themselves
+    pub fn set_max_bytes(&mut self, bytes: u64) {
+        self.max_qpages = ((bytes / PAGE_FILE_SIZE as u64) as usize).max(1);
+    }
+}
+
+/// changes several [runtime options](RuntimeConfig) of the ringbuf at `path` at once and
+/// returns what they were before. `f` gets the current options to change.
+///
+/// everything is applied while holding the page allocation lock, so no handle (in any
+/// process) ever sees some of the changes without the others, and they take effect
+/// just like with [`set_max_qpage`]. if the new options are invalid nothing changes.
+///
+/// `f` itself runs without the lock. if the options change in the meantime it's called
+/// again with the new ones, so it shouldn't do anything but change them.
+pub fn reconfigure<P: AsRef<Path>, F: FnMut(&mut RuntimeConfig)>(
+    path: P,
+    f: F,
+) -> Result<RuntimeConfig, RingbufError> {
+    Builder::new(path).reconfigure(f)
+}
+
+/// reads the user metadata of the ringbuf at `path` without opening
+/// a sender or receiver, see [`DiskRing::set_metadata`]
+pub fn get_metadata<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, RingbufError> {
+    Builder::new(path).get_metadata()
+}
+
+/// disk space taken up by a ringbuf, see [`usage`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Usage {
+    /// page files in the directory, including preallocated ones
+    pub pages_on_disk: usize,
+    /// apparent size of all page files
+    pub bytes_on_disk: u64,
+    /// blocks actually allocated for the page files, pages are sparse until written
+    /// so this is usually well below `bytes_on_disk`. same as `bytes_on_disk` where
+    /// the os doesn't report it
+    pub bytes_allocated: u64,
+    pub max_qpages: usize,
+    pub unbounded: bool,
+    /// messages on pages that were reclaimed over the ringbuf's lifetime,
+    /// see [`DiskRing::dropped_total`]
+    pub dropped_messages: u64,
+    /// messages still on the retained pages, see [`retained_messages`]
+    pub retained_messages: usize,
+}
+
+/// how much disk space the ringbuf at `path` is using, by statting its page files, and
+/// how many messages it holds. counting those walks the frame headers of every
+/// retained page like [`retained_messages`] does.
+pub fn usage<P: AsRef<Path>>(path: P) -> Result<Usage, RingbufError> {
+    Builder::new(path).usage()
+}
+
+/// how many messages the ringbuf at `path` still holds on the pages it retains, read
+/// or not, i.e. everything pushed to it minus what was reclaimed.
+///
+/// pages don't keep a count of their frames, so this walks the frame headers of every
+/// retained page. it never reads the messages themselves, which makes it far cheaper
+/// than popping everything, but the cost still grows with the number of messages.
+/// like [`head_position`] it doesn't count pushes that are still in flight.
+pub fn retained_messages<P: AsRef<Path>>(path: P) -> Result<usize, RingbufError> {
+    Builder::new(path).retained_messages()
+}
+
+fn count_retained(
+    config: &Config,
+    dir: &RingDir,
+    info: &DiskRingInfo,
+) -> Result<usize, RingbufError> {
+    count_since(
+        config,
+        dir,
+        info,
+        Position {
+            qpage_no: 0,
+            read_byte: 0,
+        },
+    )
+}
+
+// messages committed from `from` on, leaving out the ones on pages already reclaimed
+fn count_since(
+    config: &Config,
+    dir: &RingDir,
+    info: &DiskRingInfo,
+    from: Position,
+) -> Result<usize, RingbufError> {
+    // no handle set up the ringbuf yet, so there can't be any messages
+    let Some(format) = info.existing_format()? else {
+        return Ok(0);
+    };
+
+    let qpage_count = info.qpage_count.load(Ordering::Acquire);
+    let oldest = match info.max_qpages.load(Ordering::Acquire) {
+        0 => info.start_page(),
+        max_qpages => (qpage_count + 1).saturating_sub(max_qpages),
+    };
+
+    let mut count = 0;
+    for qpage_no in from.qpage_no.max(oldest)..=qpage_count {
+        // reclaimed or trimmed while we're looking, or the newest page isn't there
+        // (or isn't fully created) yet. mapping a short page would fault on the read
+        let mut page = match config.open_page_read_only(dir, qpage_no) {
+            Ok(page) => page,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let start_byte = match qpage_no == from.qpage_no {
+            true => from.read_byte,
+            false => 0,
+        };
+        let (committed, _) = page
+            .get_inner()
+            .count_committed(start_byte, usize::MAX, format);
+        count += committed;
+    }
+
+    Ok(count)
+}
+
+/// the end of what writers have committed to the newest page of the ringbuf at `path`,
+/// i.e. the furthest [`DiskRing::read_position`] a receiver could reach right now.
+/// compare it with the receivers' positions to tell how far behind they are.
+///
+/// never waits on writers in the middle of a push, whatever they haven't committed
+/// yet just isn't counted.
+pub fn head_position<P: AsRef<Path>>(path: P) -> Result<Position, RingbufError> {
+    Builder::new(path).head_position()
+}
+
+/// blocks until a sender has started page `page_no` of the ringbuf at `path`, or
+/// `timeout` passes, and returns whether it did, e.g. to start processing the page
+/// before it once the producer rolled over. waits for the ringbuf itself to show up
+/// too, without creating it.
+///
+/// it goes by the page count in the info file rather than the page files, so a page
+/// that was started and already reclaimed again still counts, and a preallocated
+/// page file that no sender started yet doesn't.
+pub fn wait_for_page<P: AsRef<Path>>(
+    path: P,
+    page_no: usize,
+    timeout: Duration,
+) -> Result<bool, RingbufError> {
+    Builder::new(path).wait_for_page(page_no, timeout)
+}
+
+/// checks every frame committed to page `page_no` of the ringbuf at `path`, see
+/// [`PageReport`]. it only reads the page, so it's safe to run next to live senders
+/// and receivers, e.g. from an fsck style tool. fails with [`RingbufError::NotFound`]
+/// if there's no such ringbuf or page.
+pub fn verify_page<P: AsRef<Path>>(path: P, page_no: usize) -> Result<PageReport, RingbufError> {
+    Builder::new(path).verify_page(page_no)
+}
+
+/// what's on a single page of a ringbuf, see [`page_metadata`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageMeta {
+    /// when the page file was last modified. writes through the mappings only bump it
+    /// once the os writes them back, see [`gc`]
+    pub modified: SystemTime,
+    /// bytes of the page taken up by committed frames, padding included
+    pub used_bytes: usize,
+    /// whole messages on the page, a message split across pages counts on the page
+    /// its tail is on
+    pub messages: usize,
+    /// whether the page is done, so nothing more will ever be pushed to it
+    pub full: bool,
+}
+
+/// what's on page `page_no` of the ringbuf at `path`, e.g. to decide which pages to
+/// archive or trim, without popping anything. `NotFound` if there's no such page.
+///
+/// reads the page header, and walks the frame headers to count the messages like
+/// [`retained_messages`] does. messages don't carry sequence numbers or timestamps,
+/// so there's no range of those to report. pushes still in flight aren't counted.
+pub fn page_metadata<P: AsRef<Path>>(path: P, page_no: usize) -> Result<PageMeta, RingbufError> {
+    Builder::new(path).page_metadata(page_no)
+}
+
+/// deletes every ringbuf directly under `root` (the directories with an info file)
+/// whose pages and info file all were last modified more than `older_than` ago,
+/// returning the directories it removed them from.
+///
+/// only the ringbuf's own files (pages, info file and cursors) are deleted, the
+/// directory itself only goes if nothing else is left in it.
+///
+/// writes through the page mappings only bump the modification time once the os
+/// writes them back, which can lag behind by a while, so keep `older_than` well
+/// above that (on linux, `vm.dirty_expire_centisecs`). on unix every open handle holds
+/// a lock on the info file, so a ringbuf that's open but idle for that long is left
+/// alone. elsewhere handles aren't tracked and it's deleted too.
+pub fn gc<P: AsRef<Path>>(root: P, older_than: Duration) -> Result<Vec<PathBuf>, RingbufError> {
+    Builder::new(root).gc(older_than)
+}
+
+/// renders the stats of the ringbuf at `path` in the prometheus text exposition format,
+/// labelled with `ringbuf="<path>"`. the lag of every consumer with a
+/// [cursor](Builder::cursor), i.e. how many messages it has yet to read, is labelled
+/// with `consumer="<cursor name>"` as well. everything is read without taking part in
+/// any pushes or pops, so it's safe to scrape a live ringbuf.
+///
+/// `disk_ringbuffer_up` is 0 (and nothing else is rendered) when the stats couldn't
+/// be read, e.g. because there's no ringbuf at `path`. nothing is created there.
+#[cfg(feature = "prometheus")]
+pub fn render_prometheus<P: AsRef<Path>>(path: P) -> String {
+    Builder::new(path).render_prometheus()
+}
+
+/// how long [`repair_stuck_writers`] waits for a page to show signs of life
+pub const REPAIR_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// unwedges pages that still have writers registered by producers that died mid-push
+/// (e.g. a SIGKILL'd process), which otherwise leaves every reader of that page spinning.
+///
+/// only pages whose write index doesn't move at all for [`REPAIR_GRACE_PERIOD`] are touched,
+/// and those get sealed at the last point readers could safely see, so messages pushed
+/// after that on the stuck page are dropped. this should only be run while no producers
+/// are alive, a live writer stuck in a huge copy for longer than the grace period is
+/// indistinguishable from a dead one.
+///
+/// returns the number of pages that were repaired.
+pub fn repair_stuck_writers<P: AsRef<Path>>(path: P) -> Result<usize, RingbufError> {
+    Builder::new(path).repair_stuck_writers()
+}
+
+pub fn new<P: AsRef<Path>>(
+    path: P,
+) -> Result<(DiskRing<Sender>, DiskRing<Receiver>), RingbufError> {
+    Builder::new(path).build()
+}
+
+/// like [`new`] but only opens a ringbuf that's already there, failing with
+/// [`RingbufError::NotFound`] instead of creating an empty one, e.g. when the
+/// path comes from user input where a typo shouldn't quietly make a new topic.
+pub fn open_existing<P: AsRef<Path>>(
+    path: P,
+) -> Result<(DiskRing<Sender>, DiskRing<Receiver>), RingbufError> {
+    let builder = Builder::new(path);
+
+    if !builder.dir.exists(Path::new(&builder.config.info_name))? {
+        return Err(RingbufError::NotFound);
+    }
+
+    builder.build()
+}
+
+/// like [`new`], but also tells whether the ringbuf was created just now (`true`) or
+/// already there, e.g. to seed it with initial data only once. of several openers
+/// racing to create the same ringbuf only the one that set it up sees `true`.
+pub fn open_or_create<P: AsRef<Path>>(
+    path: P,
+) -> Result<(DiskRing<Sender>, DiskRing<Receiver>, bool), RingbufError> {
+    Builder::new(path).build_created()
+}
+
+/// writes every message still in the ringbuf at `path`, oldest first, to a single
+/// archive at `out_file` that [`import`] turns back into a ringbuf, and returns how
+/// many messages it wrote. unlike the page files the archive only holds the messages
+/// themselves, so it's a lot smaller to copy around.
+///
+/// the archive is a short header followed by every message as a little endian `u32`
+/// length, its `u64` [key](Builder::message_keys) and its [headers](Builder::message_headers)
+/// if the ringbuf has them, and the message itself. messages pushed while this runs
+/// may or may not make it in.
+pub fn export<P: AsRef<Path>, Q: AsRef<Path>>(path: P, out_file: Q) -> Result<usize, RingbufError> {
+    Builder::new(path).export(out_file)
+}
+
+/// opens a receiver on the oldest page that's left of the existing ringbuf at `path`,
+/// instead of the newest one receivers usually start on
+fn open_oldest(builder: &Builder) -> Result<DiskRing<Receiver>, RingbufError> {
+    if !builder.dir.exists(Path::new(&builder.config.info_name))? {
+        return Err(RingbufError::NotFound);
+    }
+
+    // only reads, so a cursor the builder might have isn't restored or moved
+    let config = Config {
+        cursor: None,
+        ..builder.config.clone()
+    };
+    let mut rx = DiskRing::<Receiver>::open(builder.dir.clone(), Arc::new(config))?;
+
+    let info = rx.diskring_info.get_inner();
+    let max_qpages = info.max_qpages.load(Ordering::Acquire);
+    let oldest = match max_qpages {
+        0 => info.start_page(),
+        _ => (rx.qpage_no + 1).saturating_sub(max_qpages),
+    };
+    for qpage_no in oldest..rx.qpage_no {
+        if builder.dir.exists(&rx.config.page_name(qpage_no))? {
+            rx.qpage_no = qpage_no;
+            rx.qpage = rx.open_page(qpage_no)?;
+            break;
+        }
+    }
+
+    Ok(rx)
+}
+
+/// copies every message still in the ringbuf at `src`, oldest first, into the new
+/// ringbuf `dst` builds, and returns how many it copied. everything recorded in the
+/// info file (the [`length_prefix`](Builder::length_prefix), [`byte_order`](Builder::byte_order),
+/// [message keys](Builder::message_keys), ...) is fixed for the life of a ringbuf, so
+/// this is how to change it without losing data: migrate, point consumers at `dst`,
+/// then remove `src`.
+///
+/// the page size is a compile time constant rather than a setting, so it's the same
+/// on both sides. keys are carried over if both ringbufs have them. if only `src` has
+/// them, `dst` has to drop them explicitly with `message_keys(false)`, leaving it unset
+/// fails with [`RingbufError::InvalidConfig`]. headers are always carried over, `dst`
+/// gets them if `src` has them and turning them off fails with
+/// [`RingbufError::InvalidConfig`]. `dst` can't already hold a ringbuf, and messages
+/// pushed to `src` while this runs may or may not make it in.
+///
+/// `dst` is unbounded unless it sets [`max_qpages`](Builder::max_qpages) or
+/// [`max_bytes`](Builder::max_bytes), in which case messages copied early on can
+/// already be reclaimed again by the time this returns.
+pub fn migrate<P: AsRef<Path>>(src: P, dst: Builder) -> Result<usize, RingbufError> {
+    Builder::new(src).migrate(dst)
+}
+
+/// whether the ringbufs at `a` and `b` hold the same messages in the same order,
+/// e.g. to check a copy made with [`migrate`] or [`import`]. only the messages are
+/// compared (with their [headers](Builder::message_headers), and their
+/// [keys](Builder::message_keys) if both have them), not the pages, so ringbufs with
+/// different formats can still be equal. a ringbuf without headers compares as if
+/// every message had none.
+///
+/// like [`export`] it goes through everything that's still retained, oldest first,
+/// messages pushed while this runs may or may not be compared.
+pub fn streams_equal<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> Result<bool, RingbufError> {
+    Builder::new(a).streams_equal(&Builder::new(b))
+}
+
+/// pushes every message in an archive written by [`export`] to the ringbuf at `path`,
+/// creating it (unbounded) if needed, and returns how many it pushed. messages are
+/// appended to whatever the ringbuf already holds. an archive with message keys or
+/// headers can only go into a ringbuf that has them, see [`Builder::message_keys`] and
+/// [`Builder::message_headers`].
+pub fn import<P: AsRef<Path>, Q: AsRef<Path>>(archive: P, path: Q) -> Result<usize, RingbufError> {
+    Builder::new(path).import(archive)
+}
+
+// the free functions that work on a ringbuf without opening a sender or receiver
+// assume the default naming scheme. these are the same for the ringbuf a builder
+// would open, with its page extension and info name.
+impl Builder {
+    /// [`set_max_qpage`] for the ringbuf this builder opens
+    pub fn set_max_qpage(&self, val: usize) -> Result<usize, RingbufError> {
+        self.reconfigure(|runtime| runtime.max_qpages = val)
+            .map(|prev| prev.max_qpages)
+    }
+
+    /// [`get_or_update_max_qpage`] for the ringbuf this builder opens
+    pub fn get_or_update_max_qpage(&self, val: usize) -> Result<usize, RingbufError> {
+        let mut diskring_info = self.config.open_info(&self.dir)?;
+
+        let curr_max_qpages = diskring_info.get_inner().max_qpages.load(Ordering::Acquire);
+
+        if val == curr_max_qpages {
+            return Ok(val);
+        }
+
+        self.set_max_qpage(val)
+    }
+
+    /// [`reconfigure`] for the ringbuf this builder opens
+    pub fn reconfigure<F: FnMut(&mut RuntimeConfig)>(
+        &self,
+        mut f: F,
+    ) -> Result<RuntimeConfig, RingbufError> {
+        let mut diskring_info = self.config.open_info(&self.dir)?;
+        let info = diskring_info.get_inner();
+
+        // the lock lives in the info file, a panic in `f` while holding it would poison it
+        // for every handle opened on the ringbuf from then on
+        let (prev, runtime, lock) = loop {
+            let prev = RuntimeConfig {
+                max_qpages: info.runtime_max_qpages(),
+                metadata: info.metadata(),
+            };
+
+            let mut runtime = prev.clone();
+            f(&mut runtime);
+            if runtime.metadata.len() > MAX_METADATA_LEN {
+                return Err(RingbufError::MetadataTooLong(runtime.metadata.len()));
+            }
+
+            let lock = info.lock.write();
+            if info.runtime_max_qpages() == prev.max_qpages && info.read_metadata() == prev.metadata
+            {
+                break (prev, runtime, lock);
+            }
+        };
+
+        info.write_metadata(&runtime.metadata);
+        if runtime.max_qpages != prev.max_qpages {
+            info.max_qpages.store(runtime.max_qpages, Ordering::Release);
+            info.max_qpages_set.store(true, Ordering::Release);
+        }
+
+        let reclaimed = self.config.reclaim_pages(
+            &self.dir,
+            info.qpage_count.load(Ordering::Acquire),
+            runtime.max_qpages,
+        )?;
+        drop(lock);
+
+        reclaimed.count(info, info.existing_format()?);
+
+        Ok(prev)
+    }
+
+    /// [`get_metadata`] for the ringbuf this builder opens
+    pub fn get_metadata(&self) -> Result<Vec<u8>, RingbufError> {
+        Ok(self
+            .config
+            .open_existing_info(&self.dir)?
+            .get_inner()
+            .metadata())
+    }
+
+    /// [`usage`] for the ringbuf this builder opens
+    pub fn usage(&self) -> Result<Usage, RingbufError> {
+        let mut diskring_info = self.config.open_existing_info(&self.dir)?;
+
+        self.usage_of(diskring_info.get_inner())
+    }
+
+    fn usage_of(&self, info: &DiskRingInfo) -> Result<Usage, RingbufError> {
+        let max_qpages = info.max_qpages.load(Ordering::Acquire);
+
+        let mut usage = Usage {
+            pages_on_disk: 0,
+            bytes_on_disk: 0,
+            bytes_allocated: 0,
+            max_qpages,
+            unbounded: max_qpages == 0,
+            dropped_messages: info.dropped.load(Ordering::Relaxed),
+            retained_messages: count_retained(&self.config, &self.dir, info)?,
+        };
+
+        for name in self.dir.list()? {
+            if !self.config.is_page_name(&name) {
+                continue;
+            }
+
+            // pages can be reclaimed while we're looking
+            let meta = match self.dir.metadata(Path::new(&name)) {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            usage.pages_on_disk += 1;
+            usage.bytes_on_disk += meta.len();
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                usage.bytes_allocated += meta.blocks() * 512;
+            }
+            #[cfg(not(unix))]
+            {
+                usage.bytes_allocated += meta.len();
+            }
+        }
+
+        Ok(usage)
+    }
+
+    /// [`render_prometheus`] for the ringbuf this builder opens, labelled with its
+    /// directory
+    #[cfg(feature = "prometheus")]
+    pub fn render_prometheus(&self) -> String {
+        use std::fmt::Write as _;
+
+        let escape = |value: &str| {
+            value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        };
+        let ringbuf = match &self.dir {
+            RingDir::Path(path) => escape(&path.to_string_lossy()),
+            #[cfg(unix)]
+            RingDir::Fd(fd) => format!("fd:{}", fd.as_raw_fd()),
+        };
+
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, u64)]| {
+            let _ = writeln!(out, "# HELP disk_ringbuffer_{name} {help}");
+            let _ = writeln!(out, "# TYPE disk_ringbuffer_{name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(
+                    out,
+                    "disk_ringbuffer_{name}{{ringbuf=\"{ringbuf}\"{labels}}} {value}"
+                );
+            }
+        };
+        let mut gauge = |name: &str, help: &str, value: u64| {
+            metric(name, "gauge", help, &[(String::new(), value)])
+        };
+
+        let stats = self
+            .config
+            .open_existing_info(&self.dir)
+            .and_then(|mut diskring_info| {
+                let info = diskring_info.get_inner();
+
+                Ok((
+                    self.usage_of(info)?,
+                    info.qpage_count.load(Ordering::Acquire),
+                    info.closed.load(Ordering::Acquire),
+                    self.consumer_lags(info)?,
+                ))
+            });
+
+        let Ok((usage, qpage_count, closed, lags)) = stats else {
+            gauge("up", "Whether the ringbuf stats could be read.", 0);
+            return out;
+        };
+
+        gauge("up", "Whether the ringbuf stats could be read.", 1);
+        gauge(
+            "pages",
+            "Page files on disk, including preallocated ones.",
+            usage.pages_on_disk as u64,
+        );
+        gauge(
+            "head_page",
+            "Number of the page senders are currently writing to.",
+            qpage_count as u64,
+        );
+        gauge(
+            "max_pages",
+            "Pages kept before the oldest is reclaimed, 0 if unbounded.",
+            usage.max_qpages as u64,
+        );
+        gauge(
+            "bytes_on_disk",
+            "Apparent size of all page files.",
+            usage.bytes_on_disk,
+        );
+        gauge(
+            "bytes_allocated",
+            "Disk blocks actually allocated for the page files, in bytes.",
+            usage.bytes_allocated,
+        );
+        gauge(
+            "retained_messages",
+            "Messages still on the retained pages, read or not.",
+            usage.retained_messages as u64,
+        );
+        gauge(
+            "closed",
+            "Whether a sender closed the ringbuf.",
+            closed as u64,
+        );
+        metric(
+            "dropped_messages_total",
+            "counter",
+            "Messages on pages that were reclaimed.",
+            &[(String::new(), usage.dropped_messages)],
+        );
+        let lags: Vec<_> = lags
+            .into_iter()
+            .map(|(consumer, lag)| {
+                let labels = format!(",consumer=\"{}\"", escape(&consumer));
+                (labels, lag as u64)
+            })
+            .collect();
+        metric(
+            "consumer_lag_messages",
+            "gauge",
+            "Messages a consumer with a cursor has yet to read.",
+            &lags,
+        );
+
+        out
+    }
+
+    // how many messages every consumer with a cursor has yet to read, by cursor name
+    #[cfg(feature = "prometheus")]
+    fn consumer_lags(&self, info: &DiskRingInfo) -> Result<Vec<(String, usize)>, RingbufError> {
+        let mut lags = Vec::new();
+
+        for name in self.dir.list()? {
+            let name = Path::new(&name);
+            if name.extension() != Some("cursor".as_ref()) {
+                continue;
+            }
+
+            let mut contents = Vec::new();
+            match self.dir.open_read(name) {
+                Ok(mut f) => f.read_to_end(&mut contents)?,
+                // removed while we're looking
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            // nothing to compare a corrupt cursor with
+            let Some(pos) = decode_cursor(contents) else {
+                continue;
+            };
+
+            let consumer = name.file_stem().unwrap_or_default().to_string_lossy();
+            lags.push((
+                consumer.into_owned(),
+                count_since(&self.config, &self.dir, info, pos)?,
+            ));
+        }
+        lags.sort();
+
+        Ok(lags)
+    }
+
+    /// [`retained_messages`] for the ringbuf this builder opens
+    pub fn retained_messages(&self) -> Result<usize, RingbufError> {
+        let mut diskring_info = self.config.open_existing_info(&self.dir)?;
+
+        count_retained(&self.config, &self.dir, diskring_info.get_inner())
+    }
+
+    /// [`head_position`] for the ringbuf this builder opens
+    pub fn head_position(&self) -> Result<Position, RingbufError> {
+        let qpage_count = self
+            .config
+            .open_existing_info(&self.dir)?
+            .get_inner()
+            .qpage_count
+            .load(Ordering::Acquire);
+
+        // nothing was ever pushed to a ringbuf without pages, or to a page that
+        // isn't fully created yet
+        let read_byte = match self.config.open_page_read_only(&self.dir, qpage_count) {
+            Ok(mut qpage) => qpage.get_inner().committed_head(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Position {
+            qpage_no: qpage_count,
+            read_byte,
+        })
+    }
+
+    /// [`wait_for_page`] for the ringbuf this builder opens
+    pub fn wait_for_page(&self, page_no: usize, timeout: Duration) -> Result<bool, RingbufError> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = Backoff::new();
+        let mut diskring_info = None;
+
+        loop {
+            if diskring_info.is_none() {
+                diskring_info = match self.config.open_existing_info(&self.dir) {
+                    Ok(diskring_info) => Some(diskring_info),
+                    Err(RingbufError::NotFound) => None,
+                    Err(e) => return Err(e),
+                };
+            }
+
+            if let Some(diskring_info) = &mut diskring_info {
+                let info = diskring_info.get_inner();
+                let qpage_count = info.qpage_count.load(Ordering::Acquire);
+
+                // senders count a new page before creating its file
+                if qpage_count > page_no
+                    || (qpage_count == page_no
+                        && self.dir.exists(&self.config.page_name(page_no))?)
+                {
+                    return Ok(true);
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+
+            backoff.snooze();
+        }
+    }
+
+    /// [`verify_page`] for the ringbuf this builder opens
+    pub fn verify_page(&self, page_no: usize) -> Result<PageReport, RingbufError> {
+        let format = self
+            .config
+            .open_existing_info(&self.dir)?
+            .get_inner()
+            .format()?;
+
+        let f = match self.dir.open_read(&self.config.page_name(page_no)) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(RingbufError::NotFound)
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // mapping past the end of the file would fault on the first read
+        let len = f.metadata()?.len();
+        if len < PAGE_FILE_SIZE as u64 {
+            return Ok(PageReport {
+                anomalies: vec![PageAnomaly::Truncated { len }],
+                ..PageReport::default()
+            });
+        }
+
+        let report = QPage::map_read_only(&f)?.get_inner().verify(format);
+        trace!(
+            debug,
+            page_no,
+            frames = report.frames,
+            anomalies = report.anomalies.len(),
+            "verify_page"
+        );
+
+        Ok(report)
+    }
+
+    /// [`page_metadata`] for the ringbuf this builder opens
+    pub fn page_metadata(&self, page_no: usize) -> Result<PageMeta, RingbufError> {
+        let format = self
+            .config
+            .open_existing_info(&self.dir)?
+            .get_inner()
+            .format()?;
+
+        let f = match self.dir.open_read(&self.config.page_name(page_no)) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(RingbufError::NotFound)
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let metadata = f.metadata()?;
+        let modified = metadata.modified()?;
+
+        // a sender that just created it hasn't grown it yet, there's nothing on it
+        if metadata.len() < PAGE_FILE_SIZE as u64 {
+            return Ok(PageMeta {
+                modified,
+                used_bytes: 0,
+                messages: 0,
+                full: false,
+            });
+        }
+
+        let mut mapping = QPage::map_read_only(&f)?;
+        let qpage = mapping.get_inner();
+        let (messages, full) = qpage.count_committed(0, usize::MAX, format);
+
+        Ok(PageMeta {
+            modified,
+            used_bytes: qpage.committed_head(),
+            messages,
+            full,
+        })
+    }
+
+    /// [`gc`] of the ringbufs directly under this builder's directory that use its
+    /// naming scheme. the directory has to be given by path.
+    pub fn gc(&self, older_than: Duration) -> Result<Vec<PathBuf>, RingbufError> {
+        let config = &self.config;
+        let RingDir::Path(root) = &self.dir else {
+            return Err(RingbufError::InvalidConfig(
+                "gc needs the root directory by path",
+            ));
+        };
+        let Some(cutoff) = SystemTime::now().checked_sub(older_than) else {
+            return Ok(Vec::new());
+        };
+
+        let mut removed = Vec::new();
+
+        for entry in std::fs::read_dir(root)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if !entry.file_type()?.is_dir() || !config.info_path(&path).try_exists()? {
+                continue;
+            }
+
+            let mut idle = true;
+            for file in std::fs::read_dir(&path)? {
+                let file = file?;
+                let name = file.file_name();
+
+                if name != config.info_name.as_str() && !config.is_page_name(&name) {
+                    continue;
+                }
+
+                // pages can be reclaimed while we're looking
+                let modified = match file.metadata() {
+                    Ok(meta) => meta.modified()?,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e.into()),
+                };
+
+                if modified >= cutoff {
+                    idle = false;
+                    break;
+                }
+            }
+
+            if !idle {
+                continue;
+            }
+
+            let info_file = match File::open(config.info_path(&path)) {
+                Ok(f) => f,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            // held until everything's deleted, so no handle can open it meanwhile
+            #[cfg(unix)]
+            if !lock_info_file(&info_file, true)? {
+                continue;
+            }
+
+            for file in std::fs::read_dir(&path)? {
+                let name = file?.file_name();
+                let cursor = name
+                    .to_str()
+                    .is_some_and(|name| name.ends_with(".cursor") || name.ends_with(".cursor.tmp"));
+
+                if !config.is_page_name(&name) && !cursor {
+                    continue;
+                }
+
+                match std::fs::remove_file(path.join(name)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            std::fs::remove_file(config.info_path(&path))?;
+            drop(info_file);
+
+            // anything else in there isn't ours to delete
+            if std::fs::read_dir(&path)?.next().is_none() {
+                std::fs::remove_dir(&path)?;
+            }
+            removed.push(path);
+        }
+
+        Ok(removed)
+    }
+
+    /// [`repair_stuck_writers`] for the ringbuf this builder opens
+    pub fn repair_stuck_writers(&self) -> Result<usize, RingbufError> {
+        let mut diskring_info = self.config.open_existing_info(&self.dir)?;
+
+        let max_qpages = diskring_info.get_inner().max_qpages.load(Ordering::Acquire);
+        let qpage_count = diskring_info
+            .get_inner()
+            .qpage_count
+            .load(Ordering::Acquire);
+
+        let oldest_qpage = match max_qpages {
+            0 => diskring_info.get_inner().start_page(),
+            max_qpages => qpage_count.saturating_sub(max_qpages),
+        };
+
+        let mut stuck = Vec::new();
+
+        for qpage_no in oldest_qpage..=qpage_count {
+            let name = self.config.page_name(qpage_no);
+
+            // don't resurrect pages that were already reclaimed
+            if !self.dir.exists(&name)? {
+                continue;
+            }
+
+            let mut qpage = QPage::from_file(self.dir.open(&name)?)?;
+
+            if let Some(idx) = qpage.get_inner().registered_writers() {
+                stuck.push((qpage, idx));
+            }
+        }
+
+        if stuck.is_empty() {
+            return Ok(0);
+        }
+
+        std::thread::sleep(REPAIR_GRACE_PERIOD);
+
+        let mut repaired = 0;
+
+        for (mut qpage, idx) in stuck {
+            if qpage.get_inner().seal_stuck_writers(idx) {
+                repaired += 1;
+            }
+        }
+
+        Ok(repaired)
+    }
+
+    /// [`export`] of the ringbuf this builder opens
+    pub fn export<P: AsRef<Path>>(&self, out_file: P) -> Result<usize, RingbufError> {
+        let mut rx = open_oldest(self)?;
+        let (keys, headers) = (rx.format.keys, rx.format.headers);
+
+        let mut out = std::io::BufWriter::new(File::create(out_file)?);
+        out.write_all(&ARCHIVE_MAGIC)?;
+        out.write_all(&[keys as u8 | (headers as u8) << 1])?;
+
+        let mut count = 0;
+        loop {
+            match rx.find_next()? {
+                Found::Msg(at) => {
+                    let start_byte = rx.read_byte;
+                    let key = match keys {
+                        true => rx
+                            .qpage
+                            .get_inner()
+                            .frame_key(start_byte, rx.format)
+                            .expect("a frame that was just popped has a valid header"),
+                        false => 0,
+                    };
+                    let msg_headers = frame_headers_at(&mut rx, start_byte)?;
+                    let m = rx.popped_msg(at);
+
+                    out.write_all(&(m.len() as u32).to_le_bytes())?;
+                    if keys {
+                        out.write_all(&key.to_le_bytes())?;
+                    }
+                    if headers {
+                        write_archive_headers(&mut out, &msg_headers)?;
+                    }
+                    out.write_all(m)?;
+
+                    rx.read_byte = advance_read_byte(rx.read_byte, rx.popped_frame_len)?;
+                    count += 1;
+                }
+                Found::NoNewMsgs => break,
+                Found::PageDone => rx.page_flip()?,
+            }
+        }
+
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+        trace!(debug, count, "export");
+        Ok(count)
+    }
+
+    /// [`migrate`] from the ringbuf this builder opens
+    pub fn migrate(&self, dst: Builder) -> Result<usize, RingbufError> {
+        if dst.dir.exists(Path::new(&dst.config.info_name))? {
+            return Err(RingbufError::InvalidConfig(
+                "migration target already holds a ringbuf",
+            ));
+        }
+
+        let mut rx = open_oldest(self)?;
+        let dst = match dst.config.headers {
+            None => dst.message_headers(rx.format.headers),
+            Some(false) if rx.format.headers => {
+                return Err(RingbufError::InvalidConfig(
+                    "migration target has to keep the message headers",
+                ))
+            }
+            Some(_) => dst,
+        };
+        // losing the keys is fine, but only if asked for
+        if dst.config.keys.is_none() && rx.format.keys {
+            return Err(RingbufError::InvalidConfig(
+                "migration target has to keep or explicitly drop the message keys",
+            ));
+        }
+        // like import, `dst` has to hold everything unless asked to keep less
+        let dst = match (dst.config.max_qpages, dst.config.max_bytes) {
+            (None, None) => dst.max_qpages(0),
+            _ => dst,
+        };
+        let (mut tx, _) = dst.build()?;
+        let keys = rx.format.keys && tx.format.keys;
+
+        let mut count = 0;
+        loop {
+            match rx.find_next()? {
+                Found::Msg(at) => {
+                    let start_byte = rx.read_byte;
+                    let key = match keys {
+                        true => rx
+                            .qpage
+                            .get_inner()
+                            .frame_key(start_byte, rx.format)
+                            .expect("a frame that was just popped has a valid header"),
+                        false => 0,
+                    };
+
+                    let headers = frame_headers_at(&mut rx, start_byte)?;
+                    let m = rx.popped_msg(at);
+
+                    match rx.format.headers {
+                        true => {
+                            tx.push_headers_detailed(&headers, m, key)?;
+                        }
+                        false if keys => {
+                            tx.push_keyed(key, m)?;
+                        }
+                        false => {
+                            tx.push(m)?;
+                        }
+                    }
+
+                    rx.read_byte = advance_read_byte(rx.read_byte, rx.popped_frame_len)?;
+                    count += 1;
+                }
+                Found::NoNewMsgs => break,
+                Found::PageDone => rx.page_flip()?,
+            }
+        }
+
+        tx.flush()?;
+
+        trace!(debug, count, "migrate");
+        Ok(count)
+    }
+
+    /// [`streams_equal`] for the ringbuf this builder opens and the one `other` opens
+    pub fn streams_equal(&self, other: &Builder) -> Result<bool, RingbufError> {
+        let mut rx_a = open_oldest(self)?;
+        let mut rx_b = open_oldest(other)?;
+        let keys = rx_a.format.keys && rx_b.format.keys;
+
+        let popped_key = |rx: &DiskRing<Receiver>| {
+            rx.qpage
+                .clone()
+                .get_inner()
+                .frame_key(rx.read_byte - rx.popped_frame_len, rx.format)
+        };
+        let popped_headers = |rx: &mut DiskRing<Receiver>| {
+            let start_byte = rx.read_byte - rx.popped_frame_len;
+            frame_headers_at(rx, start_byte)
+        };
+
+        let (mut buf_a, mut buf_b) = (Vec::new(), Vec::new());
+        loop {
+            match (rx_a.pop_into(&mut buf_a)?, rx_b.pop_into(&mut buf_b)?) {
+                (None, None) => return Ok(true),
+                (Some(_), Some(_)) if buf_a == buf_b => {
+                    if keys && popped_key(&rx_a) != popped_key(&rx_b) {
+                        return Ok(false);
+                    }
+                    if popped_headers(&mut rx_a)? != popped_headers(&mut rx_b)? {
+                        return Ok(false);
+                    }
+                }
+                _ => return Ok(false),
+            }
+        }
+    }
+
+    /// [`import`] into the ringbuf this builder opens, which keeps the builder's
+    /// options. a new ringbuf is unbounded unless the builder sets a limit, and has
+    /// message keys and headers if the archive does.
+    pub fn import<P: AsRef<Path>>(self, archive: P) -> Result<usize, RingbufError> {
+        let mut archive = std::io::BufReader::new(File::open(archive)?);
+
+        let mut header = [0; ARCHIVE_MAGIC.len() + 1];
+        archive
+            .read_exact(&mut header)
+            .map_err(|_| RingbufError::InvalidArchive)?;
+
+        let (keys, headers) = match header.split_last() {
+            Some((&flags, magic)) if *magic == ARCHIVE_MAGIC && flags <= 0b11 => {
+                (flags & 0b01 != 0, flags & 0b10 != 0)
+            }
+            _ => return Err(RingbufError::InvalidArchive),
+        };
+
+        // a new ringbuf has to hold the whole archive, however many pages that takes
+        let builder = match (self.config.max_qpages, self.config.max_bytes) {
+            (None, None) => self.max_qpages(0),
+            _ => self,
+        };
+        let builder = match headers {
+            true => builder.message_headers(true),
+            false => builder,
+        };
+        let (mut tx, _rx) = builder.message_keys(keys).build()?;
+
+        let mut msg = Vec::new();
+        let mut count = 0;
+        loop {
+            // the archive can only end between two messages, anywhere else it's cut off
+            if archive.fill_buf()?.is_empty() {
+                break;
+            }
+
+            let mut len = [0; 4];
+            archive
+                .read_exact(&mut len)
+                .map_err(|_| RingbufError::InvalidArchive)?;
+
+            // checked before allocating anything for it, the headers section and the
+            // message have to fit in a single message of the new ringbuf together
+            let len = u32::from_le_bytes(len) as usize;
+            if len > tx.max_msg_len() {
+                return Err(RingbufError::InvalidArchive);
+            }
+
+            let mut key = [0; 8];
+            if keys {
+                archive
+                    .read_exact(&mut key)
+                    .map_err(|_| RingbufError::InvalidArchive)?;
+            }
+            let key = u64::from_le_bytes(key);
+
+            let msg_headers = match headers {
+                true => read_archive_headers(&mut archive, tx.max_msg_len() - len)?,
+                false => Vec::new(),
+            };
+
+            msg.resize(len, 0);
+            archive
+                .read_exact(&mut msg)
+                .map_err(|_| RingbufError::InvalidArchive)?;
+
+            match headers {
+                true => {
+                    tx.push_headers_detailed(&msg_headers, &msg, key)?;
+                }
+                // the key is 0 if the archive has none, which is all a ringbuf
+                // without keys takes
+                false => {
+                    tx.push_keyed_detailed(&msg, key)?;
+                }
+            }
+            count += 1;
+        }
+
+        tx.flush()?;
+
+        trace!(debug, count, "import");
+        Ok(count)
+    }
+}
+
+impl<T> DiskRing<T> {
+    /// opens a single handle on the ringbuf in the directory behind `fd`,
+    /// see [`Builder::from_dir_fd`]
+    #[cfg(unix)]
+    pub fn from_dir_fd(fd: OwnedFd) -> Result<DiskRing<T>, RingbufError> {
+        DiskRing::open(RingDir::Fd(Arc::new(fd)), Arc::default())
+    }
+
+    fn open(dir: RingDir, config: Arc<Config>) -> Result<DiskRing<T>, RingbufError> {
+        Self::open_created(dir, config).map(|(ring, _)| ring)
+    }
+
+    // like open, but also tells whether this was the first open, which created the ringbuf
+    fn open_created(
+        dir: RingDir,
+        config: Arc<Config>,
+    ) -> Result<(DiskRing<T>, bool), RingbufError> {
+        let info_file = loop {
+            let f = dir.open_mode(Path::new(&config.info_name), config.file_mode)?;
+
+            // see gc, which deletes the file we waited on. the ringbuf is created
+            // anew then
+            #[cfg(unix)]
+            {
+                lock_info_file(&f, false)?;
+                if std::os::unix::fs::MetadataExt::nlink(&f.metadata()?) == 0 {
+                    continue;
+                }
+            }
+
+            break f;
+        };
+        let mut diskring_info = DiskRingInfo::from_file(info_file.try_clone()?)?;
+
+        // has to come before the first page is opened, which is where numbering starts
+        let info = diskring_info.get_inner();
+        info.init_version()?;
+        let created = info.init_start_page(config.start_page, config.initial_max_qpages())?;
+
+        let qpage_no = info.qpage_count.load(Ordering::Acquire);
+        let qpage = config.open_page(&dir, qpage_no)?;
+
+        let ring = Self::from_mappings(
+            dir,
+            config,
+            diskring_info,
+            Some(Arc::new(info_file)),
+            qpage_no,
+            qpage,
+        )?;
+
+        Ok((ring, created))
+    }
+
+    // checks the format the config asks for against the info file and sets up a handle
+    // on page `qpage_no`
+    fn from_mappings(
+        dir: RingDir,
+        config: Arc<Config>,
+        mut diskring_info: MmapMutWrapper<DiskRingInfo>,
+        info_lock: Option<Arc<File>>,
+        qpage_no: usize,
+        qpage: MmapMutWrapper<QPage>,
+    ) -> Result<DiskRing<T>, RingbufError> {
+        let info = diskring_info.get_inner();
+        let format = FrameFormat {
+            prefix: info.init_length_prefix(config.length_prefix)?,
+            byte_order: info.init_byte_order(config.byte_order)?,
+            sync_markers: info.init_sync_markers(config.sync_markers)?,
+            keys: info.init_keys(config.keys)?,
+            split: info.init_split(config.split)?,
+            headers: info.init_headers(config.headers)?,
+        };
+
+        let read_cache = ReadCache {
+            capacity: config.read_cache,
+            ..ReadCache::default()
+        };
+        let commits = CommitState {
+            since_commit: 0,
+            last_commit: config.clock.now(),
+            committed: None,
+        };
+        let last_flush = config.clock.now();
+
+        Ok(DiskRing {
+            _kind: PhantomData,
+            dir,
+            config,
+            read_byte: 0,
+            corrupt_skips: 0,
+            format,
+            fmt_buf: Vec::new(),
+            read_cache,
+            chunk_progress: None,
+            stitcher: Stitcher::default(),
+            popped_frame_len: 0,
+            on_page_full: None,
+            on_reclaim_pressure: None,
+            reclaim_warned: None,
+            polling: false,
+            commits,
+            last_flush,
+            flush_on_drop: false,
+            _info_lock: info_lock,
+            diskring_info,
+            qpage,
+            qpage_no,
+        })
+    }
+
+    // the actual work of DiskRing::flush, which is only there for senders
+    fn sync_files(&self) -> Result<(), std::io::Error> {
+        self.dir
+            .open(&self.config.page_name(self.qpage_no))?
+            .sync_data()?;
+        self.dir
+            .open(Path::new(&self.config.info_name))?
+            .sync_data()
+    }
+
+    fn open_page(&self, qpage_no: usize) -> Result<MmapMutWrapper<QPage>, std::io::Error> {
+        match self.polling {
+            true => self.config.open_page_read_only(&self.dir, qpage_no),
+            false => self.config.open_page(&self.dir, qpage_no),
+        }
+    }
+
+    /// longest message that fits in a single frame of this ringbuf
+    pub fn max_msg_len(&self) -> usize {
+        self.format.max_msg_len()
+    }
+
+    /// user metadata stored alongside the ringbuf, empty until someone sets it
+    pub fn get_metadata(&mut self) -> Vec<u8> {
+        self.diskring_info.get_inner().metadata()
+    }
+
+    /// stores up to [`MAX_METADATA_LEN`] bytes of user metadata (schema ids, topic
+    /// names, ...) in the info file, replacing whatever was there. it's shared by
+    /// every handle on the ringbuf and kept across opens.
+    pub fn set_metadata(&mut self, metadata: &[u8]) -> Result<(), RingbufError> {
+        self.diskring_info.get_inner().set_metadata(metadata)
+    }
+
+    /// where the ringbuf ends if a sender [finished](DiskRing::finish) it
+    pub fn final_position(&mut self) -> Option<FinalPosition> {
+        self.diskring_info.get_inner().final_position()
+    }
+
+    /// whether a sender has [closed](DiskRing::close) the ringbuf
+    pub fn is_closed(&self) -> bool {
+        // the wrapper wants `&mut` to hand out the info, cloning it is just an arc bump
+        self.diskring_info
+            .clone()
+            .get_inner()
+            .closed
+            .load(Ordering::Acquire)
+    }
+}
+
+/// spins, then yields, then sleeps for longer and longer while waiting on
+/// other threads or processes
+pub(crate) struct Backoff {
+    step: u32,
+    // busy spins when there's none
+    policy: Option<BackoffPolicy>,
+}
+
+impl Backoff {
+    pub(crate) fn new() -> Backoff {
+        Backoff::with_policy(BackoffPolicy::default())
+    }
+
+    pub(crate) fn with_policy(policy: BackoffPolicy) -> Backoff {
+        Backoff {
+            step: 0,
+            policy: Some(policy),
+        }
+    }
+
+    fn for_reader(backoff: ReaderBackoff) -> Backoff {
+        match backoff {
+            ReaderBackoff::Cooperative(policy) => Backoff::with_policy(policy),
+            ReaderBackoff::BusySpin => Backoff {
+                step: 0,
+                policy: None,
+            },
+        }
+    }
+
+    pub(crate) fn snooze(&mut self) {
+        let Some(policy) = self.policy else {
+            std::hint::spin_loop();
+            return;
+        };
+
+        if self.step <= policy.spin_limit {
+            for _ in 0..1u64 << self.step.min(32) {
+                std::hint::spin_loop();
+            }
+        } else if self.step <= policy.yield_limit {
+            std::thread::yield_now();
+        } else {
+            let sleep = 1u64
+                .checked_shl(self.step - policy.yield_limit)
+                .map_or(Duration::MAX, Duration::from_micros);
+            if sleep >= policy.max_sleep {
+                std::thread::sleep(policy.max_sleep);
+                return;
+            }
+
+            std::thread::sleep(sleep);
+        }
+
+        self.step += 1;
+    }
+}
+
+/// blocking iterator over a receiver, see [`DiskRing::stream`]
+pub struct RecvStream<'a> {
+    rx: &'a mut DiskRing<Receiver>,
+}
+
+impl Iterator for RecvStream<'_> {
+    type Item = Result<String, RingbufError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut backoff = Backoff::for_reader(self.rx.config.read_backoff);
+
+        loop {
+            // has to be checked before popping, everything pushed before the
+            // close is visible by then so coming up empty really is the end
+            let closed = self.rx.is_closed();
+
+            match self.rx.pop() {
+                Ok(Some(m)) => return Some(Ok(m)),
+                Ok(None) if closed => return None,
+                Ok(None) => backoff.snooze(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// a frame reserved on the current page, see [`DiskRing::reserve`].
+///
+/// derefs to the message area so it can be filled in place. dropping it without
+/// [`FrameWriter::commit`]ting leaves a padding frame that receivers skip.
+pub struct FrameWriter<'a> {
+    tx: &'a mut DiskRing<Sender>,
+    start_idx: usize,
+    reserved_len: usize,
+    max_len: usize,
+    done: bool,
+}
+
+impl FrameWriter<'_> {
+    /// finishes the frame with the first `actual_len` bytes of the message area and
+    /// makes it visible to receivers, returning the number of bytes it takes up
+    pub fn commit(mut self, actual_len: usize) -> Result<usize, RingbufError> {
+        if actual_len > self.max_len {
+            return Err(RingbufError::InvalidConfig(
+                "committed length is longer than the reservation",
+            ));
+        }
+
+        let qpage = self.tx.qpage.get_inner();
+        qpage.commit_reserved(
+            self.start_idx,
+            self.reserved_len,
+            self.max_len,
+            actual_len,
+            self.tx.format,
+        );
+        qpage.release();
+        self.done = true;
+
+        let frame_len = self.tx.format.frame_len(actual_len);
+        trace!(
+            trace,
+            qpage_no = self.tx.qpage_no,
+            bytes = frame_len,
+            "push"
+        );
+        self.tx.flush_when_due()?;
+
+        Ok(frame_len)
+    }
+}
+
+impl std::ops::Deref for FrameWriter<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        let msg_start = self.start_idx + self.tx.format.header_len(self.max_len);
+        self.tx
+            .qpage
+            .clone()
+            .get_inner()
+            .reserved_mut(msg_start, self.max_len)
+    }
+}
+
+impl std::ops::DerefMut for FrameWriter<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let msg_start = self.start_idx + self.tx.format.header_len(self.max_len);
+        self.tx
+            .qpage
+            .get_inner()
+            .reserved_mut(msg_start, self.max_len)
+    }
+}
+
+impl Drop for FrameWriter<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        let qpage = self.tx.qpage.get_inner();
+        qpage.abort_reserved(self.start_idx, self.reserved_len, self.tx.format);
+        qpage.release();
+    }
+}
+
+// drop can't be implemented for DiskRing<Sender> alone, receivers never set the flag
+impl<T> Drop for DiskRing<T> {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn drop(&mut self) {
+        if !self.flush_on_drop {
+            return;
+        }
+
+        if let Err(e) = self.sync_files() {
+            trace!(warn, qpage_no = self.qpage_no, error = %e, "flush_on_drop");
+        }
+    }
+}
+
+/// reads a receiver's messages up to where writers were when it was taken,
+/// see [`DiskRing::snapshot`]
+#[derive(Debug)]
+pub struct SnapshotReader {
+    rx: DiskRing<Receiver>,
+    end: Position,
+}
+
+impl SnapshotReader {
+    /// the next message from before the snapshot was taken, `None` once it gets
+    /// to where writers were back then, however much was pushed since
+    pub fn pop(&mut self) -> Result<Option<String>, RingbufError> {
+        loop {
+            if self.is_at_end() {
+                return Ok(None);
+            }
+
+            match self.rx.find_next()? {
+                // anything padding led up to might be newer
+                Found::Msg(_) if self.is_at_end() => return Ok(None),
+                Found::Msg(at) => {
+                    let msg = String::from_utf8_lossy(self.rx.popped_msg(at)).into_owned();
+                    self.rx.read_byte =
+                        advance_read_byte(self.rx.read_byte, self.rx.popped_frame_len)?;
+
+                    return Ok(Some(msg));
+                }
+                Found::NoNewMsgs => return Ok(None),
+                Found::PageDone => self.rx.page_flip()?,
+            }
+        }
+    }
+
+    /// where the snapshot ends
+    pub fn end(&self) -> Position {
+        self.end
+    }
+
+    fn is_at_end(&self) -> bool {
+        (self.rx.qpage_no, self.rx.read_byte) >= (self.end.qpage_no, self.end.read_byte)
+    }
+}
+
+impl Iterator for SnapshotReader {
+    type Item = Result<String, RingbufError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pop().transpose()
+    }
+}
+
+impl Iterator for DiskRing<Receiver> {
+    type Item = Result<Option<String>, RingbufError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.pop())
+    }
+}
+
+// maps the info file of a polling receiver afresh over `diskring_info`
+#[cfg(unix)]
+fn remap_polled_info(
+    dir: &RingDir,
+    config: &Config,
+    diskring_info: &mut MmapMutWrapper<DiskRingInfo>,
+) -> Result<(), RingbufError> {
+    let mut backoff = Backoff::for_reader(config.read_backoff);
+
+    loop {
+        // opening it again is what gets an nfs client to check its cached copy against
+        // the server (close-to-open consistency), a mapping on its own never does
+        let f = dir.open_read(Path::new(&config.info_name))?;
+        unsafe { qpage::remap_private(diskring_info, &f)? };
+
+        // receivers only take the info lock for reading, which they can on any copy of
+        // it that nobody held for writing. one caught while someone did would never be
+        // released in the copy, so it has to be remapped
+        if !diskring_info.get_inner().lock.is_write_locked() {
+            return Ok(());
+        }
+
+        backoff.snooze();
+    }
+}
+
+/// the page a receiver on `qpage_no` moves on to, skipping pages senders already
+/// reclaimed and anything further behind than [`Builder::max_reader_lag`] allows.
+/// also returns how many of the skipped pages were reclaimed, the ones skipped for
+/// the lag limit aren't counted.
+fn next_read_page(info: &DiskRingInfo, config: &Config, qpage_no: usize) -> (usize, usize) {
+    let max_qpages = info.max_qpages.load(Ordering::Acquire);
+
+    if max_qpages > 0 || config.max_reader_lag.is_some() {
+        let qpage_count = info.qpage_count.load(Ordering::Acquire);
+
+        let mut next = qpage_no + 1;
+
+        if max_qpages > 0 {
+            // the oldest page senders haven't reclaimed yet
+            next = next.max((qpage_count + 1).saturating_sub(max_qpages));
+        }
+        let reclaimed = next - (qpage_no + 1);
+
+        if let Some(max_reader_lag) = config.max_reader_lag {
+            next = next.max(qpage_count.saturating_sub(max_reader_lag));
+        }
+
+        (next, reclaimed)
+    } else {
+        (qpage_no + 1, 0)
+    }
+}
+
+// the position stored in a cursor file, see DiskRing::commit
+fn decode_cursor(contents: Vec<u8>) -> Option<Position> {
+    let contents = <[u8; 16]>::try_from(contents).ok()?;
+
+    Some(Position {
+        qpage_no: u64::from_le_bytes(contents[..8].try_into().expect("8 bytes")) as usize,
+        read_byte: u64::from_le_bytes(contents[8..].try_into().expect("8 bytes")) as usize,
+    })
+}
+
+// keys and values of a message's headers, see Builder::message_headers
+type OwnedHeaders = Vec<(Vec<u8>, Vec<u8>)>;
+
+// the headers of the frame starting at `start_byte` on the receiver's page, none if
+// the ringbuf doesn't have them
+fn frame_headers_at(
+    rx: &mut DiskRing<Receiver>,
+    start_byte: usize,
+) -> Result<OwnedHeaders, RingbufError> {
+    if !rx.format.headers {
+        return Ok(Vec::new());
+    }
+
+    let format = rx.format;
+    let section = rx
+        .qpage
+        .get_inner()
+        .frame_headers(start_byte, format)
+        .ok_or(RingbufError::ReadError)?;
+
+    Ok(format
+        .decode_headers(section)
+        .into_iter()
+        .map(|(key, value)| (key.to_vec(), value.to_vec()))
+        .collect())
+}
+
+// headers in an archive: their count, then every key and value each behind its
+// own length, all as little endian `u16`s
+fn write_archive_headers<W: Write>(
+    out: &mut W,
+    headers: &[(Vec<u8>, Vec<u8>)],
+) -> Result<(), std::io::Error> {
+    out.write_all(&(headers.len() as u16).to_le_bytes())?;
+    for field in headers.iter().flat_map(|(key, value)| [key, value]) {
+        out.write_all(&(field.len() as u16).to_le_bytes())?;
+        out.write_all(field)?;
+    }
+
+    Ok(())
+}
+
+// see write_archive_headers
+fn read_archive_headers<R: Read>(
+    archive: &mut R,
+    max_section_len: usize,
+) -> Result<OwnedHeaders, RingbufError> {
+    let count = read_archive_u16(archive)?;
+    let mut section_len = HEADER_LEN_LEN;
+
+    // every length is checked against what's left before reading what it's for
+    let mut read_field = |archive: &mut R| {
+        let len = read_archive_u16(archive)?;
+        section_len += HEADER_LEN_LEN + len;
+        if section_len > max_section_len {
+            return Err(RingbufError::InvalidArchive);
+        }
+
+        let mut field = vec![0; len];
+        archive
+            .read_exact(&mut field)
+            .map_err(|_| RingbufError::InvalidArchive)?;
+
+        Ok(field)
+    };
+
+    (0..count)
+        .map(|_| Ok((read_field(archive)?, read_field(archive)?)))
+        .collect()
+}
+
+fn read_archive_u16<R: Read>(archive: &mut R) -> Result<usize, RingbufError> {
+    let mut n = [0; HEADER_LEN_LEN];
+    archive
+        .read_exact(&mut n)
+        .map_err(|_| RingbufError::InvalidArchive)?;
+
+    Ok(u16::from_le_bytes(n) as usize)
+}
+
+// where the reader ends up after `len` more bytes, which a corrupt length
+// must never send past the end of the page
+fn advance_read_byte(read_byte: usize, len: usize) -> Result<usize, RingbufError> {
+    read_byte
+        .checked_add(len)
+        .filter(|&read_byte| read_byte <= qpage::DEFAULT_QUEUE_SIZE)
+        .ok_or(RingbufError::ReadError)
+}
+
+impl DiskRing<Receiver> {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<DiskRing<Receiver>, RingbufError> {
+        DiskRing::open(RingDir::Path(path.as_ref().into()), Arc::default())
+    }
+
+    /// opens a receiver on the existing ringbuf at `path` that polls its files for new
+    /// data rather than relying on its mappings, e.g. one a sender on another host
+    /// writes to over NFS. it only ever reads the files, see [`Builder::polling_read`]
+    /// for how it works and its caveats.
+    #[cfg(unix)]
+    pub fn open_polling<P: AsRef<Path>>(path: P) -> Result<DiskRing<Receiver>, RingbufError> {
+        DiskRing::open_polled(RingDir::Path(path.as_ref().into()), Arc::default())
+    }
+
+    #[cfg(unix)]
+    fn open_polled(dir: RingDir, config: Arc<Config>) -> Result<DiskRing<Receiver>, RingbufError> {
+        let info_file = match dir.open_read(Path::new(&config.info_name)) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(RingbufError::NotFound)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut diskring_info = DiskRingInfo::map_read_only(&info_file)?;
+        // not every network filesystem has locks, without one gc can't see this handle
+        let info_lock = lock_info_file(&info_file, false)
+            .is_ok()
+            .then(|| Arc::new(info_file));
+        remap_polled_info(&dir, &config, &mut diskring_info)?;
+
+        let info = diskring_info.get_inner();
+        info.init_version()?;
+        let qpage_no = info.qpage_count.load(Ordering::Acquire);
+        let qpage = config.open_page_read_only(&dir, qpage_no)?;
+
+        let mut rx =
+            DiskRing::from_mappings(dir, config, diskring_info, info_lock, qpage_no, qpage)?;
+        rx.polling = true;
+
+        Ok(rx)
+    }
+
+    /// maps the page and info file of a [polling](Builder::polling_read) receiver
+    /// afresh, to see whatever senders did to them since the last time
+    #[cfg(unix)]
+    fn remap_polled(&mut self) -> Result<(), RingbufError> {
+        remap_polled_info(&self.dir, &self.config, &mut self.diskring_info)?;
+
+        match self.dir.open_read(&self.config.page_name(self.qpage_no)) {
+            Ok(f) => unsafe { qpage::remap_private(&mut self.qpage, &f)? },
+            // reclaimed, what's left of it stays readable as it was
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        trace!(trace, qpage_no = self.qpage_no, "remap_polled");
+        Ok(())
+    }
+
+    // whether a polling receiver done with its page can flip to the next one yet.
+    // senders count the newest page before creating it, and this receiver can't
+    // create it for them
+    #[cfg(unix)]
+    fn next_page_created(&mut self) -> Result<bool, RingbufError> {
+        let info = self.diskring_info.get_inner();
+        let (qpage_no, _) = next_read_page(info, &self.config, self.qpage_no);
+        if qpage_no < info.qpage_count.load(Ordering::Acquire) {
+            return Ok(true);
+        }
+
+        match self.config.open_page_read_only(&self.dir, qpage_no) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// opens a receiver at the current write head, like `tail -f`, so it skips
+    /// everything already in the ringbuf and only sees what's pushed afterwards.
+    ///
+    /// pushes still in flight while it's opened are waited for and skipped too.
+    /// a push that reserves its space after that is seen, including one that
+    /// lands on a newer page while this is still positioning on the old one.
+    pub fn open_live<P: AsRef<Path>>(path: P) -> Result<DiskRing<Receiver>, RingbufError> {
+        let mut rx = DiskRing::<Receiver>::new(path)?;
+        rx.read_byte = rx.qpage.get_inner().tail(rx.config.writer_stall_timeout)?;
+
+        Ok(rx)
+    }
+
+    fn page_flip(&mut self) -> Result<(), RingbufError> {
+        let mut info = self.diskring_info.clone();
+        let info = info.get_inner();
+        let (mut qpage_no, mut reclaimed) = next_read_page(info, &self.config, self.qpage_no);
+
+        // pages are only reclaimed with the info lock held for writing, so none can go
+        // missing between checking for it and opening it
+        let lock = info.lock.read();
+        let qpage_count = info.qpage_count.load(Ordering::Acquire);
+
+        // senders count the newest page before creating it, every older one was
+        // created already. one that isn't there was reclaimed after next_read_page
+        // looked, opening it would bring it back empty and hide that it's gone
+        while qpage_no < qpage_count && !self.dir.exists(&self.config.page_name(qpage_no))? {
+            qpage_no += 1;
+            reclaimed += 1;
+        }
+
+        trace!(debug, from = self.qpage_no, to = qpage_no, "page_flip");
+
+        self.qpage = self.open_page(qpage_no)?;
+        self.qpage_no = qpage_no;
+        self.read_byte = 0;
+        drop(lock);
+        self.stitcher.forget_reclaimed(info);
+
+        if reclaimed > 0 {
+            trace!(warn, qpage_no, reclaimed, "lagged");
+            return Err(RingbufError::Lagged(reclaimed));
+        }
+
+        Ok(())
+    }
+
+    /// a point in time view of the ringbuf: reads from where this receiver is up to
+    /// what writers have committed right now, like [`head_position`], and stops
+    /// there even if more gets pushed later. this receiver doesn't move.
+    pub fn snapshot(&self) -> Result<SnapshotReader, RingbufError> {
+        let mut rx = self.clone();
+
+        let qpage_no = rx
+            .diskring_info
+            .get_inner()
+            .qpage_count
+            .load(Ordering::Acquire);
+        let mut qpage = match qpage_no == rx.qpage_no {
+            true => rx.qpage.clone(),
+            false => rx.open_page(qpage_no)?,
+        };
+
+        let end = Position {
+            qpage_no,
+            read_byte: qpage.get_inner().committed_head(),
+        };
+        trace!(debug, qpage_no, read_byte = end.read_byte, "snapshot");
+
+        Ok(SnapshotReader { rx, end })
+    }
+
+    /// the last `n` messages still in the ringbuf, oldest first, e.g. to show recent
+    /// activity. like a [`snapshot`](DiskRing::snapshot) it stops at what writers have
+    /// committed right now, and this receiver doesn't move. returns fewer if the ringbuf
+    /// doesn't hold `n` messages anymore, and can come up a few short while senders are
+    /// pushing.
+    pub fn tail(&self, n: usize) -> Result<Vec<String>, RingbufError> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut reader = self.snapshot()?;
+        let rx = &mut reader.rx;
+        let mut info = rx.diskring_info.clone();
+        let info = info.get_inner();
+
+        // pages are only reclaimed with the info lock held for writing, so none go
+        // missing (and get brought back empty by opening them) while walking back
+        let lock = info.lock.read();
+        let oldest = match info.max_qpages.load(Ordering::Acquire) {
+            0 => info.start_page(),
+            max_qpages => (info.qpage_count.load(Ordering::Acquire) + 1).saturating_sub(max_qpages),
+        };
+
+        // counting is a lot cheaper than decoding, find the page the last n start on
+        let mut first = reader.end.qpage_no;
+        let mut count = 0;
+        loop {
+            count += rx
+                .open_page(first)?
+                .get_inner()
+                .count_committed(0, n - count, rx.format)
+                .0;
+            if count >= n || first <= oldest || !rx.dir.exists(&rx.config.page_name(first - 1))? {
+                break;
+            }
+            first -= 1;
+        }
+
+        // the first message counted may be the tail of one that started a page earlier
+        if rx.format.split && first > oldest && rx.dir.exists(&rx.config.page_name(first - 1))? {
+            first -= 1;
+        }
+
+        rx.qpage = rx.open_page(first)?;
+        rx.qpage_no = first;
+        rx.read_byte = 0;
+        rx.stitcher = Stitcher::default();
+        drop(lock);
+
+        let mut msgs = VecDeque::with_capacity(n);
+        for msg in reader {
+            if msgs.len() == n {
+                msgs.pop_front();
+            }
+            msgs.push_back(msg?);
+        }
+
+        Ok(msgs.into())
+    }
+
+    /// cheap readiness check for event loops, reads the page indices once and never
+    /// spins or blocks. `false` can be stale while a push is still in flight, and a
+    /// finished page reads as `true` even if the next one is still empty.
+    pub fn has_data(&self) -> bool {
+        // the wrapper wants `&mut` to hand out the page, cloning it is just an arc bump
+        self.qpage.clone().get_inner().has_committed(self.read_byte)
+    }
+
+    /// where the next message will be popped from, pass it to [`DiskRing::re_read`] to
+    /// read that message again later
+    pub fn read_position(&self) -> Position {
+        Position {
+            qpage_no: self.qpage_no,
+            read_byte: self.read_byte,
+        }
+    }
+
+    /// reads the message at `pos` again without moving the receiver, served from the
+    /// [read cache](Builder::read_cache) when it's in there.
+    ///
+    /// returns `None` when there's no message at `pos` (yet), or when its page has been
+    /// reclaimed since.
+    pub fn re_read(&mut self, pos: Position) -> Result<Option<String>, RingbufError> {
+        if self.is_reclaimed(pos.qpage_no) {
+            self.read_cache.invalidate_page(pos.qpage_no);
+            return Ok(None);
+        }
+
+        if let Some(msg) = self.read_cache.get(pos) {
+            return Ok(Some(msg));
+        }
+
+        let mut qpage = match pos.qpage_no == self.qpage_no {
+            true => self.qpage.clone(),
+            false => self.open_page(pos.qpage_no)?,
+        };
+
+        let msg = match self.read_frame(&mut qpage, pos.read_byte)? {
+            Some((FrameKind::Whole, m)) => String::from_utf8_lossy(&m).into_owned(),
+            Some((FrameKind::Tail, tail)) if tail.len() >= ORIGIN_LEN => {
+                let origin = Position {
+                    qpage_no: u64::from_le_bytes(tail[..8].try_into().unwrap()) as usize,
+                    read_byte: u64::from_le_bytes(tail[8..ORIGIN_LEN].try_into().unwrap()) as usize,
+                };
+
+                if self.is_reclaimed(origin.qpage_no) {
+                    return Ok(None);
+                }
+
+                let mut head_page = self.open_page(origin.qpage_no)?;
+                let Some((FrameKind::Head, mut msg)) =
+                    self.read_frame(&mut head_page, origin.read_byte)?
+                else {
+                    return Ok(None);
+                };
+
+                msg.extend_from_slice(&tail[ORIGIN_LEN..]);
+                String::from_utf8_lossy(&msg).into_owned()
+            }
+            // the first half of a split message is re-read through its second half
+            _ => return Ok(None),
+        };
+
+        self.read_cache.insert(pos, &msg);
+        Ok(Some(msg))
+    }
+
+    // the frame at `start` of a page and which part of a message it holds
+    fn read_frame(
+        &self,
+        qpage: &mut MmapMutWrapper<QPage>,
+        start: usize,
+    ) -> Result<Option<(FrameKind, Vec<u8>)>, RingbufError> {
+        let qpage = qpage.get_inner();
+        match qpage.try_pop(start, self.config.writer_stall_timeout, self.format)? {
+            PopResult::Msg(m) => {
+                let m = self
+                    .format
+                    .strip_headers(m)
+                    .ok_or(RingbufError::ReadError)?;
+
+                Ok(qpage
+                    .frame_kind(start, self.format)
+                    .map(|kind| (kind, m.to_vec())))
+            }
+            PopResult::Padding(_) | PopResult::NoNewMsgs | PopResult::PageDone => Ok(None),
+        }
+    }
+
+    // whether senders already deleted the page
+    fn is_reclaimed(&mut self, qpage_no: usize) -> bool {
+        let info = self.diskring_info.get_inner();
+
+        let max_qpages = info.max_qpages.load(Ordering::Acquire);
+        if max_qpages == 0 {
+            return false;
+        }
+
+        let qpage_count = info.qpage_count.load(Ordering::Acquire);
+        qpage_no < (qpage_count + 1).saturating_sub(max_qpages)
+    }
+
+    /// blocks until at least `count` messages are ready to pop, or `timeout` passes.
+    /// nothing is consumed, returns whether there were enough messages in time.
+    pub fn wait_for(&self, count: usize, timeout: Duration) -> Result<bool, RingbufError> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = Backoff::for_reader(self.config.read_backoff);
+
+        loop {
+            if self.lag(count)? >= count {
+                return Ok(true);
+            }
+
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+
+            backoff.snooze();
+        }
+    }
+
+    // how many messages are ready to pop, counting no further than `limit`
+    fn lag(&self, limit: usize) -> Result<usize, RingbufError> {
+        let (mut lag, mut page_done) =
+            self.qpage
+                .clone()
+                .get_inner()
+                .count_committed(self.read_byte, limit, self.format);
+
+        let mut diskring_info = self.diskring_info.clone();
+        let info = diskring_info.get_inner();
+        let mut qpage_no = self.qpage_no;
+
+        while page_done && lag < limit {
+            let qpage_count = info.qpage_count.load(Ordering::Acquire);
+            let max_qpages = info.max_qpages.load(Ordering::Acquire);
+
+            qpage_no += 1;
+            if max_qpages > 0 {
+                // the oldest page senders haven't reclaimed yet
+                qpage_no = qpage_no.max((qpage_count + 1).saturating_sub(max_qpages));
+            }
+
+            // don't create pages no sender has flipped to yet
+            if qpage_no > qpage_count {
+                break;
+            }
+
+            let count;
+            (count, page_done) =
+                self.open_page(qpage_no)?
+                    .get_inner()
+                    .count_committed(0, limit - lag, self.format);
+            lag += count;
+        }
+
+        Ok(lag)
+    }
+
+    /// whether this receiver has read everything up to `final_pos`, see
+    /// [`DiskRing::finish`]
+    pub fn is_at_end(&self, final_pos: FinalPosition) -> bool {
+        match self.qpage_no.cmp(&final_pos.qpage_no) {
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => self.read_byte >= final_pos.write_byte,
+            std::cmp::Ordering::Greater => true,
+        }
+    }
+
+    pub fn pop(&mut self) -> Result<Option<String>, RingbufError> {
+        let mut flips = 0;
+
+        loop {
+            match self.pop_status()? {
+                PopStatus::Message(m) => return Ok(Some(m)),
+                PopStatus::CaughtUp => return Ok(None),
+                PopStatus::FlippedPage => {
+                    flips += 1;
+                    if self
+                        .config
+                        .max_flips_per_pop
+                        .is_some_and(|max| flips >= max)
+                    {
+                        trace!(debug, qpage_no = self.qpage_no, flips, "flip_cap");
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+
+    /// pops from the current page, resyncing past corrupt frames if the receiver was
+    /// built with [`OnCorrupt::Skip`]. the message borrows the receiver, so the page
+    /// stays mapped for as long as it's around.
+    fn try_pop(&mut self) -> Result<Popped<'_>, RingbufError> {
+        Ok(match self.find_next()? {
+            Found::Msg(at) => Popped::Msg(self.popped_msg(at)),
+            Found::NoNewMsgs => Popped::NoNewMsgs,
+            Found::PageDone => Popped::PageDone,
+        })
+    }
+
+    /// the message [`DiskRing::find_next`] found at `at`. it's still there after
+    /// [`DiskRing::consume_popped`], but not after the next pop.
+    fn popped_msg(&self, at: MsgAt) -> &[u8] {
+        match at {
+            MsgAt::Frame(start_byte) => self.frame_msg(start_byte),
+            // the stitcher holds on to it until the next pop, which can't happen
+            // while it's borrowed
+            MsgAt::Stitched => self.stitched_msg(),
+        }
+    }
+
+    fn frame_msg(&self, start_byte: usize) -> &[u8] {
+        let msg = self
+            .qpage
+            .clone()
+            .get_inner()
+            .frame_msg(start_byte, self.format)
+            .expect("a frame that was just popped has a valid header");
+
+        self.format
+            .strip_headers(msg)
+            .expect("a frame that was just popped has valid headers")
+    }
+
+    fn stitched_msg(&self) -> &[u8] {
+        let (_, msg) = self
+            .stitcher
+            .stitched
+            .as_ref()
+            .expect("a split message that was just popped was put back together");
+
+        self.format
+            .strip_headers(msg)
+            .expect("a split message that was just popped has valid headers")
+    }
+
+    /// like [`DiskRing::try_pop`] but only says where the message is, see
+    /// [`DiskRing::popped_msg`]
+    fn find_next(&mut self) -> Result<Found, RingbufError> {
+        // a polling receiver's page is a snapshot that only changes when it's remapped,
+        // waiting on a push in flight would never end
+        #[cfg(unix)]
+        if self.polling && !self.qpage.get_inner().has_committed(self.read_byte) {
+            self.remap_polled()?;
+            if !self.qpage.get_inner().has_committed(self.read_byte) {
+                return Ok(Found::NoNewMsgs);
+            }
+        }
+
+        let qpage = self.qpage.get_inner();
+
+        loop {
+            match qpage.try_pop(
+                self.read_byte,
+                self.config.writer_stall_timeout,
+                self.format,
+            ) {
+                Err(qpage::Error::FrameOutOfBounds)
+                    if self.config.on_corrupt == OnCorrupt::Skip =>
+                {
+                    // without sync markers nothing marks where frames start, the only
+                    // boundary we can trust is the end of what writers have committed
+                    let end_byte =
+                        qpage.committed_end(self.read_byte, self.config.writer_stall_timeout)?;
+                    let resync_at = match self.format.sync_markers {
+                        true => qpage
+                            .next_synced_frame(self.read_byte + 1, end_byte, self.format)
+                            .unwrap_or(end_byte),
+                        false => end_byte,
+                    };
+                    trace!(
+                        warn,
+                        qpage_no = self.qpage_no,
+                        bytes = resync_at - self.read_byte,
+                        "skip_corrupt"
+                    );
+
+                    self.read_byte = resync_at;
+                    self.corrupt_skips += 1;
+                }
+                // left behind by a reservation that didn't use all of its space
+                Ok(PopResult::Padding(padding_len)) => {
+                    self.read_byte = advance_read_byte(self.read_byte, padding_len)?
+                }
+                Ok(PopResult::Msg(m)) => {
+                    let frame_len = self.format.frame_len(m.len());
+                    let kind = qpage
+                        .frame_kind(self.read_byte, self.format)
+                        .expect("a frame that was just popped has a valid header");
+
+                    let pos = self.read_position();
+                    let (msg, at) = match kind {
+                        FrameKind::Whole => (Some(m), MsgAt::Frame(self.read_byte)),
+                        _ => (self.stitcher.stitch(pos, kind, m), MsgAt::Stitched),
+                    };
+
+                    if let Some(msg) = msg {
+                        self.format
+                            .strip_headers(msg)
+                            .ok_or(RingbufError::ReadError)?;
+                        self.popped_frame_len = frame_len;
+
+                        self.check_reclaim_pressure();
+                        return Ok(Found::Msg(at));
+                    }
+
+                    trace!(
+                        trace,
+                        qpage_no = self.qpage_no,
+                        bytes = frame_len,
+                        "pop_fragment"
+                    );
+                    self.read_byte = advance_read_byte(self.read_byte, frame_len)?;
+                }
+                #[cfg(unix)]
+                Ok(PopResult::PageDone) if self.polling && !self.next_page_created()? => {
+                    self.remap_polled()?;
+                    return Ok(Found::NoNewMsgs);
+                }
+                Ok(PopResult::NoNewMsgs) => return Ok(Found::NoNewMsgs),
+                Ok(PopResult::PageDone) => return Ok(Found::PageDone),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// calls `f` once for each page this receiver is still reading when it's become the
+    /// oldest page of a bounded ringbuf, i.e. when the next page a sender starts reclaims
+    /// it (see [`set_max_qpage`]), so the consumer can speed up or checkpoint before it
+    /// starts losing messages. replaces whatever was registered before, and clones of
+    /// this receiver made afterwards share it.
+    ///
+    /// it's checked as messages are popped, so keep it cheap. with `max_qpages` of 1
+    /// every page is the oldest one, and it fires for each of them.
+    pub fn on_reclaim_pressure<F: FnMut(ReclaimInfo) + Send + 'static>(&mut self, f: F) {
+        self.on_reclaim_pressure = Some(Arc::new(Mutex::new(f)));
+    }
+
+    fn check_reclaim_pressure(&mut self) {
+        if self.on_reclaim_pressure.is_none() || self.reclaim_warned == Some(self.qpage_no) {
+            return;
+        }
+
+        let info = self.diskring_info.get_inner();
+        let max_qpages = info.max_qpages.load(Ordering::Acquire);
+        let newest_page = info.qpage_count.load(Ordering::Acquire);
+        if max_qpages == 0 || self.qpage_no + max_qpages > newest_page + 1 {
+            return;
+        }
+
+        trace!(
+            warn,
+            qpage_no = self.qpage_no,
+            newest_page,
+            "reclaim_pressure"
+        );
+        self.reclaim_warned = Some(self.qpage_no);
+        if let Some(on_reclaim_pressure) = &self.on_reclaim_pressure {
+            (on_reclaim_pressure.lock().expect("unpoisoned lock"))(ReclaimInfo {
+                page: self.qpage_no,
+                newest_page,
+                max_qpages,
+            });
+        }
+    }
+
+    /// how many times this receiver skipped over corrupt frames, see [`OnCorrupt::Skip`]
+    pub fn corrupt_skips(&self) -> usize {
+        self.corrupt_skips
+    }
+
+    /// like [`DiskRing::pop`] but returns after flipping to the next page instead of
+    /// transparently carrying on, e.g. to checkpoint at page boundaries.
+    pub fn pop_status(&mut self) -> Result<PopStatus, RingbufError> {
+        match self.pop_no_flip()? {
+            PopNoFlipStatus::Message(msg) => Ok(PopStatus::Message(msg)),
+            PopNoFlipStatus::CaughtUp => Ok(PopStatus::CaughtUp),
+            PopNoFlipStatus::AtPageBoundary => {
+                self.page_flip()?;
+                Ok(PopStatus::FlippedPage)
+            }
+        }
+    }
+
+    /// like [`DiskRing::pop_status`] but stays on a finished page instead of flipping,
+    /// so the caller decides when to move on with [`DiskRing::advance_page`], e.g. after
+    /// committing everything it read from the page.
+    pub fn pop_no_flip(&mut self) -> Result<PopNoFlipStatus, RingbufError> {
+        match self.try_pop()? {
+            Popped::Msg(m) => {
+                let msg = String::from_utf8_lossy(m).into_owned();
+                self.read_cache.insert(self.read_position(), &msg);
+
+                self.consume_popped()?;
+                Ok(PopNoFlipStatus::Message(msg))
+            }
+            Popped::NoNewMsgs => Ok(PopNoFlipStatus::CaughtUp),
+            Popped::PageDone => Ok(PopNoFlipStatus::AtPageBoundary),
+        }
+    }
+
+    /// writes the receiver's position to its [cursor](Builder::cursor), so a receiver
+    /// opened with the same cursor later carries on from here
+    pub fn commit(&mut self) -> Result<(), RingbufError> {
+        let Some(cursor_name) = self.config.cursor_name() else {
+            return Err(RingbufError::InvalidConfig("receiver has no cursor"));
+        };
+
+        let pos = self.read_position();
+        let mut contents = [0; 16];
+        contents[..8].copy_from_slice(&(pos.qpage_no as u64).to_le_bytes());
+        contents[8..].copy_from_slice(&(pos.read_byte as u64).to_le_bytes());
+        self.dir.replace(&cursor_name, &contents)?;
+
+        self.commits = CommitState {
+            since_commit: 0,
+            last_commit: self.config.clock.now(),
+            committed: Some(pos),
+        };
+
+        Ok(())
+    }
+
+    /// the position last [committed](DiskRing::commit) by this receiver, or
+    /// restored from its cursor when it was opened
+    pub fn committed_position(&self) -> Option<Position> {
+        self.commits.committed
+    }
+
+    // moves past the message try_pop just returned and counts it towards the commit
+    // policy. every pop that consumes a message goes through here
+    fn consume_popped(&mut self) -> Result<(), RingbufError> {
+        trace!(
+            trace,
+            qpage_no = self.qpage_no,
+            bytes = self.popped_frame_len,
+            "pop"
+        );
+        self.read_byte = advance_read_byte(self.read_byte, self.popped_frame_len)?;
+
+        self.commit_if_due()
+    }
+
+    // counts a popped message towards the commit policy
+    fn commit_if_due(&mut self) -> Result<(), RingbufError> {
+        if self.config.cursor.is_none() {
+            return Ok(());
+        }
+
+        self.commits.since_commit += 1;
+
+        let due = match self.config.commit_policy {
+            CommitPolicy::Manual => false,
+            CommitPolicy::EveryMessages(n) => self.commits.since_commit >= n,
+            CommitPolicy::Every(interval) => {
+                let now = self.config.clock.now();
+
+                // otherwise the next commit waits for the clock to catch up again
+                if now < self.commits.last_commit {
+                    trace!(warn, "clock_backward");
+                    self.commits.last_commit = now;
+                }
+
+                now.saturating_duration_since(self.commits.last_commit) >= interval
+            }
+        };
+
+        match due {
+            true => self.commit(),
+            false => Ok(()),
+        }
+    }
+
+    // moves to the position stored in the cursor, if there is one
+    fn restore_cursor(&mut self) -> Result<(), RingbufError> {
+        let Some(cursor_name) = self.config.cursor_name() else {
+            return Ok(());
+        };
+
+        let mut contents = Vec::new();
+        match self.dir.exists(&cursor_name)? {
+            true => self.dir.open(&cursor_name)?.read_to_end(&mut contents)?,
+            false => return Ok(()),
+        };
+
+        let Some(pos) = decode_cursor(contents) else {
+            return Err(RingbufError::InvalidConfig("cursor file is corrupt"));
+        };
+
+        let info = self.diskring_info.get_inner();
+        let qpage_count = info.qpage_count.load(Ordering::Acquire);
+        let max_qpages = info.max_qpages.load(Ordering::Acquire);
+
+        if pos.qpage_no > qpage_count || pos.read_byte > qpage::DEFAULT_QUEUE_SIZE {
+            return Err(RingbufError::InvalidConfig(
+                "cursor is past the end of the ringbuf",
+            ));
+        }
+
+        // the page it points at might have been reclaimed since
+        let oldest = match max_qpages {
+            0 => 0,
+            _ => (qpage_count + 1).saturating_sub(max_qpages),
+        };
+        (self.qpage_no, self.read_byte) = match pos.qpage_no < oldest {
+            true => (oldest, 0),
+            false => (pos.qpage_no, pos.read_byte),
+        };
+        self.qpage = self.open_page(self.qpage_no)?;
+        self.commits.committed = Some(pos);
+
+        Ok(())
+    }
+
+    /// like [`DiskRing::pop`] but skips (and consumes) every message whose
+    /// [key](Builder::message_keys) doesn't match `predicate`. without message keys
+    /// every message has key 0.
+    pub fn pop_filtered<F: Fn(u64) -> bool>(
+        &mut self,
+        predicate: F,
+    ) -> Result<Option<String>, RingbufError> {
+        loop {
+            match self.find_next()? {
+                Found::Msg(at) => {
+                    let key = self
+                        .qpage
+                        .get_inner()
+                        .frame_key(self.read_byte, self.format)
+                        .expect("a frame that was just popped has a valid header");
+
+                    self.consume_popped()?;
+
+                    if predicate(key) {
+                        let msg = String::from_utf8_lossy(self.popped_msg(at)).into_owned();
+                        return Ok(Some(msg));
+                    }
+                }
+                Found::NoNewMsgs => return Ok(None),
+                Found::PageDone => self.page_flip()?,
+            }
+        }
+    }
+
+    /// moves on to the next page if the receiver is
+    /// [at a page boundary](PopNoFlipStatus::AtPageBoundary), returning whether it did.
+    /// nothing that's left to read on the current page is ever skipped.
+    pub fn advance_page(&mut self) -> Result<bool, RingbufError> {
+        match self.try_pop()? {
+            Popped::PageDone => {
+                self.page_flip()?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// like [`DiskRing::pop`] but also returns the headers the message was pushed with,
+    /// which needs the ringbuf to be built with [message headers](Builder::message_headers)
+    #[allow(clippy::type_complexity)]
+    pub fn pop_with_headers(
+        &mut self,
+    ) -> Result<Option<(String, Vec<(String, String)>)>, RingbufError> {
+        if !self.format.headers {
+            return Err(RingbufError::InvalidConfig(
+                "ringbuf wasn't built with message headers",
+            ));
+        }
+
+        loop {
+            match self.find_next()? {
+                Found::Msg(at) => {
+                    let section = self
+                        .qpage
+                        .get_inner()
+                        .frame_headers(self.read_byte, self.format)
+                        .expect("a frame that was just popped has valid headers");
+                    let headers = self
+                        .format
+                        .decode_headers(section)
+                        .into_iter()
+                        .map(|(key, value)| {
+                            (
+                                String::from_utf8_lossy(key).into_owned(),
+                                String::from_utf8_lossy(value).into_owned(),
+                            )
+                        })
+                        .collect();
+                    let msg = String::from_utf8_lossy(self.popped_msg(at)).into_owned();
+
+                    self.consume_popped()?;
+                    return Ok(Some((msg, headers)));
+                }
+                Found::NoNewMsgs => return Ok(None),
+                Found::PageDone => {}
+            }
+
+            self.page_flip()?;
+        }
+    }
+
+    /// like [`DiskRing::pop`] but decodes the message as any [`FromRingbufBytes`] type,
+    /// e.g. `pop_as::<Vec<u8>>()` for the raw bytes.
+    ///
+    /// a message that fails to decode is still consumed, the next call moves on to the
+    /// one after it.
+    pub fn pop_as<M: FromRingbufBytes>(&mut self) -> Result<Option<M>, RingbufError> {
+        loop {
+            match self.try_pop()? {
+                Popped::Msg(m) => {
+                    let msg = M::from_ringbuf_bytes(m);
+                    self.consume_popped()?;
+                    return msg
+                        .map(Some)
+                        .map_err(|e| RingbufError::DecodeError(e.into()));
+                }
+                Popped::NoNewMsgs => return Ok(None),
+                Popped::PageDone => {}
+            }
+
+            self.page_flip()?;
+        }
+    }
+
+    /// the length of the next message without consuming it, e.g. to size the buffer
+    /// for [`DiskRing::pop_into`]. `None` when there's nothing to pop yet.
+    pub fn next_len(&mut self) -> Result<Option<usize>, RingbufError> {
+        loop {
+            match self.try_pop()? {
+                Popped::Msg(m) => return Ok(Some(m.len())),
+                Popped::NoNewMsgs => return Ok(None),
+                Popped::PageDone => {}
+            }
+
+            self.page_flip()?;
+        }
+    }
+
+    /// like [`DiskRing::pop`] but copies the raw message into `buf`, replacing whatever
+    /// was in it, and returns its length. reusing the same buffer saves allocating
+    /// for every message.
+    pub fn pop_into(&mut self, buf: &mut Vec<u8>) -> Result<Option<usize>, RingbufError> {
+        loop {
+            match self.try_pop()? {
+                Popped::Msg(m) => {
+                    buf.clear();
+                    buf.extend_from_slice(m);
+                    self.consume_popped()?;
+                    return Ok(Some(buf.len()));
+                }
+                Popped::NoNewMsgs => return Ok(None),
+                Popped::PageDone => {}
+            }
+
+            self.page_flip()?;
+        }
+    }
+
+    /// like [`DiskRing::pop_into`] but copies into memory that doesn't have to be
+    /// initialized first, e.g. a buffer that gets reused across pops without ever
+    /// zeroing it. returns the message's length, `buf[..len]` is initialized after.
+    ///
+    /// a message that doesn't fit fails with [`RingbufError::BufferTooSmall`] instead
+    /// of being truncated. it isn't popped then, so it can be retried with a bigger
+    /// buffer.
+    pub fn pop_into_uninit(
+        &mut self,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Result<Option<usize>, RingbufError> {
+        loop {
+            match self.try_pop()? {
+                Popped::Msg(m) => {
+                    if m.len() > buf.len() {
+                        return Err(RingbufError::BufferTooSmall(m.len()));
+                    }
+
+                    let len = m.len();
+                    // same layout, and writing initialized bytes over uninitialized
+                    // ones is always fine
+                    unsafe {
+                        std::ptr::copy_nonoverlapping(m.as_ptr(), buf.as_mut_ptr().cast(), len);
+                    }
+                    self.consume_popped()?;
+                    return Ok(Some(len));
+                }
+                Popped::NoNewMsgs => return Ok(None),
+                Popped::PageDone => {}
+            }
+
+            self.page_flip()?;
+        }
+    }
+
+    /// where the next message is in the page files, without reading or consuming it.
+    /// `None` when there's nothing to pop on the current page, which includes a
+    /// finished page whose next message is already on the next one.
+    pub fn frame_info(&self) -> Option<FrameInfo> {
+        let qpage = self.qpage.clone().get_inner();
+        let mut start_byte = self.read_byte;
+
+        loop {
+            match qpage.try_pop(start_byte, self.config.writer_stall_timeout, self.format) {
+                Ok(PopResult::Msg(m)) => {
+                    return Some(FrameInfo {
+                        page: self.qpage_no,
+                        offset: qpage::BUF_OFFSET + start_byte,
+                        header_len: self.format.header_len(m.len()),
+                        payload_len: m.len(),
+                    })
+                }
+                Ok(PopResult::Padding(padding_len)) => start_byte += padding_len,
+                _ => return None,
+            }
+        }
+    }
+
+    /// discards up to `count` messages without copying them anywhere, moving on to the
+    /// next page when needed, and returns how many it skipped. stops early when it
+    /// catches up.
+    pub fn skip_messages(&mut self, count: usize) -> Result<usize, RingbufError> {
+        let mut skipped = 0;
+
+        while skipped < count {
+            match self.try_pop()? {
+                Popped::Msg(_) => {
+                    self.consume_popped()?;
+                    skipped += 1;
+                }
+                Popped::NoNewMsgs => break,
+                Popped::PageDone => self.page_flip()?,
+            }
+        }
+
+        trace!(debug, qpage_no = self.qpage_no, skipped, "skip");
+        Ok(skipped)
+    }
+
+    /// like [`DiskRing::pop`] but borrows the message straight out of the page when it's
+    /// valid utf-8, only allocating to replace invalid sequences.
+    ///
+    /// the page stays mapped for as long as the receiver is borrowed, so it can't flip
+    /// away from under the returned message.
+    pub fn pop_cow(&mut self) -> Result<Option<Cow<'_, str>>, RingbufError> {
+        loop {
+            match self.find_next()? {
+                Found::Msg(at) => {
+                    self.consume_popped()?;
+                    return Ok(Some(String::from_utf8_lossy(self.popped_msg(at))));
+                }
+                Found::NoNewMsgs => return Ok(None),
+                Found::PageDone => {}
+            }
+
+            self.page_flip()?;
+        }
+    }
+
+    /// like [`DiskRing::pop_as`] for raw bytes, but copies out at most
+    /// [`Builder::chunk_size`] bytes at a time so huge messages never have to be held
+    /// in memory all at once. the receiver only moves on to the next message once the
+    /// last chunk (with `more` unset) of the current one was returned.
+    ///
+    /// any other pop in between returns the whole message, and the next `pop_chunk`
+    /// starts over at the beginning of the one after it.
+    pub fn pop_chunk(&mut self) -> Result<Option<Chunk>, RingbufError> {
+        loop {
+            match self.find_next()? {
+                Found::Msg(at) => {
+                    let pos = self.read_position();
+                    let offset = match self.chunk_progress {
+                        Some((chunk_pos, offset)) if chunk_pos == pos => offset,
+                        _ => 0,
+                    };
+
+                    let m = self.popped_msg(at);
+                    let end = m.len().min(offset + self.config.chunk_size);
+                    let chunk = Chunk {
+                        bytes: m[offset..end].to_vec(),
+                        more: end < m.len(),
+                    };
+
+                    if chunk.more {
+                        self.chunk_progress = Some((pos, end));
+                    } else {
+                        self.chunk_progress = None;
+                        self.consume_popped()?;
+                    }
+
+                    return Ok(Some(chunk));
+                }
+                Found::NoNewMsgs => return Ok(None),
+                Found::PageDone => {}
+            }
+
+            self.page_flip()?;
+        }
+    }
+
+    /// appends length-prefixed frames (same layout as on disk) to `out` until the next
+    /// frame would go over `max_bytes`. frames are never split, so a message bigger than
+    /// the whole budget is left in place.
+    ///
+    /// returns the number of messages and bytes appended.
+    pub fn pop_batch_bytes(
+        &mut self,
+        max_bytes: usize,
+        out: &mut Vec<u8>,
+    ) -> Result<(usize, usize), RingbufError> {
+        let format = self.format;
+        let mut msgs = 0;
+        let mut bytes = 0;
+
+        loop {
+            match self.try_pop()? {
+                Popped::Msg(m) => {
+                    let frame_len = format.frame_len(m.len());
+
+                    if bytes + frame_len > max_bytes {
+                        break;
+                    }
+
+                    let header_start = out.len();
+                    out.resize(header_start + format.header_len(m.len()), 0);
+                    format.encode(m.len(), &mut out[header_start..]);
+                    out.extend_from_slice(m);
+
+                    self.consume_popped()?;
+                    msgs += 1;
+                    bytes += frame_len;
+                }
+                Popped::NoNewMsgs => break,
+                Popped::PageDone => self.page_flip()?,
+            }
+        }
+
+        Ok((msgs, bytes))
+    }
+
+    /// yields up to `max` messages straight out of the page without copying them,
+    /// for bulk processing. a single call never crosses pages: it flips to the next
+    /// page only if the receiver is at a page boundary before anything was popped,
+    /// and stops at the end of the page it's on.
+    ///
+    /// the whole batch is popped right away, however much of it ends up being
+    /// iterated, and the messages borrow the receiver until they're done with. an
+    /// error ends the batch early, the next pop returns it again. a message
+    /// [split](Builder::split_messages) across pages ends the batch too, the receiver
+    /// keeps it put back together only until the next one.
+    pub fn pop_batch_ref(&mut self, max: usize) -> impl Iterator<Item = &[u8]> + '_ {
+        let mut batch = Vec::new();
+        let mut flipped = false;
+
+        while batch.len() < max {
+            match self.find_next() {
+                Ok(Found::Msg(at)) => {
+                    if self.consume_popped().is_err() {
+                        break;
+                    }
+                    batch.push(at);
+
+                    // the next pop would replace it in the stitcher
+                    if at == MsgAt::Stitched {
+                        break;
+                    }
+                }
+                Ok(Found::PageDone) if batch.is_empty() && !flipped => {
+                    if self.page_flip().is_err() {
+                        break;
+                    }
+                    flipped = true;
+                }
+                _ => break,
+            }
+        }
+
+        let this = &*self;
+        batch.into_iter().map(move |at| this.popped_msg(at))
+    }
+
+    /// yields every message that's currently available (flipping pages as needed)
+    /// and stops as soon as the receiver has caught up with the writers.
+    pub fn drain(&mut self) -> impl Iterator<Item = Result<String, RingbufError>> + '_ {
+        std::iter::from_fn(move || self.pop().transpose())
+    }
+
+    /// blocks for every message until the ringbuf is [closed](DiskRing::close) and
+    /// everything pushed before that has been read, like reading from a pipe.
+    ///
+    /// ```rust
+    /// use disk_ringbuffer::ringbuf;
+    ///
+    /// let (mut tx, mut rx) = ringbuf::new("test-stream-doc").unwrap();
+    ///
+    /// let t = std::thread::spawn(move || {
+    ///     for i in 0..10 {
+    ///         tx.push(i.to_string()).unwrap();
+    ///     }
+    ///     tx.close();
+    /// });
+    ///
+    /// assert_eq!(rx.stream().count(), 10);
+    /// t.join().unwrap();
+    /// # std::fs::remove_dir_all("test-stream-doc").unwrap();
+    /// ```
+    pub fn stream(&mut self) -> RecvStream<'_> {
+        RecvStream { rx: self }
+    }
+
+    /// calls `f` with every message straight from the page as it comes in, waiting
+    /// for new ones like [`DiskRing::stream`], until `f` breaks or the ringbuf is
+    /// [closed](DiskRing::close) and everything pushed before that has been handled.
+    ///
+    /// a message counts as popped once `f` returns, the one it breaks on included, and
+    /// that's when the [`CommitPolicy`] decides whether to commit the cursor.
+    pub fn consume<F: FnMut(&[u8]) -> ControlFlow<()>>(
+        &mut self,
+        mut f: F,
+    ) -> Result<(), RingbufError> {
+        let mut backoff = Backoff::for_reader(self.config.read_backoff);
+
+        loop {
+            // see RecvStream, coming up empty after a close is the end
+            let closed = self.is_closed();
+
+            match self.try_pop()? {
+                Popped::Msg(m) => {
+                    let flow = f(m);
+                    self.consume_popped()?;
+
+                    if flow.is_break() {
+                        return Ok(());
+                    }
+                    backoff = Backoff::for_reader(self.config.read_backoff);
+                }
+                Popped::NoNewMsgs if closed => return Ok(()),
+                Popped::NoNewMsgs => backoff.snooze(),
+                Popped::PageDone => self.page_flip()?,
+            }
+        }
+    }
+}
+
+impl DiskRing<Sender> {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<DiskRing<Sender>, RingbufError> {
+        DiskRing::open(RingDir::Path(path.as_ref().into()), Arc::default())
+    }
+
+    // moves on to the next page, refusing to recreate a ringbuf
+    // someone deleted from under us. see page_flip for what it returns
+    fn flip_and_open(&mut self) -> Result<Option<usize>, RingbufError> {
+        if !self.dir.exists(Path::new(&self.config.info_name))? {
+            return Err(RingbufError::Gone);
+        }
+
+        let sealed = self.page_flip()?;
+        trace!(
+            debug,
+            from = self.qpage_no - 1,
+            to = self.qpage_no,
+            "page_flip"
+        );
+
+        self.qpage = match self.open_page(self.qpage_no) {
+            Ok(qpage) => qpage,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(RingbufError::Gone),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(sealed)
+    }
+
+    // the page this sender left behind if it was the one to move the ringbuf on to
+    // a new page, `None` if another sender already had
+    fn page_flip(&mut self) -> Result<Option<usize>, RingbufError> {
+        let info = self.diskring_info.get_inner();
+        let qpage_count = info.qpage_count.load(Ordering::Acquire);
+
+        if self.qpage_no < qpage_count {
+            self.qpage_no += 1;
+            return Ok(None);
+        }
+
+        if self.qpage_no == qpage_count {
+            // a finish holds the lock for writing, so the ringbuf can't end on a page
+            // while a sender is moving on from it
+            let lock = info.lock.read();
+
+            // a finished ringbuf ends on the page it was finished on
+            if info.closed.load(Ordering::Acquire) {
+                return Err(RingbufError::Closed);
+            }
+
+            // of all the senders moving on from this page, only one counts the next
+            if info
+                .qpage_count
+                .compare_exchange(
+                    qpage_count,
+                    qpage_count + 1,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_err()
+            {
+                self.qpage_no += 1;
+                return Ok(None);
+            }
+            drop(lock);
+
+            self.qpage_no += 1;
+            let max_qpages = info.max_qpages.load(Ordering::Acquire);
+
+            let lock = info.lock.write();
+            let reclaimed = self.config.reclaim_pages(
+                &self.dir,
+                info.qpage_count.load(Ordering::Acquire),
+                max_qpages,
+            )?;
+            drop(lock);
+
+            reclaimed.count(info, Some(self.format));
+            return Ok(Some(self.qpage_no - 1));
+        }
+
+        Ok(None)
+    }
+
+    pub fn push<T: AsRef<[u8]>>(&mut self, input: T) -> Result<usize, RingbufError> {
+        self.push_with_remaining(input).map(|(written, _)| written)
+    }
+
+    /// how many messages were on pages that got reclaimed so far, by any sender or
+    /// [`set_max_qpage`] in any process. messages are counted as dropped whether or
+    /// not receivers got to read them before their page was reclaimed.
+    pub fn dropped_total(&self) -> u64 {
+        self.diskring_info
+            .clone()
+            .get_inner()
+            .dropped
+            .load(Ordering::Relaxed)
+    }
+
+    /// marks the ringbuf as finished for every sender and receiver, in any process.
+    /// receivers [streaming](DiskRing::stream) from it stop once they've read everything
+    /// that was pushed before this and any push afterwards fails with
+    /// [`RingbufError::Closed`].
+    ///
+    /// the flag is stored in the info file, so the ringbuf stays closed across restarts
+    /// until a sender [reopens](DiskRing::reopen) it.
+    pub fn close(mut self) {
+        self.diskring_info
+            .get_inner()
+            .closed
+            .store(true, Ordering::Release);
+    }
+
+    /// [closes](DiskRing::close) the ringbuf, waits for pushes still in flight and
+    /// records where the last message ends in the info file, then
+    /// [flushes](DiskRing::flush) it all to disk. pushes that checked the flag before
+    /// it was set but hadn't reserved their spot yet fail with
+    /// [`RingbufError::Closed`] too, nothing lands past the final position.
+    ///
+    /// receivers can tell they've read everything once they're
+    /// [at the end](DiskRing::is_at_end), rather than polling until nothing shows up.
+    pub fn finish(mut self) -> Result<FinalPosition, RingbufError> {
+        let info = self.diskring_info.get_inner();
+        info.closed.store(true, Ordering::Release);
+
+        // nobody can flip to a new page while we hold this
+        let lock = info.lock.write();
+        let qpage_count = info.qpage_count.load(Ordering::Acquire);
+
+        if self.qpage_no != qpage_count {
+            self.qpage_no = qpage_count;
+            self.qpage = self.open_page(self.qpage_no)?;
+        }
+
+        self.qpage.get_inner().close();
+
+        let final_pos = FinalPosition {
+            qpage_no: self.qpage_no,
+            write_byte: self
+                .qpage
+                .get_inner()
+                .tail(self.config.writer_stall_timeout)?,
+        };
+
+        info.final_qpage_no
+            .store(final_pos.qpage_no, Ordering::Relaxed);
+        info.final_write_byte
+            .store(final_pos.write_byte + 1, Ordering::Release);
+        drop(lock);
+
+        self.flush()?;
+
+        Ok(final_pos)
+    }
+
+    /// undoes a [`close`](DiskRing::close) or [`finish`](DiskRing::finish), e.g. when a
+    /// producer that closed the ringbuf on its way out is restarted. pushes work again
+    /// and the final position is forgotten. receivers that already stopped because it
+    /// was closed have to be started again.
+    pub fn reopen(&mut self) -> Result<(), RingbufError> {
+        let info = self.diskring_info.get_inner();
+        let lock = info.lock.write();
+        let qpage_count = info.qpage_count.load(Ordering::Acquire);
+
+        if self.qpage_no != qpage_count {
+            self.qpage_no = qpage_count;
+            self.qpage = self.open_page(self.qpage_no)?;
+        }
+        self.qpage.get_inner().reopen();
+
+        info.final_write_byte.store(0, Ordering::Release);
+        info.closed.store(false, Ordering::Release);
+        drop(lock);
+
+        Ok(())
+    }
+
+    /// like [`DiskRing::push`] but also returns how many bytes are left on the page the
+    /// message landed on, so producers can size batches to fill pages up.
+    ///
+    /// the remaining space is a snapshot from when the message was written, other senders
+    /// may have used up some of it since.
+    pub fn push_with_remaining<T: AsRef<[u8]>>(
+        &mut self,
+        input: T,
+    ) -> Result<(usize, usize), RingbufError> {
+        self.push_keyed_detailed(input.as_ref(), 0)
+            .map(|details| (details.written, details.page_remaining))
+    }
+
+    /// like [`DiskRing::push`] but tells all about where the message went, e.g. how many
+    /// pages a big message got [split](Builder::split_messages) over, for accounting
+    /// or to see whether pages are sized right for the messages they get.
+    pub fn push_detailed<T: AsRef<[u8]>>(&mut self, input: T) -> Result<PushDetails, RingbufError> {
+        self.push_keyed_detailed(input.as_ref(), 0)
+    }
+
+    /// like [`DiskRing::push`] but tags the message with `key`, which needs the ringbuf
+    /// to be built with [message keys](Builder::message_keys)
+    pub fn push_keyed<T: AsRef<[u8]>>(
+        &mut self,
+        key: u64,
+        input: T,
+    ) -> Result<usize, RingbufError> {
+        if !self.format.keys {
+            return Err(RingbufError::InvalidConfig(
+                "ringbuf wasn't built with message keys",
+            ));
+        }
+
+        self.push_keyed_detailed(input.as_ref(), key)
+            .map(|details| details.written)
+    }
+
+    /// like [`DiskRing::push`] but with `headers` in front of the message, which needs
+    /// the ringbuf to be built with [message headers](Builder::message_headers). keys,
+    /// values and the number of headers each have to fit in a `u16`.
+    pub fn push_with_headers<T: AsRef<[u8]>>(
+        &mut self,
+        headers: &[(&str, &str)],
+        input: T,
+    ) -> Result<usize, RingbufError> {
+        if !self.format.headers {
+            return Err(RingbufError::InvalidConfig(
+                "ringbuf wasn't built with message headers",
+            ));
+        }
+
+        self.push_headers_detailed(headers, input.as_ref(), 0)
+            .map(|details| details.written)
+    }
+
+    fn push_keyed_detailed(&mut self, input: &[u8], key: u64) -> Result<PushDetails, RingbufError> {
+        match self.format.headers {
+            true => self.push_headers_detailed::<&str, &str>(&[], input, key),
+            false => self.push_frame(input, key),
+        }
+    }
+
+    fn push_headers_detailed<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &mut self,
+        headers: &[(K, V)],
+        input: &[u8],
+        key: u64,
+    ) -> Result<PushDetails, RingbufError> {
+        let too_long = |len: usize| len > u16::MAX as usize;
+        if too_long(headers.len())
+            || headers
+                .iter()
+                .any(|(key, value)| too_long(key.as_ref().len()) || too_long(value.as_ref().len()))
+        {
+            return Err(RingbufError::InvalidConfig(
+                "header keys, values and their count have to fit in a u16",
+            ));
+        }
+
+        let section_len = HEADER_LEN_LEN
+            + headers
+                .iter()
+                .map(|(key, value)| 2 * HEADER_LEN_LEN + key.as_ref().len() + value.as_ref().len())
+                .sum::<usize>();
+        let mut msg = Vec::with_capacity(section_len + input.len());
+        self.format.encode_headers(headers, &mut msg);
+        msg.extend_from_slice(input);
+
+        self.push_frame(&msg, key)
+    }
+
+    // pushes `input` as the whole message of a frame, headers section included
+    fn push_frame(&mut self, input: &[u8], key: u64) -> Result<PushDetails, RingbufError> {
+        if self.is_closed() {
+            return Err(RingbufError::Closed);
+        }
+
+        let mut backoff = None;
+        loop {
+            match self.qpage.get_inner().try_push(input, key, self.format)? {
+                PushResult::BytesWritten {
+                    written,
+                    page_remaining,
+                } => {
+                    trace!(trace, qpage_no = self.qpage_no, bytes = written, "push");
+                    self.flush_when_due()?;
+                    return Ok(PushDetails {
+                        written,
+                        page_remaining,
+                        spanned_pages: 1,
+                    });
+                }
+                PushResult::Split {
+                    written,
+                    head_len,
+                    start_idx,
+                } => {
+                    trace!(
+                        trace,
+                        qpage_no = self.qpage_no,
+                        bytes = written,
+                        "push_head"
+                    );
+
+                    let origin = (self.qpage_no, start_idx);
+                    self.flip_when_full()?;
+                    let (tail_written, page_remaining) =
+                        self.push_tail(origin, &input[head_len..], key)?;
+                    self.flush_when_due()?;
+
+                    return Ok(PushDetails {
+                        written: written + tail_written,
+                        page_remaining,
+                        spanned_pages: 2,
+                    });
+                }
+                PushResult::PageFull => {}
+            }
+
+            self.flip_when_full()?;
+            self.back_off(&mut backoff);
+        }
+    }
+
+    /// deletes every page before `qpage_no`, even in an unbounded ringbuf, for when
+    /// retention is managed from the outside. fails without deleting anything if a
+    /// [cursor](Builder::cursor) is still on one of those pages, or if `qpage_no` is
+    /// past the newest page.
+    ///
+    /// receivers without a cursor aren't tracked anywhere, ones still reading a page
+    /// that gets deleted are left with an empty page that never finishes.
+    pub fn trim_before(&mut self, qpage_no: usize) -> Result<(), RingbufError> {
+        let info = self.diskring_info.get_inner();
+        let lock = info.lock.write();
+        let qpage_count = info.qpage_count.load(Ordering::Acquire);
+
+        if qpage_no > qpage_count {
+            return Err(RingbufError::InvalidConfig(
+                "can't trim past the newest page",
+            ));
+        }
+
+        for name in self.dir.list()? {
+            let name = Path::new(&name);
+            if name.extension() != Some("cursor".as_ref()) {
+                continue;
+            }
+
+            let mut contents = Vec::new();
+            self.dir.open(name)?.read_to_end(&mut contents)?;
+
+            match decode_cursor(contents) {
+                Some(pos) if pos.qpage_no < qpage_no => {
+                    return Err(RingbufError::InvalidConfig(
+                        "a cursor is still on a page to trim",
+                    ))
+                }
+                Some(_) => {}
+                None => return Err(RingbufError::InvalidConfig("cursor file is corrupt")),
+            }
+        }
+
+        if qpage_no > 0 {
+            // the same as reclaiming with a window from there to the newest page
+            let reclaimed =
+                self.config
+                    .reclaim_pages(&self.dir, qpage_count, qpage_count + 1 - qpage_no)?;
+            drop(lock);
+
+            reclaimed.count(info, Some(self.format));
+        }
+
+        trace!(debug, qpage_no, "trim_before");
+        Ok(())
+    }
+
+    // moves on from a page a push didn't fit on, telling the page full hook if
+    // this sender is the one that moved the ringbuf on
+    fn flip_when_full(&mut self) -> Result<(), RingbufError> {
+        let sealed = self.flip_and_open()?;
+
+        if let (Some(qpage_no), Some(on_page_full)) = (sealed, &self.on_page_full) {
+            (on_page_full.lock().expect("unpoisoned lock"))(qpage_no);
+        }
+
+        Ok(())
+    }
+
+    // waits before the next retry of a push, see Builder::push_backoff
+    fn back_off(&self, backoff: &mut Option<Backoff>) {
+        if let Some(policy) = self.config.push_backoff {
+            backoff
+                .get_or_insert_with(|| Backoff::with_policy(policy))
+                .snooze();
+        }
+    }
+
+    /// opens a receiver at the current write head of the ringbuf, so it only sees what's
+    /// pushed from here on, e.g. to listen for the response to a request that's about
+    /// to be pushed. like [`DiskRing::open_live`], pushes still in flight are waited
+    /// for and skipped.
+    pub fn receiver_at_head(&self) -> Result<DiskRing<Receiver>, RingbufError> {
+        let mut rx = DiskRing::<Receiver>::open(self.dir.clone(), self.config.clone())?;
+        rx.read_byte = rx.qpage.get_inner().tail(rx.config.writer_stall_timeout)?;
+
+        Ok(rx)
+    }
+
+    /// calls `f` with the page number every time a push from this sender doesn't fit
+    /// on what's left of its page and moves the ringbuf on to the next one, e.g. to
+    /// track how often pages fill up. replaces whatever was registered before, and
+    /// clones of this sender made afterwards share it.
+    ///
+    /// only the sender that creates the next page calls it, so every page that fills
+    /// up is reported once however many senders ran into it. it's called on the pushing
+    /// thread in the middle of the push, so keep it cheap. pages ended with
+    /// [`DiskRing::seal_page`] don't count.
+    pub fn on_page_full<F: FnMut(usize) + Send + 'static>(&mut self, f: F) {
+        self.on_page_full = Some(Arc::new(Mutex::new(f)));
+    }
+
+    // pushes the rest of a message whose head was split off at `origin`
+    fn push_tail(
+        &mut self,
+        origin: (usize, usize),
+        tail: &[u8],
+        key: u64,
+    ) -> Result<(usize, usize), RingbufError> {
+        let mut backoff = None;
+        loop {
+            match self
+                .qpage
+                .get_inner()
+                .try_push_tail(origin, tail, key, self.format)?
+            {
+                PushResult::BytesWritten {
+                    written,
+                    page_remaining,
+                } => {
+                    trace!(
+                        trace,
+                        qpage_no = self.qpage_no,
+                        bytes = written,
+                        "push_tail"
+                    );
+                    return Ok((written, page_remaining));
+                }
+                PushResult::Split { .. } => unreachable!("tails are never split"),
+                PushResult::PageFull => {}
+            }
+
+            self.flip_when_full()?;
+            self.back_off(&mut backoff);
+        }
+    }
+
+    /// ends the current page even if it has room left and moves on to a fresh one, so
+    /// the next push (from any sender) starts a new page, e.g. to line batches up
+    /// with page files for backup or rotation tooling.
+    ///
+    /// a sender still on a page another sender already moved past only catches up
+    /// to the newest page, so senders sealing at the same time don't skip pages.
+    pub fn seal_page(&mut self) -> Result<(), RingbufError> {
+        if self.is_closed() {
+            return Err(RingbufError::Closed);
+        }
+
+        self.qpage.get_inner().seal()?;
+        trace!(debug, qpage_no = self.qpage_no, "seal_page");
+
+        self.flip_and_open().map(|_| ())
+    }
+
+    /// waits for everything pushed to the current page, and the info file, to hit
+    /// the disk. pages this sender already flipped away from are left to the os.
+    pub fn flush(&mut self) -> Result<(), RingbufError> {
+        self.sync_files()?;
+        self.last_flush = self.config.clock.now();
+
+        Ok(())
+    }
+
+    // flushes after a push once the flush interval has passed, see Builder::flush_interval
+    fn flush_when_due(&mut self) -> Result<(), RingbufError> {
+        let Some(interval) = self.config.flush_interval else {
+            return Ok(());
+        };
+
+        let now = self.config.clock.now();
+
+        // otherwise the next flush waits for the clock to catch up again
+        if now < self.last_flush {
+            trace!(warn, "clock_backward");
+            self.last_flush = now;
+        }
+
+        if now.saturating_duration_since(self.last_flush) < interval {
+            return Ok(());
+        }
+
+        trace!(trace, qpage_no = self.qpage_no, "interval_flush");
+        self.flush()
+    }
+
+    /// reserves room for a message of up to `max_len` bytes so it can be written in
+    /// place instead of being built up in a separate buffer first.
+    ///
+    /// receivers can't see anything pushed after the reservation until it's committed
+    /// or dropped, so don't hold on to it for longer than
+    /// [`Builder::writer_stall_timeout`].
+    pub fn reserve(&mut self, max_len: usize) -> Result<FrameWriter<'_>, RingbufError> {
+        if self.is_closed() {
+            return Err(RingbufError::Closed);
+        }
+
+        if max_len > self.format.max_msg_len() {
+            return Err(RingbufError::QError(qpage::Error::MsgTooLong));
+        }
+
+        if self.format.headers {
+            return Err(RingbufError::InvalidConfig(
+                "frames can't be reserved with message headers",
+            ));
+        }
+
+        // always leave room for padding over whatever doesn't get used
+        let reserved_len = self.format.frame_len(max_len) + self.format.min_padding_len();
+
+        let mut backoff = None;
+        loop {
+            if let Some(start_idx) = self.qpage.get_inner().reserve(reserved_len)? {
+                return Ok(FrameWriter {
+                    tx: self,
+                    start_idx,
+                    reserved_len,
+                    max_len,
+                    done: false,
+                });
+            }
+
+            self.flip_when_full()?;
+            self.back_off(&mut backoff);
+        }
+    }
+
+    /// pushes the [`Display`] output of `value`, formatting it into a buffer that's
+    /// reused across calls instead of allocating a new `String` every time.
+    pub fn push_display<D: Display>(&mut self, value: D) -> Result<usize, RingbufError> {
+        let mut fmt_buf = std::mem::take(&mut self.fmt_buf);
+
+        let res = match write!(fmt_buf, "{value}") {
+            Ok(()) => self.push(&fmt_buf),
+            Err(e) => Err(e.into()),
+        };
+
+        // don't hang on to the memory of the occasional huge value
+        fmt_buf.clear();
+        fmt_buf.shrink_to(DEFAULT_INTERNAL_BUF_SIZE);
+        self.fmt_buf = fmt_buf;
+
+        res
+    }
+}
+
+/// one set of page mappings shared by any number of in-process readers, for fan-out
+/// without every receiver opening and mapping the same page files itself.
+///
+/// readers are [`SharedReader`]s handed out by [`RingbufShared::new_reader`]. each one
+/// keeps its own position, the mapping of a page is dropped once no reader is on it
+/// anymore.
+///
+/// ```rust
+/// use disk_ringbuffer::ringbuf::{self, Builder, RingbufShared};
+///
+/// let (mut tx, _rx) = ringbuf::new("test-shared-doc").unwrap();
+/// let shared = RingbufShared::new(Builder::new("test-shared-doc")).unwrap();
+/// let mut a = shared.new_reader().unwrap();
+/// let mut b = shared.new_reader().unwrap();
+///
+/// tx.push("hello").unwrap();
+/// assert_eq!(a.pop().unwrap(), Some("hello".to_string()));
+/// assert_eq!(b.pop().unwrap(), Some("hello".to_string()));
+/// # std::fs::remove_dir_all("test-shared-doc").unwrap();
+/// ```
+pub struct RingbufShared {
+    dir: RingDir,
+    config: Arc<Config>,
+    format: FrameFormat,
+    diskring_info: MmapMutWrapper<DiskRingInfo>,
+    // mapped pages by page number, along with how many readers are on each
+    pages: Mutex<HashMap<usize, (MmapMutWrapper<QPage>, usize)>>,
+}
+
+impl std::fmt::Debug for RingbufShared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RingbufShared")
+            .field("dir", &self.dir)
+            .field("mapped_pages", &self.mapped_pages())
+            .finish_non_exhaustive()
+    }
+}
+
+impl RingbufShared {
+    /// opens the ringbuf `builder` points at for sharing between readers
+    pub fn new(builder: Builder) -> Result<Arc<RingbufShared>, RingbufError> {
+        builder.config.validate()?;
+
+        let rx = DiskRing::<Receiver>::open(builder.dir, Arc::new(builder.config))?;
+
+        Ok(Arc::new(RingbufShared {
+            dir: rx.dir.clone(),
+            config: rx.config.clone(),
+            format: rx.format,
+            diskring_info: rx.diskring_info.clone(),
+            pages: Mutex::default(),
+        }))
+    }
+
+    /// a reader starting on the page senders are currently writing to,
+    /// same as a freshly opened [`DiskRing<Receiver>`]
+    pub fn new_reader(self: &Arc<Self>) -> Result<SharedReader, RingbufError> {
+        let qpage_no = self
+            .diskring_info
+            .clone()
+            .get_inner()
+            .qpage_count
+            .load(Ordering::Acquire);
+
+        Ok(SharedReader {
+            qpage: self.checkout(qpage_no)?,
+            shared: self.clone(),
+            qpage_no,
+            read_byte: 0,
+            stitcher: Stitcher::default(),
+        })
+    }
+
+    /// how many page files are mapped right now
+    pub fn mapped_pages(&self) -> usize {
+        self.pages.lock().expect("unpoisoned lock").len()
+    }
+
+    fn checkout(&self, qpage_no: usize) -> Result<MmapMutWrapper<QPage>, RingbufError> {
+        let mut pages = self.pages.lock().expect("unpoisoned lock");
+
+        if let Some((qpage, readers)) = pages.get_mut(&qpage_no) {
+            *readers += 1;
+            return Ok(qpage.clone());
+        }
+
+        let qpage = self.config.open_page(&self.dir, qpage_no)?;
+        pages.insert(qpage_no, (qpage.clone(), 1));
+
+        Ok(qpage)
+    }
+
+    fn check_in(&self, qpage_no: usize) {
+        let mut pages = self.pages.lock().expect("unpoisoned lock");
+
+        if let Some((_, readers)) = pages.get_mut(&qpage_no) {
+            *readers -= 1;
+            if *readers == 0 {
+                pages.remove(&qpage_no);
+            }
+        }
+    }
+}
+
+/// a reader on a [`RingbufShared`], only its position is its own
+pub struct SharedReader {
+    shared: Arc<RingbufShared>,
+    qpage_no: usize,
+    read_byte: usize,
+    qpage: MmapMutWrapper<QPage>,
+    stitcher: Stitcher,
+}
+
+impl std::fmt::Debug for SharedReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedReader")
+            .field("dir", &self.shared.dir)
+            .field("qpage_no", &self.qpage_no)
+            .field("read_byte", &self.read_byte)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SharedReader {
+    /// see [`DiskRing::pop`]
+    pub fn pop(&mut self) -> Result<Option<String>, RingbufError> {
+        let format = self.shared.format;
+        let stall_timeout = self.shared.config.writer_stall_timeout;
+
+        loop {
+            match self
+                .qpage
+                .get_inner()
+                .try_pop(self.read_byte, stall_timeout, format)?
+            {
+                PopResult::Msg(m) => {
+                    let pos = self.read_position();
+                    let kind = self
+                        .qpage
+                        .get_inner()
+                        .frame_kind(self.read_byte, format)
+                        .expect("a frame that was just popped has a valid header");
+
+                    self.read_byte = advance_read_byte(self.read_byte, format.frame_len(m.len()))?;
+                    let m = format.strip_headers(m).ok_or(RingbufError::ReadError)?;
+                    if let Some(msg) = self.stitcher.stitch(pos, kind, m) {
+                        return Ok(Some(String::from_utf8_lossy(msg).into_owned()));
+                    }
+                }
+                PopResult::Padding(padding_len) => {
+                    self.read_byte = advance_read_byte(self.read_byte, padding_len)?
+                }
+                PopResult::NoNewMsgs => return Ok(None),
+                PopResult::PageDone => self.page_flip()?,
+            }
+        }
+    }
+
+    pub fn read_position(&self) -> Position {
+        Position {
+            qpage_no: self.qpage_no,
+            read_byte: self.read_byte,
+        }
+    }
+
+    fn page_flip(&mut self) -> Result<(), RingbufError> {
+        let (qpage_no, reclaimed) = next_read_page(
+            self.shared.diskring_info.clone().get_inner(),
+            &self.shared.config,
+            self.qpage_no,
+        );
+
+        trace!(debug, from = self.qpage_no, to = qpage_no, "page_flip");
+
+        self.qpage = self.shared.checkout(qpage_no)?;
+        self.shared.check_in(self.qpage_no);
+        self.qpage_no = qpage_no;
+        self.read_byte = 0;
+        self.stitcher
+            .forget_reclaimed(self.shared.diskring_info.clone().get_inner());
+
+        if reclaimed > 0 {
+            trace!(warn, qpage_no, reclaimed, "lagged");
+            return Err(RingbufError::Lagged(reclaimed));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for SharedReader {
+    fn drop(&mut self) {
+        self.shared.check_in(self.qpage_no);
+    }
+}
+
+#[cfg(test)]
+fn get_qpage_count_static<P: AsRef<Path>>(info_path: P) -> usize {
+    let Ok(mut diskring_info) = File::options()
+        .read(true)
+        .write(true)
+        .open(info_path)
+        .map_err(RingbufError::from)
+        .and_then(DiskRingInfo::from_file)
+    else {
+        return 0;
+    };
+
+    diskring_info
+        .get_inner()
+        .qpage_count
+        .load(Ordering::Acquire)
+}
+
+#[test]
+fn seq_test() {
+    let test_dir_path = "test-seq";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    for i in 0..50_000_000 {
+        tx.push(i.to_string()).unwrap();
+    }
+
+    for i in 0..50_000_000 {
+        let m = rx.pop().unwrap();
+        assert_eq!(m, Some(i.to_string()));
+    }
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn spsc_test() {
+    let test_dir_path = "test-spsc";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    let t = std::thread::spawn(move || {
+        for i in 0..50_000_000 {
+            tx.push(i.to_string()).unwrap();
+        }
+    });
+
+    let mut i = 0;
+    loop {
+        if i == 50_000_000 {
+            break;
+        }
+
+        let m = match rx.pop().unwrap() {
+            Some(m) => m,
+            None => continue,
+        };
+
+        assert_eq!(m, i.to_string());
+        i += 1;
+    }
+
+    t.join().unwrap();
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn mpsc_test() {
+    let test_dir_path = "test-mpsc";
+    let num_threads = 4;
+    let mut threads = Vec::new();
+
+    let (tx, mut rx) = new(test_dir_path).unwrap();
+
+    for _ in 0..num_threads {
+        let mut tx_clone = tx.clone();
+        threads.push(std::thread::spawn(move || {
+            for i in 0..50_000_000 / num_threads {
+                tx_clone.push(i.to_string()).unwrap();
+            }
+        }));
+    }
+
+    drop(tx);
+
+    let mut i = 0;
+    loop {
+        if i == 50_000_000 {
+            break;
+        }
+
+        let _m = match rx.pop().unwrap() {
+            Some(_m) => _m,
+            None => continue,
+        };
+
+        i += 1;
+    }
+
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn custom_file_scheme_test() {
+    let test_dir_path = "test-custom-file-scheme";
+    let builder = Builder::new(test_dir_path)
+        .page_extension("queue")
+        .info_name("meta");
+
+    let (mut tx, _rx) = builder.clone().build().unwrap();
+
+    for i in 0..1_000 {
+        tx.push(i.to_string()).unwrap();
+    }
+
+    drop(tx);
+
+    assert!(Path::new(test_dir_path).join("0.queue").exists());
+    assert!(Path::new(test_dir_path).join("meta").exists());
+    assert!(!Path::new(test_dir_path).join("0.page.bin").exists());
+    assert!(!Path::new(test_dir_path).join(".info").exists());
+
+    // the builder's variants of the free functions find its files
+    assert_eq!(builder.retained_messages().unwrap(), 1_000);
+    assert_eq!(builder.usage().unwrap().pages_on_disk, 1);
+    assert_eq!(builder.head_position().unwrap().qpage_no, 0);
+    assert!(builder.wait_for_page(0, Duration::ZERO).unwrap());
+    assert_eq!(builder.page_metadata(0).unwrap().messages, 1_000);
+    assert!(builder.verify_page(0).unwrap().anomalies.is_empty());
+    assert_eq!(builder.set_max_qpage(4).unwrap(), DEFAULT_MAX_QPAGES);
+    assert!(builder.streams_equal(&builder).unwrap());
+    assert!(matches!(
+        retained_messages(test_dir_path),
+        Err(RingbufError::NotFound)
+    ));
+    assert!(!Path::new(test_dir_path).join(".info").exists());
+
+    let (_tx, mut rx) = builder.build().unwrap();
+
+    for i in 0..1_000 {
+        let m = rx.pop().unwrap();
+        assert_eq!(m, Some(i.to_string()));
+    }
+
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn invalid_file_scheme_test() {
+    let test_dir_path = "test-invalid-file-scheme";
+
+    let res = Builder::new(test_dir_path)
+        .page_extension("../page.bin")
+        .build();
+    assert!(matches!(res, Err(RingbufError::InvalidConfig(_))));
+
+    let res = Builder::new(test_dir_path).info_name("a/info").build();
+    assert!(matches!(res, Err(RingbufError::InvalidConfig(_))));
+
+    let res = Builder::new(test_dir_path).page_extension("").build();
+    assert!(matches!(res, Err(RingbufError::InvalidConfig(_))));
+
+    // validation happens before anything touches the disk
+    assert!(!Path::new(test_dir_path).exists());
+}
+
+#[test]
+fn pop_batch_bytes_test() {
+    let test_dir_path = "test-pop-batch-bytes";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    // every frame is a 4 byte length prefix + 4 byte payload
+    for i in 1000..1010 {
+        tx.push(i.to_string()).unwrap();
+    }
+
+    let mut out = Vec::new();
+    assert_eq!(rx.pop_batch_bytes(24, &mut out).unwrap(), (3, 24));
+    assert_eq!(out.len(), 24);
+
+    let mut msgs = Vec::new();
+    let mut frames = out.as_slice();
+    while !frames.is_empty() {
+        let (len, rest) = frames.split_at(size_of::<qpage::MsgLengthType>());
+        let len = qpage::MsgLengthType::from_le_bytes(len.try_into().unwrap()) as usize;
+        msgs.push(String::from_utf8(rest[..len].to_vec()).unwrap());
+        frames = &rest[len..];
+    }
+    assert_eq!(msgs, ["1000", "1001", "1002"]);
+
+    // a budget that falls in the middle of a frame doesn't consume it
+    out.clear();
+    assert_eq!(rx.pop_batch_bytes(15, &mut out).unwrap(), (1, 8));
+    assert_eq!(&out[4..], b"1003");
+
+    out.clear();
+    assert_eq!(rx.pop_batch_bytes(7, &mut out).unwrap(), (0, 0));
+    assert!(out.is_empty());
+
+    assert_eq!(rx.pop().unwrap(), Some("1004".to_string()));
+
+    out.clear();
+    assert_eq!(rx.pop_batch_bytes(usize::MAX, &mut out).unwrap(), (5, 40));
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn drain_test() {
+    let test_dir_path = "test-drain";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    // 70 * 8MB spans three pages
+    let msg_size = 2_usize.pow(23);
+    for i in 0..70 {
+        tx.push(vec![b'a' + (i % 26) as u8; msg_size]).unwrap();
+    }
+
+    let mut count = 0;
+    for (i, m) in rx.drain().enumerate() {
+        let m = m.unwrap();
+        assert_eq!(m.len(), msg_size);
+        assert!(m.bytes().all(|b| b == b'a' + (i % 26) as u8));
+        count += 1;
+    }
+
+    assert_eq!(count, 70);
+    assert_eq!(rx.qpage_no, 2);
+    assert_eq!(rx.drain().count(), 0);
+
+    tx.push("after").unwrap();
+    let rest: Vec<String> = rx.drain().map(Result::unwrap).collect();
+    assert_eq!(rest, ["after"]);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn corrupt_length_prefix_test() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let test_dir_path = "test-corrupt-length-prefix";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("first").unwrap();
+    tx.push("second").unwrap();
+
+    // overwrite the length prefix of the second frame with something
+    // that points way past the end of the page
+    let second_frame = qpage::BUF_OFFSET + size_of::<qpage::MsgLengthType>() + "first".len();
+    let mut f = std::fs::File::options()
+        .write(true)
+        .open(Path::new(test_dir_path).join("0").with_extension(PAGE_EXT))
+        .unwrap();
+    f.seek(SeekFrom::Start(second_frame as u64)).unwrap();
+    f.write_all(&qpage::MsgLengthType::MAX.to_le_bytes())
+        .unwrap();
+    drop(f);
+
+    assert_eq!(rx.pop().unwrap(), Some("first".to_string()));
+    assert!(matches!(rx.pop(), Err(RingbufError::ReadError)));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn writer_stall_timeout_test() {
+    let test_dir_path = "test-writer-stall-timeout";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .writer_stall_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+
+    tx.push("before").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("before".to_string()));
+
+    tx.qpage.get_inner().leak_writer();
+
+    let now = std::time::Instant::now();
+    assert!(matches!(rx.pop(), Err(RingbufError::WriterStalled)));
+    assert!(now.elapsed() >= Duration::from_millis(100));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn repair_stuck_writers_test() {
+    let test_dir_path = "test-repair-stuck-writers";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("before").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("before".to_string()));
+
+    // a producer dies mid-push, everything pushed on the page after this is
+    // invisible to readers until the page is repaired
+    tx.qpage.get_inner().leak_writer();
+    tx.push("lost").unwrap();
+
+    assert_eq!(repair_stuck_writers(test_dir_path).unwrap(), 1);
+    assert_eq!(repair_stuck_writers(test_dir_path).unwrap(), 0);
+
+    tx.push("after").unwrap();
+    assert_eq!(tx.qpage_no, 1);
+
+    assert_eq!(rx.pop().unwrap(), Some("after".to_string()));
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn push_display_test() {
+    let test_dir_path = "test-push-display";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push_display(12345).unwrap();
+    tx.push_display(7).unwrap();
+    tx.push_display(-1.5).unwrap();
+    tx.push_display("str").unwrap();
+    tx.push_display("x".repeat(2 * DEFAULT_INTERNAL_BUF_SIZE))
+        .unwrap();
+    assert!(tx.fmt_buf.capacity() <= DEFAULT_INTERNAL_BUF_SIZE);
+
+    assert_eq!(rx.pop().unwrap(), Some("12345".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("7".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("-1.5".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("str".to_string()));
+    assert_eq!(
+        rx.pop().unwrap(),
+        Some("x".repeat(2 * DEFAULT_INTERNAL_BUF_SIZE))
+    );
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn length_prefix_test() {
+    for (i, prefix) in [
+        LengthPrefix::U8,
+        LengthPrefix::U16,
+        LengthPrefix::U32,
+        LengthPrefix::Varint,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let test_dir_path = format!("test-length-prefix-{i}");
+        let (mut tx, mut rx) = Builder::new(&test_dir_path)
+            .length_prefix(prefix)
+            .build()
+            .unwrap();
+
+        // 253 and 509 both start with the page done marker byte
+        let sizes: Vec<usize> = [0, 1, 253, 509, prefix.max_msg_len()]
+            .into_iter()
+            .filter(|&len| len <= prefix.max_msg_len())
+            .collect();
+
+        let mut written = 0;
+        for &len in &sizes {
+            written += tx.push(vec![b'a'; len]).unwrap();
+        }
+
+        let expected: usize = sizes.iter().map(|&len| prefix.frame_len(len)).sum();
+        assert_eq!(written, expected);
+
+        assert!(matches!(
+            tx.push(vec![b'a'; prefix.max_msg_len() + 1]),
+            Err(RingbufError::QError(qpage::Error::MsgTooLong))
+        ));
+
+        for &len in &sizes {
+            assert_eq!(rx.pop().unwrap(), Some("a".repeat(len)));
+        }
+        assert_eq!(rx.pop().unwrap(), None);
+
+        // handles that don't ask for a prefix pick up the stored one
+        let mut rx = DiskRing::<Receiver>::new(&test_dir_path).unwrap();
+        assert_eq!(rx.format.prefix, prefix);
+        assert_eq!(rx.pop().unwrap(), Some(String::new()));
+
+        std::fs::remove_dir_all(&test_dir_path).unwrap();
+    }
+}
+
+#[test]
+fn length_prefix_mismatch_test() {
+    let test_dir_path = "test-length-prefix-mismatch";
+    let _ = Builder::new(test_dir_path)
+        .length_prefix(LengthPrefix::U16)
+        .build()
+        .unwrap();
+
+    let res = Builder::new(test_dir_path)
+        .length_prefix(LengthPrefix::U8)
+        .build();
+    assert!(matches!(res, Err(RingbufError::InvalidConfig(_))));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn varint_length_prefix_test() {
+    let test_dir_path = "test-varint-length-prefix";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .length_prefix(LengthPrefix::Varint)
+        .build()
+        .unwrap();
+
+    let sizes = [
+        (0, 1),
+        (127, 1),
+        (128, 2),
+        (16383, 2),
+        (16384, 3),
+        (2097151, 3),
+        (2097152, 4),
+        (qpage::DEFAULT_MAX_MSG_SIZE, 4),
+    ];
+
+    for (len, header_len) in sizes {
+        assert_eq!(LengthPrefix::Varint.header_len(len), header_len);
+        assert_eq!(tx.push(vec![b'a'; len]).unwrap(), len + header_len);
+    }
+
+    for (len, _) in sizes {
+        assert_eq!(rx.pop().unwrap(), Some("a".repeat(len)));
+    }
+    assert_eq!(rx.pop().unwrap(), None);
+
+    // non-canonical and unterminated encodings are rejected
+    assert_eq!(
+        LengthPrefix::Varint.decode(ByteOrder::default(), &[0x80, 0x00]),
+        None
+    );
+    assert_eq!(
+        LengthPrefix::Varint.decode(ByteOrder::default(), &[0xFF, 0xFF]),
+        None
+    );
+    assert_eq!(
+        LengthPrefix::Varint.decode(ByteOrder::default(), &[0xFF; 8]),
+        None
+    );
+    assert_eq!(
+        LengthPrefix::Varint.decode(ByteOrder::default(), &[0x80, 0x01]),
+        Some((128, 2))
+    );
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn byte_order_test() {
+    for (i, order) in [ByteOrder::Little, ByteOrder::Big].into_iter().enumerate() {
+        let test_dir_path = format!("test-byte-order-{i}");
+        let (mut tx, mut rx) = Builder::new(&test_dir_path)
+            .length_prefix(LengthPrefix::U16)
+            .byte_order(order)
+            .build()
+            .unwrap();
+
+        tx.push(vec![b'a'; 0x0102]).unwrap();
+        tx.push("hello").unwrap();
+
+        let page =
+            std::fs::read(Path::new(&test_dir_path).join("0").with_extension(PAGE_EXT)).unwrap();
+        let header = &page[qpage::BUF_OFFSET..qpage::BUF_OFFSET + 2];
+        match order {
+            ByteOrder::Little => assert_eq!(header, [0x02, 0x01]),
+            ByteOrder::Big => assert_eq!(header, [0x01, 0x02]),
+        }
+
+        assert_eq!(rx.pop().unwrap(), Some("a".repeat(0x0102)));
+        assert_eq!(rx.pop().unwrap(), Some("hello".to_string()));
+        assert_eq!(rx.pop().unwrap(), None);
+
+        // handles that don't ask for a byte order pick up the stored one
+        let mut rx = DiskRing::<Receiver>::new(&test_dir_path).unwrap();
+        assert_eq!(rx.format.byte_order, order);
+        assert_eq!(rx.pop().unwrap(), Some("a".repeat(0x0102)));
+
+        let flipped = match order {
+            ByteOrder::Little => ByteOrder::Big,
+            ByteOrder::Big => ByteOrder::Little,
+        };
+        let res = Builder::new(&test_dir_path).byte_order(flipped).build();
+        assert!(matches!(res, Err(RingbufError::InvalidConfig(_))));
+
+        std::fs::remove_dir_all(&test_dir_path).unwrap();
+    }
+}
+
+#[test]
+fn pop_status_test() {
+    let test_dir_path = "test-pop-status";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    let msg_size = 2_usize.pow(23);
+    let msgs_per_page = qpage::DEFAULT_QUEUE_SIZE / LengthPrefix::default().frame_len(msg_size);
+
+    for _ in 0..msgs_per_page + 1 {
+        tx.push(vec![b'a'; msg_size]).unwrap();
+    }
+
+    let mut msgs = 0;
+    loop {
+        let qpage_no = rx.qpage_no;
+
+        match rx.pop_status().unwrap() {
+            PopStatus::Message(m) => {
+                assert_eq!(rx.qpage_no, qpage_no);
+                assert_eq!(m.len(), msg_size);
+                msgs += 1;
+            }
+            PopStatus::FlippedPage => {
+                assert_eq!(msgs, msgs_per_page);
+                assert_eq!(rx.qpage_no, qpage_no + 1);
+                break;
+            }
+            PopStatus::CaughtUp => panic!("caught up before the end of the page"),
+        }
+    }
+
+    assert!(matches!(rx.pop_status().unwrap(), PopStatus::Message(_)));
+    assert_eq!(rx.pop_status().unwrap(), PopStatus::CaughtUp);
+    assert_eq!(rx.qpage_no, 1);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn has_data_test() {
+    let test_dir_path = "test-has-data";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    assert!(!rx.has_data());
+
+    tx.push("hello").unwrap();
+    assert!(rx.has_data());
+
+    assert_eq!(rx.pop().unwrap(), Some("hello".to_string()));
+    assert!(!rx.has_data());
+
+    // a writer that's still in the middle of a push doesn't count
+    tx.qpage.get_inner().leak_writer();
+    assert!(!rx.has_data());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn preallocate_pages_test() {
+    use std::os::unix::fs::MetadataExt;
+
+    let test_dir_path = "test-preallocate-pages";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .preallocate_pages(2)
+        .build()
+        .unwrap();
+
+    let page_path =
+        |qpage_no: usize| Path::new(test_dir_path).join(format!("{qpage_no}.{PAGE_EXT}"));
+
+    let inodes: Vec<u64> = (1..=2)
+        .map(|qpage_no| {
+            let meta = std::fs::metadata(page_path(qpage_no)).unwrap();
+            assert_eq!(meta.len(), std::mem::size_of::<QPage>() as u64);
+            meta.ino()
+        })
+        .collect();
+    assert!(!page_path(3).exists());
+
+    // preallocated pages aren't part of the ringbuf yet
+    assert_eq!(
+        get_qpage_count_static(Path::new(test_dir_path).join(INFO_NAME)),
+        0
+    );
+
+    let msg_size = 2_usize.pow(23);
+    let msgs_per_page = qpage::DEFAULT_QUEUE_SIZE / LengthPrefix::default().frame_len(msg_size);
+
+    for _ in 0..2 * msgs_per_page + 1 {
+        tx.push(vec![b'a'; msg_size]).unwrap();
+    }
+    assert_eq!(tx.qpage_no, 2);
+
+    // the flips reused the preallocated files rather than making new ones
+    for (qpage_no, ino) in (1..=2).zip(inodes) {
+        assert_eq!(std::fs::metadata(page_path(qpage_no)).unwrap().ino(), ino);
+    }
+
+    assert_eq!(rx.drain().count(), 2 * msgs_per_page + 1);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+/// pushes max sized messages until the sender is on page `qpage_no`
+#[cfg(test)]
+fn push_until_page(tx: &mut DiskRing<Sender>, qpage_no: usize) {
+    let msg = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE];
+
+    while tx.qpage_no < qpage_no {
+        tx.push(&msg).unwrap();
+    }
+}
+
+#[test]
+fn lower_max_qpage_test() {
+    let test_dir_path = "test-lower-max-qpage";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    let page_path =
+        |qpage_no: usize| Path::new(test_dir_path).join(format!("{qpage_no}.{PAGE_EXT}"));
+
+    push_until_page(&mut tx, 4);
+    assert!((0..=4).all(|qpage_no| page_path(qpage_no).exists()));
+
+    // a receiver in the middle of a page that's about to be reclaimed
+    assert!(rx.pop().unwrap().is_some());
+
+    // everything over the new limit goes right away
+    assert_eq!(set_max_qpage(test_dir_path, 2).unwrap(), DEFAULT_MAX_QPAGES);
+    assert!((0..=2).all(|qpage_no| !page_path(qpage_no).exists()));
+    assert!(page_path(3).exists());
+    assert!(page_path(4).exists());
+
+    assert_eq!(get_or_update_max_qpage(test_dir_path, 2).unwrap(), 2);
+
+    // and flips keep reclaiming against it
+    push_until_page(&mut tx, 5);
+    assert!(!page_path(3).exists());
+    assert!(page_path(4).exists());
+    assert!(page_path(5).exists());
+
+    // the receiver finishes its page and skips to the oldest one that's left
+    // without resurrecting any of the reclaimed ones, saying how many it missed
+    let lagged = loop {
+        match rx.pop_status() {
+            Ok(_) => continue,
+            Err(e) => break e,
+        }
+    };
+    assert!(matches!(lagged, RingbufError::Lagged(3)));
+    assert_eq!(rx.qpage_no, 4);
+    assert!((0..=3).all(|qpage_no| !page_path(qpage_no).exists()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn pop_cow_test() {
+    let test_dir_path = "test-pop-cow";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("valid").unwrap();
+    tx.push([b'a', 0xFF, b'b']).unwrap();
+
+    let m = rx.pop_cow().unwrap().unwrap();
+    assert!(matches!(m, Cow::Borrowed("valid")));
+
+    let m = rx.pop_cow().unwrap().unwrap();
+    assert!(matches!(m, Cow::Owned(_)));
+    assert_eq!(m, "a\u{FFFD}b");
+
+    assert_eq!(rx.pop_cow().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn max_reader_lag_test() {
+    let test_dir_path = "test-max-reader-lag";
+    let (mut tx, mut rx_skip) = Builder::new(test_dir_path)
+        .max_reader_lag(1)
+        .build()
+        .unwrap();
+    let mut rx_all = DiskRing::<Receiver>::new(test_dir_path).unwrap();
+
+    push_until_page(&mut tx, 5);
+    tx.push("last").unwrap();
+
+    let mut pages = vec![rx_all.qpage_no];
+    loop {
+        match rx_all.pop_status().unwrap() {
+            PopStatus::Message(_) => {}
+            PopStatus::FlippedPage => pages.push(rx_all.qpage_no),
+            PopStatus::CaughtUp => break,
+        }
+    }
+    assert_eq!(pages, [0, 1, 2, 3, 4, 5]);
+
+    let mut pages = vec![rx_skip.qpage_no];
+    let mut last = None;
+    loop {
+        match rx_skip.pop_status().unwrap() {
+            PopStatus::Message(m) => last = Some(m),
+            PopStatus::FlippedPage => pages.push(rx_skip.qpage_no),
+            PopStatus::CaughtUp => break,
+        }
+    }
+    assert_eq!(pages, [0, 4, 5]);
+    assert_eq!(last.as_deref(), Some("last"));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn from_dir_fd_test() {
+    let test_dir_path = "test-from-dir-fd";
+    let (mut tx, _) = new(test_dir_path).unwrap();
+    set_max_qpage(test_dir_path, 1).unwrap();
+
+    tx.push("hello").unwrap();
+
+    // the whole ringbuf opened through its directory
+    let dir_fd = || OwnedFd::from(File::open(test_dir_path).unwrap());
+    let mut rx = DiskRing::<Receiver>::from_dir_fd(dir_fd()).unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("hello".to_string()));
+
+    let (mut tx, _) = Builder::from_dir_fd(dir_fd()).build().unwrap();
+    push_until_page(&mut tx, 1);
+    tx.push("world").unwrap();
+
+    // page 0 was reclaimed relative to the descriptor as well
+    assert!(!Path::new(test_dir_path)
+        .join("0")
+        .with_extension(PAGE_EXT)
+        .exists());
+    assert_eq!(rx.drain().last().unwrap().unwrap(), "world");
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn push_with_remaining_test() {
+    let test_dir_path = "test-push-with-remaining";
+    let (mut tx, _) = new(test_dir_path).unwrap();
+
+    let (written, mut remaining) = tx.push_with_remaining("hello").unwrap();
+    assert_eq!(written, LengthPrefix::default().frame_len(5));
+    assert_eq!(remaining, qpage::DEFAULT_QUEUE_SIZE - written);
+
+    let msg = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE];
+    loop {
+        let (written, page_remaining) = tx.push_with_remaining(&msg).unwrap();
+
+        if tx.qpage_no == 1 {
+            // a fresh page only has the one frame on it
+            assert_eq!(page_remaining, qpage::DEFAULT_QUEUE_SIZE - written);
+            break;
+        }
+
+        assert_eq!(page_remaining, remaining - written);
+        remaining = page_remaining;
+    }
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn parse_frame_test() {
+    let test_dir_path = "test-parse-frame";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("hello").unwrap();
+    tx.push("").unwrap();
+    tx.push("world").unwrap();
+
+    let mut out = Vec::new();
+    rx.pop_batch_bytes(usize::MAX, &mut out).unwrap();
+
+    let mut msgs = Vec::new();
+    let mut start = 0;
+    while let Some(msg) = parse_frame(&out, start, FrameFormat::default()) {
+        start += LengthPrefix::U32.frame_len(msg.len());
+        msgs.push(msg);
+    }
+    assert_eq!(start, out.len());
+    assert_eq!(msgs, [&b"hello"[..], b"", b"world"]);
+
+    // frames cut short anywhere are rejected instead of read past the end
+    for end in 0..LengthPrefix::U32.frame_len(5) {
+        assert_eq!(parse_frame(&out[..end], 0, FrameFormat::default()), None);
+    }
+    assert_eq!(
+        parse_frame(&out, out.len() + 1, FrameFormat::default()),
+        None
+    );
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn pop_as_test() {
+    #[derive(Debug, PartialEq)]
+    struct Point {
+        x: u32,
+        y: u32,
+    }
+
+    impl FromRingbufBytes for Point {
+        type Error = &'static str;
+
+        fn from_ringbuf_bytes(bytes: &[u8]) -> Result<Self, Self::Error> {
+            let bytes: [u8; 8] = bytes.try_into().map_err(|_| "points are 8 bytes")?;
+            let (x, y) = bytes.split_at(4);
+
+            Ok(Point {
+                x: u32::from_le_bytes(x.try_into().unwrap()),
+                y: u32::from_le_bytes(y.try_into().unwrap()),
+            })
+        }
+    }
+
+    let test_dir_path = "test-pop-as";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push([0xFF, 0xFE]).unwrap();
+    tx.push([0xFF, 0xFE]).unwrap();
+    tx.push("hello").unwrap();
+    tx.push([1, 0, 0, 0, 2, 0, 0, 0]).unwrap();
+    tx.push("not a point").unwrap();
+
+    assert_eq!(rx.pop_as::<Vec<u8>>().unwrap(), Some(vec![0xFF, 0xFE]));
+    assert!(matches!(
+        rx.pop_as::<String>(),
+        Err(RingbufError::DecodeError(_))
+    ));
+    assert_eq!(rx.pop_as::<String>().unwrap(), Some("hello".to_string()));
+    assert_eq!(rx.pop_as::<Point>().unwrap(), Some(Point { x: 1, y: 2 }));
+    assert!(matches!(
+        rx.pop_as::<Point>(),
+        Err(RingbufError::DecodeError(_))
+    ));
+    assert_eq!(rx.pop_as::<Point>().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn stream_test() {
+    let test_dir_path = "test-stream";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    let mut tx2 = tx.clone();
+
+    const K: usize = 100_000;
+
+    let t = std::thread::spawn(move || {
+        for i in 0..K {
+            tx.push(i.to_string()).unwrap();
+        }
+        tx.close();
+    });
+
+    let mut n = 0;
+    for msg in rx.stream() {
+        assert_eq!(msg.unwrap(), n.to_string());
+        n += 1;
+    }
+    assert_eq!(n, K);
+
+    t.join().unwrap();
+
+    assert!(tx2.is_closed());
+    assert!(matches!(tx2.push("late"), Err(RingbufError::Closed)));
+    assert_eq!(rx.stream().count(), 0);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn usage_test() {
+    let test_dir_path = "test-usage";
+    let (mut tx, _) = Builder::new(test_dir_path).max_qpages(0).build().unwrap();
+
+    push_until_page(&mut tx, 2);
+
+    let page_size = std::mem::size_of::<QPage>() as u64;
+
+    let u = usage(test_dir_path).unwrap();
+    assert_eq!(u.pages_on_disk, 3);
+    assert_eq!(u.bytes_on_disk, 3 * page_size);
+    assert_eq!(u.max_qpages, 0);
+    assert!(u.unbounded);
+
+    // the two full pages are (nearly) all allocated, the new one barely is
+    let full = (qpage::DEFAULT_QUEUE_SIZE - qpage::DEFAULT_MAX_MSG_SIZE) as u64;
+    assert!(u.bytes_allocated >= 2 * full);
+    assert!(u.bytes_allocated < 3 * full);
+
+    set_max_qpage(test_dir_path, 2).unwrap();
+
+    let u = usage(test_dir_path).unwrap();
+    assert_eq!(u.pages_on_disk, 2);
+    assert_eq!(u.bytes_on_disk, 2 * page_size);
+    assert_eq!(u.max_qpages, 2);
+    assert!(!u.unbounded);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn on_corrupt_skip_test() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let test_dir_path = "test-on-corrupt-skip";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .on_corrupt(OnCorrupt::Skip)
+        .build()
+        .unwrap();
+
+    tx.push("first").unwrap();
+    tx.push("second").unwrap();
+    tx.push("third").unwrap();
+
+    let second_frame = qpage::BUF_OFFSET + LengthPrefix::U32.frame_len("first".len());
+    let mut f = std::fs::File::options()
+        .write(true)
+        .open(Path::new(test_dir_path).join("0").with_extension(PAGE_EXT))
+        .unwrap();
+    f.seek(SeekFrom::Start(second_frame as u64)).unwrap();
+    f.write_all(&qpage::MsgLengthType::MAX.to_le_bytes())
+        .unwrap();
+    drop(f);
+
+    assert_eq!(rx.pop().unwrap(), Some("first".to_string()));
+    assert_eq!(rx.corrupt_skips(), 0);
+
+    // everything committed behind the corrupt frame goes with it
+    assert_eq!(rx.pop().unwrap(), None);
+    assert_eq!(rx.corrupt_skips(), 1);
+
+    tx.push("fourth").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("fourth".to_string()));
+
+    // corrupting the last frame of a full page resyncs at its marker and still flips
+    push_until_page(&mut tx, 2);
+    tx.push("fifth").unwrap();
+
+    let frame_len = LengthPrefix::U32.frame_len(qpage::DEFAULT_MAX_MSG_SIZE);
+    let frames_per_page = (qpage::DEFAULT_QUEUE_SIZE - 2) / frame_len;
+    let last_frame = qpage::BUF_OFFSET + (frames_per_page - 1) * frame_len;
+    let mut f = std::fs::File::options()
+        .write(true)
+        .open(Path::new(test_dir_path).join("1").with_extension(PAGE_EXT))
+        .unwrap();
+    f.seek(SeekFrom::Start(last_frame as u64)).unwrap();
+    f.write_all(&qpage::MsgLengthType::MAX.to_le_bytes())
+        .unwrap();
+    drop(f);
+
+    assert_eq!(rx.drain().last().unwrap().unwrap(), "fifth");
+    assert_eq!(rx.corrupt_skips(), 2);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn sync_markers_test() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let test_dir_path = "test-sync-markers";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .sync_markers(true)
+        .on_corrupt(OnCorrupt::Skip)
+        .build()
+        .unwrap();
+
+    let msgs: Vec<String> = (0..10).map(|i| format!("msg-{i}")).collect();
+    let mut frame_starts = Vec::new();
+    let mut offset = 0;
+    for msg in &msgs {
+        frame_starts.push(qpage::BUF_OFFSET + offset);
+        offset += tx.push(msg).unwrap();
+    }
+
+    let page_path = Path::new(test_dir_path).join("0").with_extension(PAGE_EXT);
+    let page = std::fs::read(&page_path).unwrap();
+    assert!(frame_starts
+        .iter()
+        .all(|&start| page[start..start + SYNC_MARKER.len()] == SYNC_MARKER));
+
+    let mut f = std::fs::File::options()
+        .write(true)
+        .open(&page_path)
+        .unwrap();
+
+    // scribble over the marker of one frame and the length of another
+    let mut seed = 0x2545_F491_u32;
+    let garbage: Vec<u8> = (0..SYNC_MARKER.len())
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        })
+        .collect();
+    assert_ne!(garbage, SYNC_MARKER);
+    f.seek(SeekFrom::Start(frame_starts[3] as u64)).unwrap();
+    f.write_all(&garbage).unwrap();
+
+    f.seek(SeekFrom::Start(
+        (frame_starts[6] + SYNC_MARKER.len()) as u64,
+    ))
+    .unwrap();
+    f.write_all(&qpage::MsgLengthType::MAX.to_le_bytes())
+        .unwrap();
+    drop(f);
+
+    let read: Vec<String> = rx.drain().map(Result::unwrap).collect();
+    let expected: Vec<&String> = msgs
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != 3 && i != 6)
+        .map(|(_, msg)| msg)
+        .collect();
+    assert_eq!(read.iter().collect::<Vec<_>>(), expected);
+    assert_eq!(rx.corrupt_skips(), 2);
+
+    let res = Builder::new(test_dir_path).sync_markers(false).build();
+    assert!(matches!(res, Err(RingbufError::InvalidConfig(_))));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn out_of_order_commit_test() {
+    let test_dir_path = "test-out-of-order-commit";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("before").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("before".to_string()));
+
+    let format = FrameFormat::default();
+    let qpage = tx.qpage.get_inner();
+
+    // A reserves first, then B, but B finishes first
+    let a = qpage.reserve(format.frame_len(1)).unwrap().unwrap();
+    let b = qpage.reserve(format.frame_len(1)).unwrap().unwrap();
+    assert!(a < b);
+
+    qpage.write_frame(b, b"B", 0, format);
+    qpage.release();
+
+    // B is done, but nothing becomes visible while A is still in flight
+    assert!(!rx.has_data());
+
+    let reader = std::thread::spawn(move || (rx.pop().unwrap(), rx.pop().unwrap()));
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(!reader.is_finished());
+
+    qpage.write_frame(a, b"A", 0, format);
+    qpage.release();
+
+    assert_eq!(
+        reader.join().unwrap(),
+        (Some("A".to_string()), Some("B".to_string()))
+    );
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn frame_writer_test() {
+    let test_dir_path = "test-frame-writer";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    let mut tx2 = tx.clone();
+
+    // built up in place, only part of the reservation used
+    let mut frame = tx.reserve(64).unwrap();
+    frame[..5].copy_from_slice(b"hello");
+    frame[5] = b' ';
+    frame[6..11].copy_from_slice(b"world");
+    assert_eq!(
+        frame.commit(11).unwrap(),
+        FrameFormat::default().frame_len(11)
+    );
+
+    // dropped without committing
+    let mut frame = tx.reserve(64).unwrap();
+    frame[..4].copy_from_slice(b"lost");
+    drop(frame);
+
+    // someone pushed after the reservation, the unused tail gets padded over
+    let mut frame = tx.reserve(64).unwrap();
+    tx2.push("after").unwrap();
+    frame[..5].copy_from_slice(b"first");
+    frame.commit(5).unwrap();
+
+    // same but dropped
+    let frame = tx.reserve(64).unwrap();
+    tx2.push("last").unwrap();
+    drop(frame);
+
+    assert!(tx.reserve(qpage::DEFAULT_MAX_MSG_SIZE + 1).is_err());
+
+    let mut msgs = Vec::new();
+    while let Some(m) = rx.pop().unwrap() {
+        msgs.push(m);
+    }
+    assert_eq!(msgs, ["hello world", "first", "after", "last"]);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn frame_writer_varint_test() {
+    let test_dir_path = "test-frame-writer-varint";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .length_prefix(LengthPrefix::Varint)
+        .sync_markers(true)
+        .build()
+        .unwrap();
+
+    // the header shrinks from three bytes to one
+    let mut frame = tx.reserve(100_000).unwrap();
+    frame[..3].copy_from_slice(b"abc");
+    frame.commit(3).unwrap();
+    tx.push("next").unwrap();
+
+    assert_eq!(rx.pop().unwrap(), Some("abc".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("next".to_string()));
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn read_cache_test() {
+    let test_dir_path = "test-read-cache";
+    let (mut tx, mut rx) = Builder::new(test_dir_path).read_cache(1).build().unwrap();
+
+    tx.push("hello").unwrap();
+    tx.push("world").unwrap();
+
+    let hello = rx.read_position();
+    assert_eq!(rx.pop().unwrap(), Some("hello".to_string()));
+    let world = rx.read_position();
+    assert_eq!(rx.pop().unwrap(), Some("world".to_string()));
+    assert_eq!(rx.re_read(rx.read_position()).unwrap(), None);
+
+    // scribble over both messages on the page
+    let format = FrameFormat::default();
+    let qpage = tx.qpage.get_inner();
+    qpage
+        .reserved_mut(hello.read_byte() + format.header_len(5), 5)
+        .copy_from_slice(b"HELLO");
+    qpage
+        .reserved_mut(world.read_byte() + format.header_len(5), 5)
+        .copy_from_slice(b"WORLD");
+
+    // only the last one is still cached
+    assert_eq!(rx.re_read(world).unwrap(), Some("world".to_string()));
+    assert_eq!(rx.re_read(hello).unwrap(), Some("HELLO".to_string()));
+    assert_eq!(rx.re_read(world).unwrap(), Some("WORLD".to_string()));
+
+    // pretend senders moved on far enough to reclaim the page
+    set_max_qpage(test_dir_path, 2).unwrap();
+    tx.diskring_info
+        .get_inner()
+        .qpage_count
+        .store(5, Ordering::Release);
+    assert_eq!(rx.re_read(world).unwrap(), None);
+    assert!(rx.read_cache.entries.is_empty());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn metadata_test() {
+    let test_dir_path = "test-metadata";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    assert_eq!(tx.get_metadata(), b"");
+
+    tx.set_metadata(b"schema=7").unwrap();
+    assert_eq!(rx.get_metadata(), b"schema=7");
+
+    assert!(matches!(
+        tx.set_metadata(&[0; MAX_METADATA_LEN + 1]),
+        Err(RingbufError::MetadataTooLong(257))
+    ));
+
+    tx.push("hello").unwrap();
+    drop((tx, rx));
+
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    assert_eq!(tx.get_metadata(), b"schema=7");
+    assert_eq!(get_metadata(test_dir_path).unwrap(), b"schema=7");
+    assert_eq!(rx.pop().unwrap(), Some("hello".to_string()));
+
+    tx.set_metadata(b"topic").unwrap();
+    assert_eq!(rx.get_metadata(), b"topic");
+
+    // an info file from a newer version is refused
+    tx.diskring_info
+        .get_inner()
+        .version
+        .store(INFO_VERSION + 1, Ordering::Relaxed);
+    assert!(matches!(
+        new(test_dir_path),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn legacy_qpage_count_test() {
+    let test_dir_path = "test-legacy-qpage-count";
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+    for _ in 0..3 {
+        tx.seal_page().unwrap();
+    }
+    tx.push("on page 3").unwrap();
+
+    // versions before 9 kept the page count behind their lock
+    let info = tx.diskring_info.get_inner();
+    info.version.store(8, Ordering::Relaxed);
+    info.qpage_count.store(0, Ordering::Relaxed);
+    *info.legacy_qpage_count.write().unwrap() = 3;
+    drop((tx, _rx));
+
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    assert_eq!(tx.qpage_no, 3);
+    assert_eq!(rx.pop().unwrap(), Some("on page 3".to_string()));
+    assert_eq!(
+        tx.diskring_info.get_inner().version.load(Ordering::Relaxed),
+        INFO_VERSION
+    );
+
+    tx.seal_page().unwrap();
+    assert_eq!(
+        get_qpage_count_static(Path::new(test_dir_path).join(INFO_NAME)),
+        4
+    );
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn wait_for_test() {
+    let test_dir_path = "test-wait-for";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    assert!(!rx.wait_for(1, Duration::from_millis(50)).unwrap());
+    assert!(rx.wait_for(0, Duration::ZERO).unwrap());
+
+    let pusher = std::thread::spawn(move || {
+        for i in 0..10 {
+            std::thread::sleep(Duration::from_millis(5));
+            tx.push(i.to_string()).unwrap();
+        }
+        tx
+    });
+
+    assert!(rx.wait_for(10, Duration::from_secs(10)).unwrap());
+    let mut tx = pusher.join().unwrap();
+
+    // nothing was consumed
+    assert_eq!(rx.pop().unwrap(), Some("0".to_string()));
+    assert!(rx.wait_for(9, Duration::ZERO).unwrap());
+    assert!(!rx.wait_for(10, Duration::from_millis(50)).unwrap());
+
+    // counts across page flips
+    let msg = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE];
+    while tx.qpage_no == 0 {
+        tx.push(&msg).unwrap();
+    }
+    tx.push("last").unwrap();
+
+    let mut count = 0;
+    let mut probe = rx.clone();
+    while probe.pop().unwrap().is_some() {
+        count += 1;
+    }
+    assert!(count > 9);
+    assert!(rx.wait_for(count, Duration::ZERO).unwrap());
+    assert!(!rx.wait_for(count + 1, Duration::ZERO).unwrap());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn pop_chunk_test() {
+    let test_dir_path = "test-pop-chunk";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .chunk_size(1000)
+        .build()
+        .unwrap();
+
+    let big: Vec<u8> = (0..3500u32).map(|i| i as u8).collect();
+    tx.push(&big).unwrap();
+    tx.push("small").unwrap();
+    tx.push(&big).unwrap();
+
+    let mut msg = Vec::new();
+    let mut chunks = 0;
+    loop {
+        let chunk = rx.pop_chunk().unwrap().unwrap();
+        assert!(chunk.bytes.len() <= 1000);
+        msg.extend_from_slice(&chunk.bytes);
+        chunks += 1;
+
+        if !chunk.more {
+            break;
+        }
+    }
+    assert_eq!(chunks, 4);
+    assert_eq!(msg, big);
+
+    assert_eq!(
+        rx.pop_chunk().unwrap(),
+        Some(Chunk {
+            bytes: b"small".to_vec(),
+            more: false
+        })
+    );
+
+    // a regular pop mid-message takes all of it
+    assert!(rx.pop_chunk().unwrap().unwrap().more);
+    assert_eq!(rx.pop_as::<Vec<u8>>().unwrap(), Some(big));
+    assert_eq!(rx.pop_chunk().unwrap(), None);
+
+    assert!(matches!(
+        Builder::new(test_dir_path).chunk_size(0).build(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn finish_test() {
+    let test_dir_path = "test-finish";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    let mut tx2 = tx.clone();
+
+    assert_eq!(rx.final_position(), None);
+
+    // the first page fills up so the end is on a later one
+    let msg = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE];
+    while tx2.qpage_no == 0 {
+        tx2.push(&msg).unwrap();
+    }
+    for i in 0..10 {
+        tx.push(i.to_string()).unwrap();
+    }
+
+    let final_pos = tx.finish().unwrap();
+    assert_eq!(final_pos.qpage_no(), 1);
+    assert!(matches!(tx2.push("late"), Err(RingbufError::Closed)));
+
+    // a push that got past the closed check before finish can't reserve anymore
+    let mut tx3 = DiskRing::<Sender>::new(test_dir_path).unwrap();
+    let format = tx3.format;
+    assert!(matches!(
+        tx3.qpage.get_inner().try_push(b"late", 0, format),
+        Err(qpage::Error::Closed)
+    ));
+    assert!(matches!(tx3.flip_and_open(), Err(RingbufError::Closed)));
+
+    // a consumer opened later finds the end in the info file
+    let mut rx2 = DiskRing::<Receiver>::new(test_dir_path).unwrap();
+    assert_eq!(rx2.final_position(), Some(final_pos));
+
+    let mut msgs = 0;
+    while !rx.is_at_end(final_pos) {
+        if rx.pop().unwrap().is_some() {
+            msgs += 1;
+        }
+    }
+    assert!(msgs > 10);
+    assert_eq!(rx.pop().unwrap(), None);
+
+    // a restarted producer can take it up again
+    tx2.reopen().unwrap();
+    assert!(!rx.is_closed());
+    assert_eq!(rx2.final_position(), None);
+    tx2.push("resumed").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("resumed".to_string()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[cfg(feature = "prometheus")]
+#[test]
+fn render_prometheus_test() {
+    let test_dir_path = "test-render-prometheus";
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+    tx.push("hello").unwrap();
+    tx.push("world").unwrap();
+
+    let (_, mut billing) = Builder::new(test_dir_path)
+        .cursor("billing")
+        .build()
+        .unwrap();
+    billing.pop().unwrap();
+    billing.commit().unwrap();
+
+    let out = render_prometheus(test_dir_path);
+
+    let mut names = Vec::new();
+    for line in out.lines() {
+        if let Some(comment) = line.strip_prefix("# ") {
+            assert!(comment.starts_with("HELP ") || comment.starts_with("TYPE "));
+            continue;
+        }
+
+        let (series, value) = line.rsplit_once(' ').unwrap();
+        let (name, labels) = series.split_once('{').unwrap();
+        assert!(labels.starts_with(&format!("ringbuf=\"{test_dir_path}\"")));
+        value.parse::<f64>().unwrap();
+
+        names.push(name);
+    }
+
+    assert_eq!(
+        names,
+        [
+            "disk_ringbuffer_up",
+            "disk_ringbuffer_pages",
+            "disk_ringbuffer_head_page",
+            "disk_ringbuffer_max_pages",
+            "disk_ringbuffer_bytes_on_disk",
+            "disk_ringbuffer_bytes_allocated",
+            "disk_ringbuffer_retained_messages",
+            "disk_ringbuffer_closed",
+            "disk_ringbuffer_dropped_messages_total",
+            "disk_ringbuffer_consumer_lag_messages",
+        ]
+    );
+    assert!(out.contains("disk_ringbuffer_up{ringbuf=\"test-render-prometheus\"} 1\n"));
+    assert!(
+        out.contains("disk_ringbuffer_retained_messages{ringbuf=\"test-render-prometheus\"} 2\n")
+    );
+    assert!(out.contains(
+        "disk_ringbuffer_consumer_lag_messages{ringbuf=\"test-render-prometheus\",consumer=\"billing\"} 1\n"
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    // scraping somewhere without a ringbuf doesn't leave one behind
+    let missing_dir_path = "test-render-prometheus-missing";
+    assert!(render_prometheus(missing_dir_path)
+        .contains("disk_ringbuffer_up{ringbuf=\"test-render-prometheus-missing\"} 0\n"));
+    assert!(!Path::new(missing_dir_path).exists());
+
+    std::fs::create_dir_all(missing_dir_path).unwrap();
+    assert!(render_prometheus(missing_dir_path).contains("} 0\n"));
+    assert_eq!(std::fs::read_dir(missing_dir_path).unwrap().count(), 0);
+    std::fs::remove_dir_all(missing_dir_path).unwrap();
+}
+
+#[test]
+fn open_live_test() {
+    let test_dir_path = "test-open-live";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("backlog 1").unwrap();
+    tx.push("backlog 2").unwrap();
+
+    let mut live = DiskRing::<Receiver>::open_live(test_dir_path).unwrap();
+    assert_eq!(live.pop().unwrap(), None);
+
+    tx.push("new").unwrap();
+    assert_eq!(live.pop().unwrap(), Some("new".to_string()));
+    assert_eq!(live.pop().unwrap(), None);
+
+    // regular receivers still start at the beginning
+    assert_eq!(rx.pop().unwrap(), Some("backlog 1".to_string()));
+
+    // a live receiver opened on a full page moves on to the next one
+    let msg = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE];
+    let mut filler = tx.clone();
+    while filler.qpage_no == 0 {
+        filler.push(&msg).unwrap();
+    }
+    tx.push("on page 1").unwrap();
+
+    let mut live = DiskRing::<Receiver>::new(test_dir_path).unwrap();
+    live.qpage = live.open_page(0).unwrap();
+    live.qpage_no = 0;
+    live.read_byte = live.qpage.get_inner().tail(None).unwrap();
+    // the push that didn't fit on page 0 anymore is the first on page 1
+    assert_eq!(live.pop_as::<Vec<u8>>().unwrap(), Some(msg));
+    assert_eq!(live.pop().unwrap(), Some("on page 1".to_string()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn pop_no_flip_test() {
+    let test_dir_path = "test-pop-no-flip";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("first").unwrap();
+    assert!(!rx.advance_page().unwrap());
+
+    let msg = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE];
+    while tx.qpage_no == 0 {
+        tx.push(&msg).unwrap();
+    }
+    tx.push("on page 1").unwrap();
+
+    assert_eq!(
+        rx.pop_no_flip().unwrap(),
+        PopNoFlipStatus::Message("first".to_string())
+    );
+    loop {
+        match rx.pop_no_flip().unwrap() {
+            PopNoFlipStatus::Message(_) => {}
+            PopNoFlipStatus::AtPageBoundary => break,
+            PopNoFlipStatus::CaughtUp => panic!("caught up before the end of the page"),
+        }
+    }
+
+    // stays put until told to move on
+    assert_eq!(rx.pop_no_flip().unwrap(), PopNoFlipStatus::AtPageBoundary);
+    assert_eq!(rx.qpage_no, 0);
+
+    assert!(rx.advance_page().unwrap());
+    assert_eq!(rx.qpage_no, 1);
+    assert!(!rx.advance_page().unwrap());
+
+    assert_eq!(rx.pop_as::<Vec<u8>>().unwrap(), Some(msg));
+    assert_eq!(rx.pop().unwrap(), Some("on page 1".to_string()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn gone_test() {
+    let test_dir_path = "test-gone";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("hello").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("hello".to_string()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    // the mapped page is still there, the error shows up as soon as a new one is needed
+    let msg = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE];
+    let err = loop {
+        if let Err(e) = tx.push(&msg) {
+            break e;
+        }
+    };
+    assert!(matches!(err, RingbufError::Gone));
+    assert!(!Path::new(test_dir_path).exists());
+
+    // same when only the info file is gone
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+    std::fs::remove_file(Path::new(test_dir_path).join(INFO_NAME)).unwrap();
+    let err = loop {
+        if let Err(e) = tx.push(&msg) {
+            break e;
+        }
+    };
+    assert!(matches!(err, RingbufError::Gone));
+    assert!(!Path::new(test_dir_path).join("1.page.bin").exists());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn commit_policy_test() {
+    let test_dir_path = "test-commit-policy";
+    let builder = Builder::new(test_dir_path)
+        .cursor("consumer")
+        .commit_policy(CommitPolicy::EveryMessages(100));
+    let (mut tx, mut rx) = builder.clone().build().unwrap();
+
+    for i in 0..250 {
+        tx.push(i.to_string()).unwrap();
+    }
+
+    let mut committed = Vec::new();
+    for _ in 0..250 {
+        rx.pop().unwrap().unwrap();
+
+        if rx.committed_position() != committed.last().copied() {
+            committed.push(rx.committed_position().unwrap());
+        }
+    }
+
+    // exactly at message 100 and 200
+    let frame_len = |i: usize| FrameFormat::default().frame_len(i.to_string().len());
+    let pos_after = |n: usize| (0..n).map(frame_len).sum::<usize>();
+    assert_eq!(
+        committed,
+        [
+            Position {
+                qpage_no: 0,
+                read_byte: pos_after(100)
+            },
+            Position {
+                qpage_no: 0,
+                read_byte: pos_after(200)
+            },
+        ]
+    );
+    drop(rx);
+
+    // a restart resumes after the last checkpoint, not where the receiver stopped
+    let (_, mut rx) = builder.clone().build().unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("200".to_string()));
+
+    rx.commit().unwrap();
+    let (_, mut rx) = builder.clone().build().unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("201".to_string()));
+
+    // every other kind of pop counts towards the policy just the same
+    let (_, mut rx) = builder
+        .commit_policy(CommitPolicy::EveryMessages(2))
+        .build()
+        .unwrap();
+    let mut buf = Vec::new();
+    rx.pop_into(&mut buf).unwrap().unwrap();
+    assert_eq!(rx.skip_messages(1).unwrap(), 1);
+    assert_eq!(rx.committed_position(), Some(rx.read_position()));
+    rx.pop_as::<Vec<u8>>().unwrap().unwrap();
+    rx.pop_cow().unwrap().unwrap();
+    assert_eq!(rx.committed_position(), Some(rx.read_position()));
+    assert_eq!(rx.pop_batch_ref(2).count(), 2);
+    assert_eq!(rx.committed_position(), Some(rx.read_position()));
+    rx.pop_batch_bytes(usize::MAX, &mut buf).unwrap();
+    assert_eq!(rx.read_position().read_byte, pos_after(250));
+    // 201 to 249 is an odd number of messages
+    assert_eq!(rx.committed_position().unwrap().read_byte, pos_after(249));
+
+    // no cursor, nothing to commit to
+    assert!(matches!(
+        DiskRing::<Receiver>::new(test_dir_path).unwrap().commit(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+// handles are only tracked on unix
+#[cfg(unix)]
+fn gc_test() {
+    let root = Path::new("test-gc");
+    let old = root.join("old");
+    let gone = root.join("gone");
+    let fresh = root.join("fresh");
+    let live = root.join("live");
+    let not_a_ringbuf = root.join("other");
+
+    for dir in [&old, &gone] {
+        let (mut tx, _) = new(dir).unwrap();
+        tx.push("old").unwrap();
+    }
+    let (mut tx, _) = new(&fresh).unwrap();
+    tx.push("fresh").unwrap();
+    let (mut live_tx, _) = new(&live).unwrap();
+    live_tx.push("live").unwrap();
+    std::fs::create_dir_all(&not_a_ringbuf).unwrap();
+
+    // age everything but a single page in the fresh one
+    let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+    for file in ["0.page.bin", INFO_NAME] {
+        for dir in [&old, &gone, &fresh, &live] {
+            File::options()
+                .write(true)
+                .open(dir.join(file))
+                .unwrap()
+                .set_modified(two_hours_ago)
+                .unwrap();
+        }
+    }
+    File::options()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(fresh.join("1.page.bin"))
+        .unwrap();
+    std::fs::write(old.join("reader.cursor"), "").unwrap();
+    std::fs::write(old.join("unrelated"), "").unwrap();
+
+    let mut removed = gc(root, Duration::from_secs(60 * 60)).unwrap();
+    removed.sort();
+    assert_eq!(removed, [root.join("gone"), root.join("old")]);
+    // files that aren't the ringbuf's stay, and so does their directory
+    assert!(!gone.exists());
+    let left: Vec<_> = std::fs::read_dir(&old)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(left, ["unrelated"]);
+    assert!(fresh.exists());
+    assert!(not_a_ringbuf.exists());
+    // still open, however idle
+    assert!(live.join(INFO_NAME).exists());
+
+    assert!(gc(root, Duration::from_secs(60 * 60)).unwrap().is_empty());
+    live_tx.push("still there").unwrap();
+
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn message_keys_test() {
+    let test_dir_path = "test-message-keys";
+    let (mut tx, rx) = Builder::new(test_dir_path)
+        .message_keys(true)
+        .build()
+        .unwrap();
+    let mut rx_even = rx.clone();
+    let mut rx_odd = rx;
+
+    for i in 0..100u64 {
+        tx.push_keyed(i % 2, i.to_string()).unwrap();
+    }
+    assert_eq!(
+        tx.push("unkeyed").unwrap(),
+        FrameFormat::default().frame_len(7) + KEY_LEN
+    );
+
+    let mut even = Vec::new();
+    while let Some(m) = rx_even.pop_filtered(|key| key == 0).unwrap() {
+        even.push(m);
+    }
+    let mut odd = Vec::new();
+    while let Some(m) = rx_odd.pop_filtered(|key| key == 1).unwrap() {
+        odd.push(m);
+    }
+
+    let expected = |rem| {
+        (0..100)
+            .filter(move |i| i % 2 == rem)
+            .map(|i| i.to_string())
+    };
+    assert_eq!(
+        even,
+        expected(0)
+            .chain(["unkeyed".to_string()])
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(odd, expected(1).collect::<Vec<_>>());
+
+    // the setting sticks to the ringbuf
+    assert!(matches!(
+        Builder::new(test_dir_path).message_keys(false).build(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    assert!(matches!(
+        tx.push_keyed(1, "no keys"),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+    tx.push("no keys").unwrap();
+    assert_eq!(
+        rx.pop_filtered(|key| key == 0).unwrap(),
+        Some("no keys".to_string())
+    );
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn shared_readers_test() {
+    let test_dir_path = "test-shared-readers";
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+
+    let shared = RingbufShared::new(Builder::new(test_dir_path)).unwrap();
+    let mut readers = (0..100)
+        .map(|_| shared.new_reader().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(shared.mapped_pages(), 1);
+
+    for i in 0..1000 {
+        tx.push(i.to_string()).unwrap();
+    }
+
+    for reader in &mut readers {
+        for i in 0..1000 {
+            assert_eq!(reader.pop().unwrap(), Some(i.to_string()));
+        }
+        assert_eq!(reader.pop().unwrap(), None);
+    }
+    assert_eq!(shared.mapped_pages(), 1);
+
+    // the last reader off a page unmaps it
+    let mut reader = readers.pop().unwrap();
+    drop(readers);
+
+    let msg = "a".repeat(qpage::DEFAULT_MAX_MSG_SIZE);
+    while tx.qpage_no == 0 {
+        tx.push(&msg).unwrap();
+    }
+    tx.push("on page 1").unwrap();
+
+    while reader.pop().unwrap().as_deref() != Some("on page 1") {}
+    assert_eq!(reader.read_position().qpage_no(), 1);
+    assert_eq!(shared.mapped_pages(), 1);
+
+    drop(reader);
+    assert_eq!(shared.mapped_pages(), 0);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn debug_test() {
+    let test_dir_path = "test-debug";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    tx.push("hello").unwrap();
+    rx.pop().unwrap();
+
+    let tx_debug = format!("{tx:?}");
+    assert!(tx_debug.starts_with("DiskRing { kind: PhantomData<"));
+    assert!(tx_debug.contains("Sender>"));
+    assert!(tx_debug.contains(test_dir_path));
+    assert!(tx_debug.contains("qpage_no: 0"));
+    assert!(tx_debug.contains(&format!("max_qpages: {DEFAULT_MAX_QPAGES}")));
+    assert!(tx_debug.len() < 256);
+
+    let rx_debug = format!("{rx:?}");
+    assert!(rx_debug.contains("Receiver>"));
+    assert!(rx_debug.contains(&format!("read_byte: {}", rx.read_byte)));
+    assert!(rx_debug.len() < 256);
+
+    let shared = RingbufShared::new(Builder::new(test_dir_path)).unwrap();
+    let reader = shared.new_reader().unwrap();
+    assert!(format!("{reader:?}").starts_with("SharedReader {"));
+    assert!(format!("{shared:?}").contains("mapped_pages: 1"));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn max_bytes_test() {
+    let test_dir_path = "test-max-bytes";
+    let page_path =
+        |qpage_no: usize| Path::new(test_dir_path).join(format!("{qpage_no}.{PAGE_EXT}"));
+
+    assert!(matches!(
+        Builder::new(test_dir_path).max_bytes(1024).build(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    // room for two pages but not three
+    let max_bytes = 3 * PAGE_FILE_SIZE as u64 - 1;
+    let (mut tx, _rx) = Builder::new(test_dir_path)
+        .max_bytes(max_bytes)
+        .build()
+        .unwrap();
+    assert_eq!(usage(test_dir_path).unwrap().max_qpages, 2);
+
+    push_until_page(&mut tx, 2);
+    assert!(!page_path(0).exists());
+    assert!(page_path(1).exists());
+    assert!(page_path(2).exists());
+    assert!(usage(test_dir_path).unwrap().bytes_on_disk <= max_bytes);
+
+    // like max_qpages it's only applied on creation, an existing ringbuf keeps its limit
+    set_max_qpage(test_dir_path, 5).unwrap();
+    drop(
+        Builder::new(test_dir_path)
+            .max_bytes(max_bytes)
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(usage(test_dir_path).unwrap().max_qpages, 5);
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    // preallocated pages and the info file come out of the same budget
+    let info_len = size_of::<DiskRingInfo>() as u64;
+    let max_bytes = 4 * PAGE_FILE_SIZE as u64 + info_len;
+    let (mut tx, _rx) = Builder::new(test_dir_path)
+        .max_bytes(max_bytes)
+        .preallocate_pages(1)
+        .build()
+        .unwrap();
+    assert_eq!(usage(test_dir_path).unwrap().max_qpages, 3);
+    push_until_page(&mut tx, 4);
+    drop(tx);
+    drop(
+        Builder::new(test_dir_path)
+            .preallocate_pages(1)
+            .build()
+            .unwrap(),
+    );
+    let info_on_disk = std::fs::metadata(Path::new(test_dir_path).join(INFO_NAME))
+        .unwrap()
+        .len();
+    assert!(usage(test_dir_path).unwrap().bytes_on_disk + info_on_disk <= max_bytes);
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    // and a page limit that allows fewer pages than the byte cap still wins
+    let (_tx, _rx) = Builder::new(test_dir_path)
+        .max_bytes(max_bytes)
+        .max_qpages(1)
+        .build()
+        .unwrap();
+    assert_eq!(usage(test_dir_path).unwrap().max_qpages, 1);
+    assert!(matches!(
+        Builder::new("test-max-bytes-no-room")
+            .max_bytes(PAGE_FILE_SIZE as u64)
+            .build(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn dropped_total_test() {
+    let test_dir_path = "test-dropped-total";
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+    set_max_qpage(test_dir_path, 2).unwrap();
+
+    let mut on_page_0 = 0;
+    for i in 0..100 {
+        tx.push(i.to_string()).unwrap();
+        on_page_0 += 1;
+    }
+
+    let msg = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE];
+    loop {
+        tx.push(&msg).unwrap();
+        if tx.qpage_no != 0 {
+            break;
+        }
+        on_page_0 += 1;
+    }
+    assert_eq!(tx.dropped_total(), 0);
+
+    // page 0 goes with the next flip
+    push_until_page(&mut tx, 2);
+    assert!(!Path::new(test_dir_path)
+        .join(format!("0.{PAGE_EXT}"))
+        .exists());
+    assert_eq!(tx.dropped_total(), on_page_0);
+    assert_eq!(usage(test_dir_path).unwrap().dropped_messages, on_page_0);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn open_existing_test() {
+    let test_dir_path = "test-open-existing";
+
+    assert!(matches!(
+        open_existing(test_dir_path),
+        Err(RingbufError::NotFound)
+    ));
+    assert!(!Path::new(test_dir_path).exists());
+
+    // an empty directory isn't a ringbuf either
+    std::fs::create_dir(test_dir_path).unwrap();
+    assert!(matches!(
+        open_existing(test_dir_path),
+        Err(RingbufError::NotFound)
+    ));
+
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+    tx.push("hello").unwrap();
+
+    let (_tx, mut rx) = open_existing(test_dir_path).unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("hello".to_string()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn next_len_test() {
+    let test_dir_path = "test-next-len";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    assert_eq!(rx.next_len().unwrap(), None);
+
+    tx.push("hello").unwrap();
+    tx.push("").unwrap();
+    tx.push("a".repeat(1000)).unwrap();
+
+    let mut buf = Vec::new();
+    for expected in [5, 0, 1000] {
+        // peeking doesn't consume anything
+        assert_eq!(rx.next_len().unwrap(), Some(expected));
+        assert_eq!(rx.next_len().unwrap(), Some(expected));
+
+        assert_eq!(rx.pop_into(&mut buf).unwrap(), Some(expected));
+        assert_eq!(buf.len(), expected);
+    }
+    assert_eq!(rx.next_len().unwrap(), None);
+    assert_eq!(rx.pop_into(&mut buf).unwrap(), None);
+
+    // and it moves on to the next page like pop does
+    push_until_page(&mut tx, 1);
+    tx.push("on page 1").unwrap();
+    while rx.qpage_no == 0 {
+        assert!(rx.pop_into(&mut buf).unwrap().is_some());
+    }
+    assert_eq!(buf, vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE]);
+    assert_eq!(rx.next_len().unwrap(), Some("on page 1".len()));
+    assert_eq!(rx.pop_into(&mut buf).unwrap(), Some("on page 1".len()));
+    assert_eq!(buf, b"on page 1");
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn skip_test() {
+    let test_dir_path = "test-skip";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    for i in 0..100 {
+        tx.push(i.to_string()).unwrap();
+    }
+
+    assert_eq!(rx.skip_messages(0).unwrap(), 0);
+    assert_eq!(rx.skip_messages(42).unwrap(), 42);
+    assert_eq!(rx.pop().unwrap(), Some("42".to_string()));
+
+    // stops once it catches up
+    assert_eq!(rx.skip_messages(100).unwrap(), 57);
+    assert_eq!(rx.pop().unwrap(), None);
+
+    // and crosses pages on the way
+    let big = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE];
+    let mut on_page_0 = 0;
+    while tx.qpage_no == 0 {
+        tx.push(&big).unwrap();
+        on_page_0 += 1;
+    }
+    tx.push("after").unwrap();
+
+    assert_eq!(rx.skip_messages(on_page_0).unwrap(), on_page_0);
+    assert_eq!(rx.qpage_no, 1);
+    assert_eq!(rx.pop().unwrap(), Some("after".to_string()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn frame_info_test() {
+    let test_dir_path = "test-frame-info";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .sync_markers(true)
+        .length_prefix(LengthPrefix::Varint)
+        .build()
+        .unwrap();
+
+    assert_eq!(rx.frame_info(), None);
+
+    tx.push("first").unwrap();
+    tx.push("a".repeat(300)).unwrap();
+
+    let page = std::fs::read(Path::new(test_dir_path).join("0").with_extension(PAGE_EXT)).unwrap();
+    for expected in ["first".to_string(), "a".repeat(300)] {
+        let info = rx.frame_info().unwrap();
+        // looking doesn't move the receiver
+        assert_eq!(rx.frame_info(), Some(info));
+        assert_eq!(info.page, 0);
+        assert_eq!(info.payload_len, expected.len());
+
+        let frame = &page[info.offset..];
+        assert_eq!(&frame[..SYNC_MARKER.len()], SYNC_MARKER);
+        let (len, prefix_len) = LengthPrefix::Varint
+            .decode(ByteOrder::Little, &frame[SYNC_MARKER.len()..])
+            .unwrap();
+        assert_eq!(len, expected.len());
+        assert_eq!(info.header_len, SYNC_MARKER.len() + prefix_len);
+        assert_eq!(
+            &frame[info.header_len..info.header_len + info.payload_len],
+            expected.as_bytes()
+        );
+
+        assert_eq!(rx.pop().unwrap(), Some(expected));
+    }
+    assert_eq!(rx.frame_info(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn seal_page_test() {
+    let test_dir_path = "test-seal-page";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    let mut tx2 = tx.clone();
+
+    tx.push("batch 1").unwrap();
+    tx.seal_page().unwrap();
+    assert_eq!(tx.qpage_no, 1);
+
+    // other senders move on too
+    tx2.push("batch 2").unwrap();
+    assert_eq!(tx2.qpage_no, 1);
+
+    // a sender still on a page someone else sealed only catches up
+    tx2.seal_page().unwrap();
+    tx.seal_page().unwrap();
+    assert_eq!(tx.qpage_no, 2);
+
+    // sealing an empty page works the same
+    tx.seal_page().unwrap();
+    tx.push("batch 3").unwrap();
+    assert_eq!(tx.qpage_no, 3);
+
+    assert_eq!(rx.pop().unwrap(), Some("batch 1".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("batch 2".to_string()));
+    assert_eq!(rx.qpage_no, 1);
+    assert_eq!(rx.pop().unwrap(), Some("batch 3".to_string()));
+    assert_eq!(rx.qpage_no, 3);
+    assert_eq!(rx.pop().unwrap(), None);
+
+    tx.close();
+    assert!(matches!(tx2.seal_page(), Err(RingbufError::Closed)));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn manual_clock_test() {
+    let test_dir_path = "test-manual-clock";
+    let clock = Arc::new(ManualClock::new());
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .cursor("consumer")
+        .commit_policy(CommitPolicy::Every(Duration::from_secs(60)))
+        .with_clock(clock.clone())
+        .build()
+        .unwrap();
+
+    for i in 0..3 {
+        tx.push(i.to_string()).unwrap();
+    }
+
+    rx.pop().unwrap();
+    clock.advance(Duration::from_secs(59));
+    rx.pop().unwrap();
+    assert_eq!(rx.committed_position(), None);
+
+    // the interval passes without anyone sleeping through it
+    clock.advance(Duration::from_secs(1));
+    rx.pop().unwrap();
+    assert_eq!(rx.committed_position(), Some(rx.read_position()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn clock_backward_test() {
+    let test_dir_path = "test-clock-backward";
+    let clock = Arc::new(ManualClock::new());
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .cursor("consumer")
+        .commit_policy(CommitPolicy::Every(Duration::from_secs(60)))
+        .with_clock(clock.clone())
+        .build()
+        .unwrap();
+
+    for i in 0..4 {
+        tx.push(i.to_string()).unwrap();
+    }
+
+    // going back doesn't commit, nor does it hold off commits until the clock catches up
+    clock.rewind(Duration::from_secs(120));
+    rx.pop().unwrap();
+    assert_eq!(rx.committed_position(), None);
+
+    clock.advance(Duration::from_secs(59));
+    rx.pop().unwrap();
+    assert_eq!(rx.committed_position(), None);
+
+    clock.advance(Duration::from_secs(1));
+    rx.pop().unwrap();
+    assert_eq!(rx.committed_position(), Some(rx.read_position()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+// run as the test itself and again as each of the writer processes it spawns
+#[test]
+fn multi_process_writers_test() {
+    const WRITER_ENV: &str = "DISK_RINGBUFFER_TEST_WRITER";
+    const MSGS_PER_WRITER: u64 = 20;
+
+    let test_dir_path = "test-multi-process-writers";
+    let msg_len = qpage::DEFAULT_MAX_MSG_SIZE;
+
+    if let Ok(writer) = std::env::var(WRITER_ENV) {
+        let writer = writer.parse::<u64>().unwrap();
+        let mut tx = DiskRing::<Sender>::new(test_dir_path).unwrap();
+
+        let mut msg = vec![0; msg_len];
+        for i in 0..MSGS_PER_WRITER {
+            msg[..8].copy_from_slice(&writer.to_le_bytes());
+            msg[8..16].copy_from_slice(&i.to_le_bytes());
+            tx.push(&msg).unwrap();
+        }
+
+        return;
+    }
+
+    let (_, mut rx) = new(test_dir_path).unwrap();
+
+    // both writers race each other through a couple of page flips
+    let writers = (0..2)
+        .map(|writer| {
+            std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "ringbuf::multi_process_writers_test"])
+                .env(WRITER_ENV, writer.to_string())
+                .stdout(std::process::Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    for mut writer in writers {
+        assert!(writer.wait().unwrap().success());
+    }
+
+    let mut next = [0; 2];
+    let mut pages = vec![rx.qpage_no];
+    while let Some(msg) = rx.pop_as::<Vec<u8>>().unwrap() {
+        assert_eq!(msg.len(), msg_len);
+        let writer = u64::from_le_bytes(msg[..8].try_into().unwrap()) as usize;
+        let i = u64::from_le_bytes(msg[8..16].try_into().unwrap());
+
+        // every message exactly once, in the order its writer pushed them
+        assert_eq!(i, next[writer]);
+        next[writer] += 1;
+
+        if pages.last() != Some(&rx.qpage_no) {
+            pages.push(rx.qpage_no);
+        }
+    }
+    assert_eq!(next, [MSGS_PER_WRITER; 2]);
+
+    // one sequence of pages without any allocated twice or skipped, and none past it
+    let qpage_count = get_qpage_count_static(Path::new(test_dir_path).join(INFO_NAME));
+    assert!(pages.len() > 1);
+    assert_eq!(pages, (0..=qpage_count).collect::<Vec<_>>());
+    assert!(!Path::new(test_dir_path)
+        .join(format!("{}.{PAGE_EXT}", qpage_count + 1))
+        .exists());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+// run as the test itself and again as each of the writer processes it spawns
+#[test]
+fn multi_process_page_flips_test() {
+    const WRITER_ENV: &str = "DISK_RINGBUFFER_TEST_FLIPPER";
+    const FLIPS_PER_WRITER: usize = 500;
+
+    let test_dir_path = "test-multi-process-page-flips";
+
+    if std::env::var(WRITER_ENV).is_ok() {
+        let mut tx = DiskRing::<Sender>::new(test_dir_path).unwrap();
+
+        // every page this writer moved the ringbuf on from, one per line
+        for _ in 0..FLIPS_PER_WRITER {
+            tx.qpage.get_inner().seal().unwrap();
+            if let Some(sealed) = tx.flip_and_open().unwrap() {
+                println!("{sealed}");
+            }
+        }
+
+        return;
+    }
+
+    let _ = new(test_dir_path).unwrap();
+
+    // both writers flip pages as fast as they can, racing for every single one
+    let writers = (0..2)
+        .map(|writer| {
+            std::process::Command::new(std::env::current_exe().unwrap())
+                .args([
+                    "--exact",
+                    "ringbuf::multi_process_page_flips_test",
+                    "--nocapture",
+                    "--quiet",
+                ])
+                .env(WRITER_ENV, writer.to_string())
+                .stdout(std::process::Stdio::piped())
+                .spawn()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let mut sealed = Vec::new();
+    for writer in writers {
+        let output = writer.wait_with_output().unwrap();
+        assert!(output.status.success());
+
+        sealed.extend(
+            String::from_utf8(output.stdout)
+                .unwrap()
+                .lines()
+                .filter_map(|line| line.parse::<usize>().ok()),
+        );
+    }
+
+    // every page was moved on from by exactly one of them
+    let qpage_count = get_qpage_count_static(Path::new(test_dir_path).join(INFO_NAME));
+    sealed.sort_unstable();
+    assert!(qpage_count >= FLIPS_PER_WRITER);
+    assert_eq!(sealed, (0..qpage_count).collect::<Vec<_>>());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn panicking_writer_test() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let test_dir_path = "test-panicking-writer";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("before").unwrap();
+
+    // a copy source that fails halfway through filling a reservation
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let mut frame = tx.reserve(100).unwrap();
+        frame[..5].copy_from_slice(b"hello");
+        panic!("copy source failed");
+    }));
+    assert!(res.is_err());
+
+    // a push dying between reserving and writing its frame, with another push
+    // already reserved behind it so the space can't just be handed back
+    let qpage = tx.qpage.get_inner();
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let _guard = qpage.reserve_guarded(100, tx.format).unwrap().unwrap();
+        qpage.try_push(b"behind", 0, tx.format).unwrap();
+        panic!("copy source failed");
+    }));
+    assert!(res.is_err());
+    assert_eq!(qpage.registered_writers(), None);
+
+    // same for a frame too short to pad over on its own
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        let _guard = qpage.reserve_guarded(1, tx.format).unwrap().unwrap();
+        qpage.try_push(b"behind tiny", 0, tx.format).unwrap();
+        panic!("copy source failed");
+    }));
+    assert!(res.is_err());
+
+    tx.push("after").unwrap();
+
+    assert_eq!(rx.pop().unwrap(), Some("before".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("behind".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("behind tiny".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("after".to_string()));
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn split_messages_test() {
+    let test_dir_path = "test-split-messages";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .split_messages(true)
+        .build()
+        .unwrap();
+    let shared = RingbufShared::new(Builder::new(test_dir_path)).unwrap();
+    let mut reader = shared.new_reader().unwrap();
+
+    let msg = |i: usize| vec![b'a' + (i % 26) as u8; 10_000_000 + i];
+    let mut pushed = 0;
+    while tx.qpage_no == 0 {
+        tx.push(msg(pushed)).unwrap();
+        pushed += 1;
+    }
+
+    // the last message starts at the very end of the first page
+    for i in 0..pushed - 1 {
+        assert_eq!(rx.pop().unwrap().unwrap().as_bytes(), msg(i));
+    }
+    assert_eq!(
+        rx.qpage.get_inner().frame_kind(rx.read_byte, rx.format),
+        Some(FrameKind::Head)
+    );
+
+    let pos = rx.read_position();
+    assert_eq!(rx.pop().unwrap().unwrap().as_bytes(), msg(pushed - 1));
+    assert_eq!(rx.qpage_no, 1);
+    assert_eq!(rx.pop().unwrap(), None);
+
+    // re-read through the second half
+    assert_eq!(rx.re_read(pos).unwrap(), None);
+    let tail = Position {
+        qpage_no: 1,
+        read_byte: 0,
+    };
+    assert_eq!(
+        rx.re_read(tail).unwrap().unwrap().as_bytes(),
+        msg(pushed - 1)
+    );
+
+    for i in 0..pushed {
+        assert_eq!(reader.pop().unwrap().unwrap().as_bytes(), msg(i));
+    }
+    assert_eq!(reader.pop().unwrap(), None);
+
+    // the format can't change once it's on disk
+    assert!(matches!(
+        Builder::new(test_dir_path).split_messages(false).build(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn head_position_test() {
+    let test_dir_path = "test-head-position";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    let pos = |qpage_no, read_byte| Position {
+        qpage_no,
+        read_byte,
+    };
+    assert_eq!(head_position(test_dir_path).unwrap(), pos(0, 0));
+
+    for i in 0..10 {
+        tx.push(format!("{i}")).unwrap();
+    }
+    let format = tx.format;
+    assert_eq!(
+        head_position(test_dir_path).unwrap(),
+        pos(0, 10 * format.frame_len(1))
+    );
+
+    // a receiver that read everything is right at the head
+    while rx.pop().unwrap().is_some() {}
+    assert_eq!(head_position(test_dir_path).unwrap(), rx.read_position());
+
+    tx.seal_page().unwrap();
+    tx.push("0").unwrap();
+    assert_eq!(
+        head_position(test_dir_path).unwrap(),
+        pos(1, format.frame_len(1))
+    );
+    assert!(head_position(test_dir_path).unwrap() > rx.read_position());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn export_import_test() {
+    let test_dir_path = "test-export";
+    let import_dir_path = "test-import";
+    let archive_path = "test-export.archive";
+
+    let (mut tx, _rx) = Builder::new(test_dir_path)
+        .message_keys(true)
+        .build()
+        .unwrap();
+    let msgs: Vec<Vec<u8>> = (0..100u8).map(|i| vec![i; i as usize]).collect();
+    for (i, msg) in msgs.iter().enumerate() {
+        tx.push_keyed(i as u64, msg).unwrap();
+        if i % 30 == 0 {
+            tx.seal_page().unwrap();
+        }
+    }
+
+    assert_eq!(export(test_dir_path, archive_path).unwrap(), msgs.len());
+    assert_eq!(import(archive_path, import_dir_path).unwrap(), msgs.len());
+    // nothing in the archive gets reclaimed on the way in
+    assert!(usage(import_dir_path).unwrap().unbounded);
+
+    // each message has to come back with its own key
+    let mut rx = DiskRing::<Receiver>::new(import_dir_path).unwrap();
+    for (i, msg) in msgs.iter().enumerate() {
+        let popped = rx.pop_filtered(|key| key == i as u64).unwrap().unwrap();
+        assert_eq!(popped.as_bytes(), msg);
+    }
+    assert_eq!(rx.pop().unwrap(), None);
+
+    // keys don't fit into a ringbuf without them
+    std::fs::remove_dir_all(import_dir_path).unwrap();
+    drop(Builder::new(import_dir_path).build().unwrap());
+    assert!(matches!(
+        import(archive_path, import_dir_path),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    std::fs::write(archive_path, b"not an archive").unwrap();
+    assert!(matches!(
+        import(archive_path, import_dir_path),
+        Err(RingbufError::InvalidArchive)
+    ));
+
+    // an archive cut off in the middle of a length isn't taken for a shorter one
+    let mut archive = ARCHIVE_MAGIC.to_vec();
+    archive.push(0);
+    archive.extend_from_slice(&3u32.to_le_bytes());
+    archive.extend_from_slice(b"abc");
+    archive.extend_from_slice(&[7, 0]);
+    std::fs::write(archive_path, &archive).unwrap();
+    std::fs::remove_dir_all(import_dir_path).unwrap();
+    assert!(matches!(
+        import(archive_path, import_dir_path),
+        Err(RingbufError::InvalidArchive)
+    ));
+    archive.truncate(archive.len() - 2);
+    std::fs::write(archive_path, &archive).unwrap();
+    std::fs::remove_dir_all(import_dir_path).unwrap();
+    assert_eq!(import(archive_path, import_dir_path).unwrap(), 1);
+    assert_eq!(
+        DiskRing::<Receiver>::new(import_dir_path)
+            .unwrap()
+            .pop()
+            .unwrap(),
+        Some("abc".to_string())
+    );
+
+    // lengths that don't fit are refused before anything is read for them
+    let oversized = |flags: u8, len: u32, section: &[u8]| {
+        let mut archive = ARCHIVE_MAGIC.to_vec();
+        archive.push(flags);
+        archive.extend_from_slice(&len.to_le_bytes());
+        archive.extend_from_slice(section);
+        std::fs::write(archive_path, &archive).unwrap();
+        std::fs::remove_dir_all(import_dir_path).unwrap();
+
+        Builder::new(import_dir_path)
+            .length_prefix(LengthPrefix::U8)
+            .import(archive_path)
+    };
+    assert!(matches!(
+        oversized(0, u32::MAX, &[]),
+        Err(RingbufError::InvalidArchive)
+    ));
+    // one header with a 300 byte key, in a ringbuf that only fits 254 byte messages
+    assert!(matches!(
+        oversized(0b10, 0, &[1, 0, 44, 1]),
+        Err(RingbufError::InvalidArchive)
+    ));
+    assert!(matches!(
+        export("test-export-missing", archive_path),
+        Err(RingbufError::NotFound)
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+    std::fs::remove_dir_all(import_dir_path).unwrap();
+    std::fs::remove_file(archive_path).unwrap();
+}
+
+#[test]
+fn read_cursor_overflow_test() {
+    let test_dir_path = "test-read-cursor-overflow";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("first").unwrap();
+
+    // a length that would wrap the cursor around, or send it off the page
+    assert!(matches!(
+        advance_read_byte(usize::MAX - 1, tx.format.frame_len(5)),
+        Err(RingbufError::ReadError)
+    ));
+    assert!(matches!(
+        advance_read_byte(qpage::DEFAULT_QUEUE_SIZE, 1),
+        Err(RingbufError::ReadError)
+    ));
+
+    // reading from a position past the page is an error instead of a panic
+    for read_byte in [qpage::DEFAULT_QUEUE_SIZE + 1, usize::MAX] {
+        let pos = Position {
+            qpage_no: 0,
+            read_byte,
+        };
+        assert!(matches!(rx.re_read(pos), Err(RingbufError::ReadError)));
+    }
+
+    assert_eq!(rx.pop().unwrap(), Some("first".to_string()));
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn on_page_full_test() {
+    let test_dir_path = "test-on-page-full";
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+
+    let full_pages = Arc::new(Mutex::new(Vec::new()));
+    let full_pages_hook = full_pages.clone();
+    tx.on_page_full(move |qpage_no| full_pages_hook.lock().unwrap().push(qpage_no));
+
+    tx.seal_page().unwrap();
+    push_until_page(&mut tx, 3);
+    assert_eq!(*full_pages.lock().unwrap(), vec![1, 2]);
+
+    // clones share the hook
+    let mut tx2 = tx.clone();
+    push_until_page(&mut tx2, 4);
+    assert_eq!(*full_pages.lock().unwrap(), vec![1, 2, 3]);
+
+    // a sender still on page 3 only follows tx2 there, page 3 was reported already
+    tx.push("late").unwrap();
+    assert_eq!(tx.qpage_no, 4);
+    assert_eq!(*full_pages.lock().unwrap(), vec![1, 2, 3]);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn trim_before_test() {
+    let test_dir_path = "test-trim-before";
+    let page_path =
+        |qpage_no: usize| Path::new(test_dir_path).join(format!("{qpage_no}.{PAGE_EXT}"));
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .cursor("consumer")
+        .build()
+        .unwrap();
+
+    for page in 0..4 {
+        tx.push(format!("page {page}")).unwrap();
+        tx.seal_page().unwrap();
+    }
+
+    assert_eq!(rx.pop().unwrap(), Some("page 0".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("page 1".to_string()));
+    rx.commit().unwrap();
+
+    // the cursor is still on page 1
+    assert!(matches!(
+        tx.trim_before(2),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+    assert!(page_path(0).exists());
+
+    tx.trim_before(1).unwrap();
+    assert!(!page_path(0).exists());
+    assert!((1..=4).all(|qpage_no| page_path(qpage_no).exists()));
+
+    assert_eq!(rx.pop().unwrap(), Some("page 2".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("page 3".to_string()));
+    assert_eq!(rx.pop().unwrap(), None);
+    rx.commit().unwrap();
+
+    tx.trim_before(4).unwrap();
+    assert!((0..4).all(|qpage_no| !page_path(qpage_no).exists()));
+    assert!(matches!(
+        tx.trim_before(5),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    // the newest page is still there to read and write
+    tx.push("page 4").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("page 4".to_string()));
+
+    // cursors are found through a directory descriptor too
+    #[cfg(unix)]
+    {
+        tx.seal_page().unwrap();
+        let dir_fd = OwnedFd::from(File::open(test_dir_path).unwrap());
+        let mut fd_tx = DiskRing::<Sender>::from_dir_fd(dir_fd).unwrap();
+        assert!(matches!(
+            fd_tx.trim_before(5),
+            Err(RingbufError::InvalidConfig(_))
+        ));
+        assert_eq!(rx.pop().unwrap(), None);
+        rx.commit().unwrap();
+        fd_tx.trim_before(5).unwrap();
+        assert!(!page_path(4).exists());
+    }
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn snapshot_test() {
+    let test_dir_path = "test-snapshot";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    tx.push("a").unwrap();
+    tx.seal_page().unwrap();
+    tx.push("b").unwrap();
+    tx.push("c").unwrap();
+
+    let mut snapshot = rx.snapshot().unwrap();
+    assert_eq!(
+        snapshot.end(),
+        Position {
+            qpage_no: 1,
+            read_byte: 2 * tx.format.frame_len(1),
+        }
+    );
+
+    tx.push("d").unwrap();
+    tx.seal_page().unwrap();
+    tx.push("e").unwrap();
+
+    let msgs: Vec<String> = snapshot.by_ref().map(Result::unwrap).collect();
+    assert_eq!(msgs, ["a", "b", "c"]);
+    assert_eq!(snapshot.pop().unwrap(), None);
+
+    // the receiver itself still sees everything
+    for msg in ["a", "b", "c", "d", "e"] {
+        assert_eq!(rx.pop().unwrap(), Some(msg.to_string()));
+    }
+
+    // a snapshot at the head is empty
+    assert_eq!(rx.snapshot().unwrap().pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn push_backoff_test() {
+    let test_dir_path = "test-push-backoff";
+    let num_threads = 4;
+    let msg = vec![b'a'; qpage::DEFAULT_MAX_MSG_SIZE / 2];
+
+    let (tx, mut rx) = Builder::new(test_dir_path)
+        .push_backoff(BackoffPolicy {
+            spin_limit: 2,
+            yield_limit: 4,
+            max_sleep: Duration::from_micros(50),
+        })
+        .build()
+        .unwrap();
+
+    // enough for every sender to run into full pages a couple of times
+    let per_thread = 3 * qpage::DEFAULT_QUEUE_SIZE / msg.len() / num_threads;
+    let threads: Vec<_> = (0..num_threads)
+        .map(|_| {
+            let mut tx = tx.clone();
+            let msg = msg.clone();
+            std::thread::spawn(move || {
+                for _ in 0..per_thread {
+                    tx.push(&msg).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    let mut popped = 0;
+    while let Some(m) = rx.pop().unwrap() {
+        assert_eq!(m.len(), msg.len());
+        popped += 1;
+    }
+    assert_eq!(popped, per_thread * num_threads);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn verify_page_test() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let test_dir_path = "test-verify-page";
+    let page_path = Path::new(test_dir_path).join("0").with_extension(PAGE_EXT);
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+
+    let mut tx2 = tx.clone();
+    tx.push("first").unwrap();
+    // a reservation someone else pushed after leaves padding behind when it's dropped
+    let reserved = tx.reserve(64).unwrap();
+    tx2.push("second").unwrap();
+    drop(reserved);
+    tx.seal_page().unwrap();
+    tx.push("third").unwrap();
+
+    let report = verify_page(test_dir_path, 0).unwrap();
+    assert!(report.is_healthy(), "{report:?}");
+    assert_eq!(report.frames, 2);
+    assert_eq!(report.padding_frames, 1);
+    assert!(verify_page(test_dir_path, 1).unwrap().is_healthy());
+    assert!(matches!(
+        verify_page(test_dir_path, 2),
+        Err(RingbufError::NotFound)
+    ));
+
+    // make the second frame claim way more than there is
+    let second_frame = report.end - tx.format.frame_len("second".len());
+    let mut f = std::fs::File::options()
+        .write(true)
+        .open(&page_path)
+        .unwrap();
+    f.seek(SeekFrom::Start((qpage::BUF_OFFSET + second_frame) as u64))
+        .unwrap();
+    f.write_all(&(qpage::MsgLengthType::MAX / 2).to_le_bytes())
+        .unwrap();
+    drop(f);
+
+    let report = verify_page(test_dir_path, 0).unwrap();
+    assert_eq!(report.frames, 1);
+    assert_eq!(
+        report.anomalies,
+        [PageAnomaly::FrameOutOfBounds {
+            offset: second_frame
+        }]
+    );
+
+    // and a page that lost its tail
+    let f = std::fs::File::options()
+        .write(true)
+        .open(&page_path)
+        .unwrap();
+    f.set_len(1024).unwrap();
+    drop(f);
+    assert_eq!(
+        verify_page(test_dir_path, 0).unwrap().anomalies,
+        [PageAnomaly::Truncated { len: 1024 }]
+    );
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn start_page_test() {
+    let test_dir_path = "test-start-page";
+    let page_path =
+        |qpage_no: usize| Path::new(test_dir_path).join(format!("{qpage_no}.{PAGE_EXT}"));
+
+    let (mut tx, mut rx) = Builder::new(test_dir_path).start_page(100).build().unwrap();
+    assert!(page_path(100).exists());
+    assert!(!page_path(0).exists());
+
+    tx.push("first").unwrap();
+    tx.seal_page().unwrap();
+    tx.push("second").unwrap();
+    assert!(page_path(101).exists());
+
+    assert_eq!(rx.read_position().qpage_no, 100);
+    assert_eq!(rx.pop().unwrap(), Some("first".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("second".to_string()));
+    assert_eq!(rx.read_position().qpage_no, 101);
+
+    // it sticks, and reclaiming stops at the first page
+    assert!(matches!(
+        Builder::new(test_dir_path).start_page(5).build(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+    drop(Builder::new(test_dir_path).build().unwrap());
+    set_max_qpage(test_dir_path, 1).unwrap();
+    assert!(!page_path(100).exists());
+    assert!(page_path(101).exists());
+    assert!(!page_path(0).exists());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn pop_batch_ref_test() {
+    let test_dir_path = "test-pop-batch-ref";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    for i in 0..100 {
+        tx.push(i.to_string()).unwrap();
+    }
+    tx.seal_page().unwrap();
+    tx.push("next page").unwrap();
+
+    let page_start = rx.qpage.get_inner() as *const QPage as usize;
+    let page = page_start..page_start + PAGE_FILE_SIZE;
+    let first: Vec<&[u8]> = rx.pop_batch_ref(10).collect();
+    assert_eq!(first.len(), 10);
+    for (i, m) in first.iter().enumerate() {
+        assert_eq!(*m, i.to_string().as_bytes());
+        // straight out of the page mapping
+        assert!(page.contains(&(m.as_ptr() as usize)));
+    }
+
+    // stops at the end of the page
+    let rest: Vec<String> = rx
+        .pop_batch_ref(usize::MAX)
+        .map(|m| String::from_utf8(m.to_vec()).unwrap())
+        .collect();
+    assert_eq!(rest, (10..100).map(|i| i.to_string()).collect::<Vec<_>>());
+    assert_eq!(rx.qpage_no, 0);
+
+    // and only moves on in the next call
+    let next: Vec<&[u8]> = rx.pop_batch_ref(usize::MAX).collect();
+    assert_eq!(next, [b"next page"]);
+    assert_eq!(rx.qpage_no, 1);
+    assert_eq!(rx.pop_batch_ref(usize::MAX).count(), 0);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn lagged_test() {
+    let test_dir_path = "test-lagged";
+    let page_path =
+        |qpage_no: usize| Path::new(test_dir_path).join(format!("{qpage_no}.{PAGE_EXT}"));
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    for page in 0..3 {
+        tx.push(format!("page {page}")).unwrap();
+        tx.seal_page().unwrap();
+    }
+    tx.push("page 3").unwrap();
+
+    // the receiver still has page 0 mapped when pages 0 and 1 are reclaimed
+    assert_eq!(rx.pop().unwrap(), Some("page 0".to_string()));
+    tx.trim_before(2).unwrap();
+    assert!(!page_path(1).exists());
+
+    // it says so instead of finding an empty page 1 where messages used to be
+    assert!(matches!(rx.pop(), Err(RingbufError::Lagged(1))));
+    assert!(!page_path(1).exists());
+    assert_eq!(rx.read_position().qpage_no, 2);
+
+    assert_eq!(rx.pop().unwrap(), Some("page 2".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("page 3".to_string()));
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    // same for pages max_qpages reclaimed while the receiver was still on page 0
+    let (mut tx, mut rx) = Builder::new(test_dir_path).max_qpages(2).build().unwrap();
+    tx.push("page 0").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("page 0".to_string()));
+
+    for page in 1..5 {
+        tx.seal_page().unwrap();
+        tx.push(format!("page {page}")).unwrap();
+    }
+    assert!(matches!(rx.pop(), Err(RingbufError::Lagged(2))));
+    assert_eq!(rx.read_position().qpage_no, 3);
+    assert_eq!(rx.pop().unwrap(), Some("page 3".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("page 4".to_string()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn file_mode_test() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let test_dir_path = "test-file-mode";
+    let mode = |name: &str| {
+        std::fs::metadata(Path::new(test_dir_path).join(name))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777
+    };
+
+    let (_tx, _rx) = Builder::new(test_dir_path)
+        .file_mode(0o600)
+        .preallocate_pages(1)
+        .build()
+        .unwrap();
+    assert_eq!(mode(INFO_NAME), 0o600);
+    assert_eq!(mode(&format!("0.{PAGE_EXT}")), 0o600);
+    assert_eq!(mode(&format!("1.{PAGE_EXT}")), 0o600);
+
+    // pages created through a directory descriptor get it too
+    let dir_fd = OwnedFd::from(File::open(test_dir_path).unwrap());
+    let (mut fd_tx, _) = Builder::from_dir_fd(dir_fd)
+        .file_mode(0o600)
+        .build()
+        .unwrap();
+    fd_tx.seal_page().unwrap();
+    fd_tx.push("hello").unwrap();
+    fd_tx.seal_page().unwrap();
+    fd_tx.push("hello").unwrap();
+    assert_eq!(fd_tx.qpage_no, 2);
+    assert_eq!(mode(&format!("2.{PAGE_EXT}")), 0o600);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn migrate_test() {
+    let src_dir_path = "test-migrate-src";
+    let dst_dir_path = "test-migrate-dst";
+
+    let (mut tx, _rx) = Builder::new(src_dir_path)
+        .message_keys(true)
+        .build()
+        .unwrap();
+    let msgs: Vec<Vec<u8>> = (0..=255u8).map(|i| vec![i; i as usize * 3]).collect();
+    for (i, msg) in msgs.iter().enumerate() {
+        tx.push_keyed(i as u64, msg).unwrap();
+        if i % 100 == 0 {
+            tx.seal_page().unwrap();
+        }
+    }
+
+    // to a smaller, big endian length prefix that still fits every message
+    let dst = Builder::new(dst_dir_path)
+        .length_prefix(LengthPrefix::U16)
+        .byte_order(ByteOrder::Big)
+        .message_keys(true);
+    assert_eq!(migrate(src_dir_path, dst).unwrap(), msgs.len());
+    assert!(usage(dst_dir_path).unwrap().unbounded);
+
+    let mut rx = DiskRing::<Receiver>::new(dst_dir_path).unwrap();
+    assert_eq!(rx.format.prefix, LengthPrefix::U16);
+    let mut buf = Vec::new();
+    for (i, msg) in msgs.iter().enumerate() {
+        assert_eq!(rx.pop_into(&mut buf).unwrap(), Some(msg.len()));
+        assert_eq!(&buf, msg);
+        assert_eq!(
+            rx.qpage
+                .get_inner()
+                .frame_key(rx.read_byte - rx.popped_frame_len, rx.format),
+            Some(i as u64)
+        );
+    }
+    assert_eq!(rx.pop().unwrap(), None);
+
+    // the target has to be new
+    assert!(matches!(
+        migrate(src_dir_path, Builder::new(dst_dir_path).message_keys(true)),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    // keys are only dropped when asked for
+    let dropped_dir_path = "test-migrate-dropped";
+    assert!(matches!(
+        migrate(src_dir_path, Builder::new(dropped_dir_path)),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+    assert!(!Path::new(dropped_dir_path).exists());
+    assert_eq!(
+        migrate(
+            src_dir_path,
+            Builder::new(dropped_dir_path).message_keys(false)
+        )
+        .unwrap(),
+        msgs.len()
+    );
+    let mut rx = DiskRing::<Receiver>::new(dropped_dir_path).unwrap();
+    assert!(!rx.format.keys);
+    for msg in &msgs {
+        assert_eq!(rx.pop_into(&mut buf).unwrap(), Some(msg.len()));
+        assert_eq!(&buf, msg);
+    }
+    std::fs::remove_dir_all(dropped_dir_path).unwrap();
+    assert!(matches!(
+        migrate(
+            "test-migrate-missing",
+            Builder::new("test-migrate-missing-dst")
+        ),
+        Err(RingbufError::NotFound)
+    ));
+
+    std::fs::remove_dir_all(src_dir_path).unwrap();
+    std::fs::remove_dir_all(dst_dir_path).unwrap();
+}
+
+#[test]
+fn max_flips_per_pop_test() {
+    let test_dir_path = "test-max-flips-per-pop";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .max_flips_per_pop(2)
+        .build()
+        .unwrap();
+
+    // five empty pages in front of the first message
+    for _ in 0..5 {
+        tx.seal_page().unwrap();
+    }
+    tx.push("hello").unwrap();
+
+    assert_eq!(rx.pop().unwrap(), None);
+    assert_eq!(rx.read_position().qpage_no, 2);
+    assert!(rx.has_data());
+    assert_eq!(rx.pop().unwrap(), None);
+    assert_eq!(rx.read_position().qpage_no, 4);
+    assert_eq!(rx.pop().unwrap(), Some("hello".to_string()));
+    assert_eq!(rx.pop().unwrap(), None);
+    assert!(!rx.has_data());
+
+    assert!(matches!(
+        Builder::new(test_dir_path).max_flips_per_pop(0).build(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn on_reclaim_pressure_test() {
+    let test_dir_path = "test-on-reclaim-pressure";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    set_max_qpage(test_dir_path, 3).unwrap();
+
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let warnings_hook = warnings.clone();
+    rx.on_reclaim_pressure(move |info| warnings_hook.lock().unwrap().push(info));
+
+    tx.push("0a").unwrap();
+    tx.push("0b").unwrap();
+    tx.seal_page().unwrap();
+    tx.push("1a").unwrap();
+
+    // two pages retained out of three, nothing to worry about yet
+    assert_eq!(rx.pop().unwrap(), Some("0a".to_string()));
+    assert!(warnings.lock().unwrap().is_empty());
+
+    // the slow receiver's page is the oldest one now, but it only fires once for it
+    tx.seal_page().unwrap();
+    tx.push("2a").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("0b".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("1a".to_string()));
+    assert_eq!(
+        *warnings.lock().unwrap(),
+        [ReclaimInfo {
+            page: 0,
+            newest_page: 2,
+            max_qpages: 3,
+        }]
+    );
+
+    // caught up again
+    assert_eq!(rx.pop().unwrap(), Some("2a".to_string()));
+    assert_eq!(warnings.lock().unwrap().len(), 1);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn open_or_create_test() {
+    let test_dir_path = "test-open-or-create";
+
+    let (mut tx, _rx, created) = open_or_create(test_dir_path).unwrap();
+    assert!(created);
+    tx.push("seed").unwrap();
+
+    let (_tx, mut rx, created) = open_or_create(test_dir_path).unwrap();
+    assert!(!created);
+    assert_eq!(rx.pop().unwrap(), Some("seed".to_string()));
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    // exactly one of several racing openers gets to create it
+    let openers: Vec<_> = (0..8)
+        .map(|_| std::thread::spawn(move || open_or_create(test_dir_path).unwrap().2))
+        .collect();
+    let created = openers
+        .into_iter()
+        .map(|opener| opener.join().unwrap())
+        .filter(|&created| created)
+        .count();
+    assert_eq!(created, 1);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn read_backoff_busy_spin_test() {
+    let test_dir_path = "test-read-backoff-busy-spin";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .read_backoff(ReaderBackoff::BusySpin)
+        .build()
+        .unwrap();
+
+    let start = Instant::now();
+    assert!(!rx.wait_for(1, Duration::from_millis(20)).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(20));
+
+    let t = std::thread::spawn(move || {
+        for i in 0..100 {
+            tx.push(i.to_string()).unwrap();
+        }
+        tx.close();
+    });
+
+    let msgs: Vec<String> = rx.stream().map(Result::unwrap).collect();
+    assert_eq!(msgs, (0..100).map(|i| i.to_string()).collect::<Vec<_>>());
+    t.join().unwrap();
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn receiver_at_head_test() {
+    let test_dir_path = "test-receiver-at-head";
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+
+    tx.push("before").unwrap();
+    let mut rx = tx.receiver_at_head().unwrap();
+    assert_eq!(rx.pop().unwrap(), None);
+
+    tx.push("after").unwrap();
+    tx.seal_page().unwrap();
+    tx.push("next page").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("after".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("next page".to_string()));
+    assert_eq!(rx.pop().unwrap(), None);
+
+    // from a sender left behind on an older page it's still the newest head
+    let mut old_tx = tx.clone();
+    tx.seal_page().unwrap();
+    tx.push("newest").unwrap();
+    let mut rx = old_tx.receiver_at_head().unwrap();
+    old_tx.push("latest").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("latest".to_string()));
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn huge_pages_test() {
+    let test_dir_path = "test-huge-pages";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .huge_pages(true)
+        .build()
+        .unwrap();
+
+    // whether or not the kernel backs them with huge pages, pages work the same
+    tx.push("first").unwrap();
+    tx.seal_page().unwrap();
+    tx.push("second").unwrap();
+    assert_eq!(rx.pop().unwrap(), Some("first".to_string()));
+    assert_eq!(rx.pop().unwrap(), Some("second".to_string()));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn reconfigure_test() {
+    let test_dir_path = "test-reconfigure";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    let page_path =
+        |qpage_no: usize| Path::new(test_dir_path).join(format!("{qpage_no}.{PAGE_EXT}"));
+
+    for _ in 0..3 {
+        tx.push("msg").unwrap();
+        tx.seal_page().unwrap();
+    }
+
+    let prev = reconfigure(test_dir_path, |runtime| {
+        runtime.set_max_bytes(2 * PAGE_FILE_SIZE as u64);
+        runtime.metadata = b"v2".to_vec();
+    })
+    .unwrap();
+    assert_eq!(
+        prev,
+        RuntimeConfig {
+            max_qpages: DEFAULT_MAX_QPAGES,
+            metadata: Vec::new(),
+        }
+    );
+
+    // both took effect, the messages on the reclaimed pages are counted as dropped
+    assert_eq!(rx.get_metadata(), b"v2");
+    assert!(!page_path(1).exists());
+    assert!(page_path(2).exists());
+    assert_eq!(tx.dropped_total(), 2);
+    assert_eq!(set_max_qpage(test_dir_path, 5).unwrap(), 2);
+
+    // nothing changes if one of them is invalid
+    assert!(matches!(
+        reconfigure(test_dir_path, |runtime| {
+            runtime.max_qpages = 1;
+            runtime.metadata = vec![0; MAX_METADATA_LEN + 1];
+        }),
+        Err(RingbufError::MetadataTooLong(_))
+    ));
+    assert_eq!(
+        reconfigure(test_dir_path, |_| {}).unwrap(),
+        RuntimeConfig {
+            max_qpages: 5,
+            metadata: b"v2".to_vec(),
+        }
+    );
+    assert!(page_path(2).exists());
+
+    // a panic in the closure leaves the ringbuf usable, in this process or any other
+    let res =
+        std::panic::catch_unwind(|| reconfigure(test_dir_path, |_| panic!("changed my mind")));
+    assert!(res.is_err());
+    assert_eq!(set_max_qpage(test_dir_path, 6).unwrap(), 5);
+    assert!(new(test_dir_path).is_ok());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    // limiting a ringbuf before its first handle leaves the format up to that handle
+    std::fs::create_dir_all(test_dir_path).unwrap();
+    set_max_qpage(test_dir_path, 3).unwrap();
+    let (_tx, _rx) = Builder::new(test_dir_path)
+        .length_prefix(LengthPrefix::U8)
+        .max_qpages(8)
+        .build()
+        .unwrap();
+    assert_eq!(usage(test_dir_path).unwrap().max_qpages, 3);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn position_ord_test() {
+    let pos = |qpage_no, read_byte| Position {
+        qpage_no,
+        read_byte,
+    };
+
+    assert!(pos(1, 0) < pos(1, 1));
+    // the start of a page is after anything on the one before
+    assert!(pos(2, 0) > pos(1, qpage::DEFAULT_QUEUE_SIZE));
+    assert_eq!(pos(3, 7).max(pos(2, 9)), pos(3, 7));
+
+    let mut positions = vec![pos(2, 0), pos(0, 5), pos(1, usize::MAX), pos(0, 0)];
+    positions.sort();
+    assert_eq!(
+        positions,
+        [pos(0, 0), pos(0, 5), pos(1, usize::MAX), pos(2, 0)]
+    );
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn reserved_range_check_test() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let test_dir_path = "test-reserved-range-check";
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+    let qpage = tx.qpage.get_inner();
+
+    // right up to the end of the page is fine
+    qpage
+        .reserved_mut(qpage::DEFAULT_QUEUE_SIZE - 4, 4)
+        .copy_from_slice(b"last");
+
+    // past it, or wrapping around, is caught in debug builds
+    let past_end = catch_unwind(AssertUnwindSafe(|| {
+        qpage.reserved_mut(qpage::DEFAULT_QUEUE_SIZE - 2, 4);
+    }));
+    assert!(past_end.is_err());
+    let wrapping = catch_unwind(AssertUnwindSafe(|| {
+        qpage.reserved_mut(usize::MAX - 1, 4);
+    }));
+    assert!(wrapping.is_err());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn tail_test() {
+    let test_dir_path = "test-tail";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    assert!(rx.tail(5).unwrap().is_empty());
+
+    for i in 0..100 {
+        tx.push(i.to_string()).unwrap();
+        if i % 30 == 29 {
+            tx.seal_page().unwrap();
+        }
+    }
+
+    let expected = |range: std::ops::Range<usize>| range.map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(rx.tail(5).unwrap(), expected(95..100));
+    // across page boundaries
+    assert_eq!(rx.tail(45).unwrap(), expected(55..100));
+    assert_eq!(rx.tail(1000).unwrap(), expected(0..100));
+    assert!(rx.tail(0).unwrap().is_empty());
+
+    // the receiver didn't move
+    assert_eq!(rx.pop().unwrap(), Some("0".to_string()));
+
+    // only what's still retained
+    set_max_qpage(test_dir_path, 2).unwrap();
+    assert_eq!(rx.tail(1000).unwrap(), expected(60..100));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn concurrent_first_open_test() {
+    const OPENERS: usize = 16;
+
+    for round in 0..5 {
+        let test_dir_path = format!("test-concurrent-first-open-{round}");
+        // so the ones only looking at the info file create it too, instead of failing
+        std::fs::create_dir_all(&test_dir_path).unwrap();
+        let barrier = Arc::new(std::sync::Barrier::new(OPENERS));
+
+        let openers: Vec<_> = (0..OPENERS)
+            .map(|i| {
+                let test_dir_path = test_dir_path.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    // half of them only look at the info file
+                    let qpage_count = match i % 2 {
+                        0 => get_qpage_count_static(Path::new(&test_dir_path).join(INFO_NAME)),
+                        _ => new(&test_dir_path).unwrap().0.qpage_no,
+                    };
+                    assert_eq!(qpage_count, 0);
+                })
+            })
+            .collect();
+        for opener in openers {
+            opener.join().unwrap();
+        }
+
+        // and it's still a fresh ringbuf afterwards
+        let (mut tx, mut rx) = new(&test_dir_path).unwrap();
+        assert_eq!(
+            get_qpage_count_static(Path::new(&test_dir_path).join(INFO_NAME)),
+            0
+        );
+        tx.push("first").unwrap();
+        assert_eq!(rx.pop().unwrap(), Some("first".to_string()));
+
+        std::fs::remove_dir_all(&test_dir_path).unwrap();
+    }
+}
+
+#[test]
+fn wait_for_page_test() {
+    let test_dir_path = "test-wait-for-page";
+
+    // nothing there yet, and waiting doesn't create it
+    assert!(!wait_for_page(test_dir_path, 0, Duration::from_millis(20)).unwrap());
+    assert!(!Path::new(test_dir_path).exists());
+
+    let (mut tx, _rx) = new(test_dir_path).unwrap();
+    assert!(wait_for_page(test_dir_path, 0, Duration::ZERO).unwrap());
+    assert!(!wait_for_page(test_dir_path, 2, Duration::from_millis(20)).unwrap());
+
+    let roller = std::thread::spawn(move || {
+        for _ in 0..2 {
+            std::thread::sleep(Duration::from_millis(10));
+            tx.seal_page().unwrap();
+        }
+        tx
+    });
+    assert!(wait_for_page(test_dir_path, 2, Duration::from_secs(10)).unwrap());
+    let mut tx = roller.join().unwrap();
+    assert_eq!(tx.qpage_no, 2);
+
+    // still counts once it's been reclaimed
+    tx.seal_page().unwrap();
+    set_max_qpage(test_dir_path, 1).unwrap();
+    assert!(!Path::new(test_dir_path)
+        .join(format!("2.{PAGE_EXT}"))
+        .exists());
+    assert!(wait_for_page(test_dir_path, 2, Duration::ZERO).unwrap());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn pop_into_uninit_test() {
+    let test_dir_path = "test-pop-into-uninit";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+    let mut std_rx = rx.clone();
+
+    let msgs: Vec<Vec<u8>> = (0..50u8).map(|i| vec![i; i as usize * 5]).collect();
+    for (i, msg) in msgs.iter().enumerate() {
+        tx.push(msg).unwrap();
+        if i == 25 {
+            tx.seal_page().unwrap();
+        }
+    }
+
+    let mut buf = [MaybeUninit::uninit(); 256];
+    let mut std_buf = Vec::new();
+    for _ in &msgs {
+        let len = rx.pop_into_uninit(&mut buf).unwrap().unwrap();
+        let popped = unsafe { slice::from_raw_parts(buf.as_ptr().cast::<u8>(), len) };
+
+        std_rx.pop_into(&mut std_buf).unwrap();
+        assert_eq!(popped, std_buf);
+    }
+    assert_eq!(rx.pop_into_uninit(&mut buf).unwrap(), None);
+
+    // too big for the buffer, it stays there until there's room
+    tx.push([7; 300]).unwrap();
+    assert!(matches!(
+        rx.pop_into_uninit(&mut buf),
+        Err(RingbufError::BufferTooSmall(300))
+    ));
+    let mut big_buf = [MaybeUninit::uninit(); 300];
+    assert_eq!(rx.pop_into_uninit(&mut big_buf).unwrap(), Some(300));
+    assert!(big_buf.iter().all(|b| unsafe { b.assume_init() } == 7));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn default_max_qpages_test() {
+    let test_dir_path = "test-default-max-qpages";
+    let (tx, rx) = new(test_dir_path).unwrap();
+    assert_eq!(usage(test_dir_path).unwrap().max_qpages, DEFAULT_MAX_QPAGES);
+
+    // an explicit limit only applies to a new ringbuf
+    drop(Builder::new(test_dir_path).max_qpages(3).build().unwrap());
+    assert_eq!(usage(test_dir_path).unwrap().max_qpages, DEFAULT_MAX_QPAGES);
+
+    // one that was made unbounded later on doesn't get the default back
+    set_max_qpage(test_dir_path, 0).unwrap();
+    drop(new(test_dir_path).unwrap());
+    assert_eq!(usage(test_dir_path).unwrap().max_qpages, 0);
+    drop((tx, rx));
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    // and one that's created unbounded stays that way
+    let (_tx, _rx) = Builder::new(test_dir_path).max_qpages(0).build().unwrap();
+    drop(new(test_dir_path).unwrap());
+    let u = usage(test_dir_path).unwrap();
+    assert_eq!(u.max_qpages, 0);
+    assert!(u.unbounded);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+
+    let test_dir_path = "test-default-max-qpages-bytes";
+    drop(
+        Builder::new(test_dir_path)
+            .max_bytes(40 * PAGE_FILE_SIZE as u64)
+            .build()
+            .unwrap(),
+    );
+    // the info file takes up some of the budget too
+    assert_eq!(usage(test_dir_path).unwrap().max_qpages, 39);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn short_file_test() {
+    let test_dir_path = "test-short-file";
+    std::fs::create_dir_all(test_dir_path).unwrap();
+
+    // files that can't be grown to their full size are never mapped
+    let page_path = Path::new(test_dir_path).join(format!("0.{PAGE_EXT}"));
+    std::fs::write(&page_path, [0; 1024]).unwrap();
+    let err = QPage::from_file(File::open(&page_path).unwrap())
+        .err()
+        .expect("a short page can't be mapped");
+    assert!(err.to_string().contains("couldn't extend a 1024 byte file"));
+
+    let info_path = Path::new(test_dir_path).join(INFO_NAME);
+    std::fs::write(&info_path, [0; 8]).unwrap();
+    assert!(matches!(
+        DiskRingInfo::from_file(File::open(&info_path).unwrap()),
+        Err(RingbufError::IoError(_))
+    ));
+
+    // but ones that already have the right size are fine without growing them
+    std::fs::remove_file(&page_path).unwrap();
+    QPage::allocate(&File::create(&page_path).unwrap()).unwrap();
+    qpage::extend_file(&File::open(&page_path).unwrap(), PAGE_FILE_SIZE as u64).unwrap();
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn flush_interval_test() {
+    let test_dir_path = "test-flush-interval";
+    let clock = Arc::new(ManualClock::new());
+    let start = clock.now();
+    let (mut tx, rx) = Builder::new(test_dir_path)
+        .flush_interval(Duration::from_secs(1))
+        .with_clock(clock.clone())
+        .build()
+        .unwrap();
+
+    // nothing's due within the interval
+    tx.push("0").unwrap();
+    clock.advance(Duration::from_millis(999));
+    tx.push("1").unwrap();
+    assert_eq!(tx.last_flush, start);
+
+    // the first push after it flushes, and the next interval starts from there
+    clock.advance(Duration::from_millis(1));
+    tx.push("2").unwrap();
+    assert_eq!(tx.last_flush, start + Duration::from_secs(1));
+
+    clock.advance(Duration::from_millis(500));
+    tx.push("3").unwrap();
+    assert_eq!(tx.last_flush, start + Duration::from_secs(1));
+
+    // reserved frames count as pushes too
+    clock.advance(Duration::from_millis(500));
+    let mut frame = tx.reserve(1).unwrap();
+    frame[0] = b'4';
+    frame.commit(1).unwrap();
+    assert_eq!(tx.last_flush, start + Duration::from_secs(2));
+
+    // the sender dies without dropping or flushing anything itself
+    std::mem::forget(tx);
+    drop(rx);
+
+    let (_, mut rx) = new(test_dir_path).unwrap();
+    for i in 0..5 {
+        assert_eq!(rx.pop().unwrap().unwrap(), i.to_string());
+    }
+    assert!(rx.pop().unwrap().is_none());
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn push_detailed_test() {
+    let test_dir_path = "test-push-detailed";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .split_messages(true)
+        .build()
+        .unwrap();
+
+    let details = tx.push_detailed("small").unwrap();
+    assert_eq!(details.spanned_pages, 1);
+    assert_eq!(details.written, tx.format.frame_len(5));
+
+    let msg = vec![b'a'; 10_000_000];
+    let details = loop {
+        let details = tx.push_detailed(&msg).unwrap();
+        if tx.qpage_no == 1 {
+            break details;
+        }
+        assert_eq!(details.spanned_pages, 1);
+    };
+
+    // the message that didn't fit went half on each page
+    assert_eq!(details.spanned_pages, 2);
+    assert!(details.written > tx.format.frame_len(msg.len()));
+    assert_eq!(rx.pop().unwrap().unwrap(), "small");
+    while let Some(popped) = rx.pop().unwrap() {
+        assert_eq!(popped.len(), msg.len());
+    }
+    assert_eq!(rx.qpage_no, 1);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn streams_equal_test() {
+    let src_dir_path = "test-streams-equal-src";
+    let copy_dir_path = "test-streams-equal-copy";
+    let migrated_dir_path = "test-streams-equal-migrated";
+
+    let (mut tx, _rx) = new(src_dir_path).unwrap();
+    for i in 0..100 {
+        tx.push(format!("msg-{i}")).unwrap();
+        if i == 50 {
+            tx.seal_page().unwrap();
+        }
+    }
+
+    // a byte for byte copy and one with a different format both hold the same messages
+    std::fs::create_dir_all(copy_dir_path).unwrap();
+    for entry in std::fs::read_dir(src_dir_path).unwrap() {
+        let path = entry.unwrap().path();
+        std::fs::copy(
+            &path,
+            Path::new(copy_dir_path).join(path.file_name().unwrap()),
+        )
+        .unwrap();
+    }
+    migrate(
+        src_dir_path,
+        Builder::new(migrated_dir_path).length_prefix(LengthPrefix::U16),
+    )
+    .unwrap();
+    assert!(streams_equal(src_dir_path, copy_dir_path).unwrap());
+    assert!(streams_equal(copy_dir_path, migrated_dir_path).unwrap());
+
+    // a message that changed under way
+    let page_path = Path::new(copy_dir_path).join(format!("1.{PAGE_EXT}"));
+    let mut page = std::fs::read(&page_path).unwrap();
+    let at = page.windows(6).position(|w| w == b"msg-77").unwrap();
+    page[at + 4] = b'8';
+    std::fs::write(&page_path, page).unwrap();
+    assert!(!streams_equal(src_dir_path, copy_dir_path).unwrap());
+
+    // and one that's missing at the end
+    tx.push("one more").unwrap();
+    assert!(!streams_equal(src_dir_path, migrated_dir_path).unwrap());
+    assert!(!streams_equal(migrated_dir_path, src_dir_path).unwrap());
+
+    for path in [src_dir_path, copy_dir_path, migrated_dir_path] {
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
+
+#[test]
+fn headers_carried_over_test() {
+    let src_dir_path = "test-headers-carried-src";
+    let imported_dir_path = "test-headers-carried-imported";
+    let migrated_dir_path = "test-headers-carried-migrated";
+    let archive_path = "test-headers-carried.archive";
+
+    let (mut tx, _rx) = Builder::new(src_dir_path)
+        .message_headers(true)
+        .message_keys(true)
+        .build()
+        .unwrap();
+    for i in 0..50 {
+        let trace_id = format!("trace-{i}");
+        tx.push_with_headers(&[("trace-id", &trace_id)], format!("msg-{i}"))
+            .unwrap();
+    }
+    tx.push_keyed(7, "no headers").unwrap();
+
+    let expected: Vec<_> = (0..50)
+        .map(|i| {
+            (
+                format!("msg-{i}"),
+                vec![("trace-id".to_string(), format!("trace-{i}"))],
+            )
+        })
+        .chain([("no headers".to_string(), Vec::new())])
+        .collect();
+    let popped = |path: &str| {
+        let mut rx = DiskRing::<Receiver>::new(path).unwrap();
+        std::iter::from_fn(|| rx.pop_with_headers().unwrap()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(export(src_dir_path, archive_path).unwrap(), 51);
+    assert_eq!(import(archive_path, imported_dir_path).unwrap(), 51);
+    assert_eq!(popped(imported_dir_path), expected);
+
+    // dst picks up the headers unless told not to
+    assert_eq!(
+        migrate(
+            src_dir_path,
+            Builder::new(migrated_dir_path)
+                .byte_order(ByteOrder::Big)
+                .message_keys(true)
+        )
+        .unwrap(),
+        51
+    );
+    assert_eq!(popped(migrated_dir_path), expected);
+    assert!(matches!(
+        migrate(
+            src_dir_path,
+            Builder::new("test-headers-carried-none").message_headers(false)
+        ),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    assert!(streams_equal(src_dir_path, imported_dir_path).unwrap());
+    assert!(streams_equal(src_dir_path, migrated_dir_path).unwrap());
+
+    // the same messages with different headers aren't the same stream
+    std::fs::remove_dir_all(migrated_dir_path).unwrap();
+    let (mut tx, _rx) = Builder::new(migrated_dir_path)
+        .message_headers(true)
+        .build()
+        .unwrap();
+    for i in 0..50 {
+        tx.push(format!("msg-{i}")).unwrap();
+    }
+    tx.push("no headers").unwrap();
+    assert!(!streams_equal(src_dir_path, migrated_dir_path).unwrap());
+
+    for path in [src_dir_path, imported_dir_path, migrated_dir_path] {
+        std::fs::remove_dir_all(path).unwrap();
+    }
+    std::fs::remove_file(archive_path).unwrap();
+}
+
+#[test]
+fn consume_test() {
+    let test_dir_path = "test-consume";
+    let (mut tx, mut rx) = new(test_dir_path).unwrap();
+
+    let t = std::thread::spawn(move || {
+        for i in 0..100 {
+            tx.push(i.to_string()).unwrap();
+            if i == 50 {
+                tx.seal_page().unwrap();
+            }
+        }
+        tx
+    });
+
+    // waits for messages that aren't there yet and stops on the break
+    let mut seen = Vec::new();
+    rx.consume(|msg| {
+        seen.push(String::from_utf8(msg.to_vec()).unwrap());
+        match seen.len() {
+            80 => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        }
+    })
+    .unwrap();
+    let tx = t.join().unwrap();
+
+    assert_eq!(seen, (0..80).map(|i| i.to_string()).collect::<Vec<_>>());
+    assert_eq!(rx.pop().unwrap().unwrap(), "80");
+
+    // and ends once the ringbuf is closed and drained
+    tx.close();
+    let mut rest = 0;
+    rx.consume(|_| {
+        rest += 1;
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    assert_eq!(rest, 19);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn message_headers_test() {
+    let test_dir_path = "test-message-headers";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .message_headers(true)
+        .message_keys(true)
+        .build()
+        .unwrap();
+
+    let headers = [("content-type", "text/plain"), ("trace-id", "abc123")];
+    tx.push_with_headers(&headers, "hello").unwrap();
+    tx.push_with_headers(&[], "no headers").unwrap();
+    tx.push_keyed(7, "plain").unwrap();
+    tx.push_with_headers(&headers, "").unwrap();
+
+    let (msg, popped) = rx.pop_with_headers().unwrap().unwrap();
+    assert_eq!(msg, "hello");
+    assert_eq!(
+        popped,
+        headers.map(|(key, value)| (key.to_string(), value.to_string()))
+    );
+    assert_eq!(
+        rx.pop_with_headers().unwrap().unwrap(),
+        ("no headers".to_string(), Vec::new())
+    );
+
+    // everything else reads around them
+    assert_eq!(rx.pop().unwrap().unwrap(), "plain");
+    assert_eq!(
+        rx.qpage
+            .get_inner()
+            .frame_key(rx.read_byte - rx.popped_frame_len, rx.format),
+        Some(7)
+    );
+    let mut buf = Vec::new();
+    assert_eq!(rx.pop_into(&mut buf).unwrap(), Some(0));
+    assert!(rx.pop_with_headers().unwrap().is_none());
+
+    // a new receiver skips the headers the same way, and so does re-reading
+    let mut rx = DiskRing::<Receiver>::new(test_dir_path).unwrap();
+    let first = Position {
+        qpage_no: 0,
+        read_byte: 0,
+    };
+    assert_eq!(rx.re_read(first).unwrap().unwrap(), "hello");
+    let mut count = 0;
+    while rx.pop().unwrap().is_some() {
+        count += 1;
+    }
+    assert_eq!(count, 4);
+
+    assert!(matches!(tx.reserve(8), Err(RingbufError::InvalidConfig(_))));
+    let long = "x".repeat(u16::MAX as usize + 1);
+    assert!(matches!(
+        tx.push_with_headers(&[("key", &long)], "msg"),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    // the setting sticks, and doesn't go with splitting
+    assert!(matches!(
+        Builder::new(test_dir_path).message_headers(false).build(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        Builder::new("test-message-headers-split")
+            .message_headers(true)
+            .split_messages(true)
+            .build(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    let (mut tx, mut rx) = new("test-message-headers-none").unwrap();
+    assert!(matches!(
+        tx.push_with_headers(&headers, "msg"),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        rx.pop_with_headers(),
+        Err(RingbufError::InvalidConfig(_))
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+    std::fs::remove_dir_all("test-message-headers-none").unwrap();
+}
+
+#[test]
+fn queries_dont_create_test() {
+    let test_dir_path = "test-queries-dont-create";
+    std::fs::create_dir_all(test_dir_path).unwrap();
+
+    assert!(matches!(usage(test_dir_path), Err(RingbufError::NotFound)));
+    assert!(matches!(
+        head_position(test_dir_path),
+        Err(RingbufError::NotFound)
+    ));
+    assert!(matches!(
+        get_metadata(test_dir_path),
+        Err(RingbufError::NotFound)
+    ));
+    assert!(matches!(
+        repair_stuck_writers(test_dir_path),
+        Err(RingbufError::NotFound)
+    ));
+
+    // none of them left a ringbuf behind
+    assert_eq!(std::fs::read_dir(test_dir_path).unwrap().count(), 0);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn retained_messages_test() {
+    let test_dir_path = "test-retained-messages";
+    let (mut tx, _rx) = Builder::new(test_dir_path).max_qpages(3).build().unwrap();
+    assert_eq!(retained_messages(test_dir_path).unwrap(), 0);
+
+    for page in 0..5 {
+        for i in 0..10 {
+            tx.push(format!("{page}-{i}")).unwrap();
+        }
+        tx.seal_page().unwrap();
+
+        // the fresh page after the sealed one takes up a slot in the window of 3
+        let full_pages = (page + 1).min(2);
+        assert_eq!(retained_messages(test_dir_path).unwrap(), 10 * full_pages);
+    }
+
+    tx.push("one more").unwrap();
+    assert_eq!(retained_messages(test_dir_path).unwrap(), 21);
+    assert_eq!(usage(test_dir_path).unwrap().retained_messages, 21);
+    assert_eq!(tx.dropped_total(), 30);
+
+    // a page that isn't fully created yet is skipped instead of mapped
+    let newest = Path::new(test_dir_path).join(format!("{}.{PAGE_EXT}", tx.qpage_no));
+    drop(tx);
+    File::options()
+        .write(true)
+        .open(newest)
+        .unwrap()
+        .set_len(100)
+        .unwrap();
+    assert_eq!(retained_messages(test_dir_path).unwrap(), 20);
+
+    assert!(matches!(
+        retained_messages("test-retained-messages-missing"),
+        Err(RingbufError::NotFound)
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn polling_read_test() {
+    const WRITER_ENV: &str = "DISK_RINGBUFFER_TEST_POLLED_WRITER";
+    const MSGS: u64 = 40;
+
+    let test_dir_path = "test-polling-read";
+    let msg_len = qpage::DEFAULT_MAX_MSG_SIZE;
+
+    if std::env::var(WRITER_ENV).is_ok() {
+        let mut tx = DiskRing::<Sender>::new(test_dir_path).unwrap();
+
+        let mut msg = vec![0; msg_len];
+        for i in 0..MSGS {
+            msg[..8].copy_from_slice(&i.to_le_bytes());
+            tx.push(&msg).unwrap();
+        }
+
+        return;
+    }
+
+    // nothing to read, and a polling receiver can't create it
+    assert!(matches!(
+        DiskRing::open_polling(test_dir_path),
+        Err(RingbufError::NotFound)
+    ));
+
+    std::fs::create_dir_all(test_dir_path).unwrap();
+    DiskRing::<Sender>::new(test_dir_path).unwrap();
+    let mut rx = DiskRing::<Receiver>::open_polling(test_dir_path).unwrap();
+    assert_eq!(rx.pop().unwrap(), None);
+
+    // reads along while the other process appends across a couple of page flips
+    let mut writer = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "ringbuf::polling_read_test"])
+        .env(WRITER_ENV, "1")
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(120);
+    let mut next = 0;
+    while next < MSGS && Instant::now() < deadline {
+        match rx.pop_as::<Vec<u8>>().unwrap() {
+            Some(msg) => {
+                assert_eq!(msg.len(), msg_len);
+                assert_eq!(u64::from_le_bytes(msg[..8].try_into().unwrap()), next);
+                next += 1;
+            }
+            None => std::thread::sleep(Duration::from_millis(1)),
+        }
+    }
+
+    assert!(writer.wait().unwrap().success());
+    assert_eq!(next, MSGS);
+    assert!(rx.qpage_no > 0);
+    assert_eq!(rx.pop().unwrap(), None);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn stored_len_test() {
+    assert_eq!(qpage::stored_len(7_u32), Some(7));
+    assert_eq!(qpage::stored_len(u16::MAX), Some(u16::MAX as usize));
+    assert_eq!(
+        qpage::stored_len(isize::MAX as usize),
+        Some(isize::MAX as usize)
+    );
+
+    // past what any slice could hold, or what fits in a usize at all
+    assert_eq!(qpage::stored_len(isize::MAX as usize + 1), None);
+    assert_eq!(qpage::stored_len(usize::MAX as u128 + 1), None);
+    assert_eq!(qpage::stored_len(-1_i64), None);
+
+    // a u32 prefix decodes to the exact length rather than a truncated one
+    let format = FrameFormat::default();
+    let mut buf = vec![0; 16];
+    format.encode(u32::MAX as usize - 1, &mut buf);
+    assert_eq!(
+        format.decode(&buf).map(|(len, _)| len),
+        qpage::stored_len(u32::MAX - 1)
+    );
+}
+
+#[test]
+fn flush_on_drop_test() {
+    let test_dir_path = "test-flush-on-drop";
+    let (mut tx, rx) = Builder::new(test_dir_path)
+        .flush_on_drop(true)
+        .build()
+        .unwrap();
+
+    // only the sender and its clones flush
+    assert!(tx.flush_on_drop);
+    assert!(tx.clone().flush_on_drop);
+    assert!(!rx.flush_on_drop);
+    drop(rx);
+
+    {
+        let mut tx = tx.clone();
+        for i in 0..10 {
+            tx.push(i.to_string()).unwrap();
+        }
+    }
+
+    let (_, mut rx) = new(test_dir_path).unwrap();
+    for i in 0..10 {
+        assert_eq!(rx.pop().unwrap().unwrap(), i.to_string());
+    }
+    assert!(rx.pop().unwrap().is_none());
+
+    // a flush that fails while dropping doesn't take the caller down with it
+    tx.push("10").unwrap();
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+    drop(tx);
+
+    // off by default
+    let (tx, _) = new(test_dir_path).unwrap();
+    assert!(!tx.flush_on_drop);
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn page_metadata_test() {
+    let test_dir_path = "test-page-metadata";
+    let (mut tx, _) = new(test_dir_path).unwrap();
+
+    let mut used_bytes = 0;
+    for i in 0..50 {
+        let msg = i.to_string().repeat(i + 1);
+        used_bytes += tx.format.frame_len(msg.len());
+        tx.push(msg).unwrap();
+    }
+    tx.seal_page().unwrap();
+    tx.push("next page").unwrap();
+
+    let meta = page_metadata(test_dir_path, 0).unwrap();
+    assert_eq!(meta.used_bytes, used_bytes);
+    assert_eq!(meta.messages, 50);
+    assert!(meta.full);
+    assert!(meta.modified <= SystemTime::now());
+
+    let meta = page_metadata(test_dir_path, 1).unwrap();
+    assert_eq!(meta.used_bytes, tx.format.frame_len("next page".len()));
+    assert_eq!(meta.messages, 1);
+    assert!(!meta.full);
+
+    assert!(matches!(
+        page_metadata(test_dir_path, 2),
+        Err(RingbufError::NotFound)
+    ));
+    assert!(matches!(
+        page_metadata("test-page-metadata-missing", 0),
+        Err(RingbufError::NotFound)
+    ));
+
+    std::fs::remove_dir_all(test_dir_path).unwrap();
+}
+
+#[test]
+fn split_message_ownership_test() {
+    let test_dir_path = "test-split-message-ownership";
+    let (mut tx, mut rx) = Builder::new(test_dir_path)
+        .split_messages(true)
+        .max_qpages(2)
+        .build()
+        .unwrap();
+
+    let msg = |i: usize| vec![b'a' + (i % 26) as u8; 10_000_000 + i];
+    let mut pushed = 0;
+    while tx.qpage_no == 0 {
+        tx.push(msg(pushed)).unwrap();
+        pushed += 1;
+    }
+    tx.push(msg(pushed)).unwrap();
+
+    // the message put back together is borrowed from the stitcher, not copied
+    assert_eq!(rx.skip_messages(pushed - 1).unwrap(), pushed - 1);
+    assert!(matches!(rx.try_pop().unwrap(), Popped::PageDone));
+    rx.page_flip().unwrap();
+    let Popped::Msg(stitched) = rx.try_pop().unwrap() else {
+        panic!("expected the split message")
+    };
+    assert_eq!(stitched, msg(pushed - 1));
+    let stitched = stitched.as_ptr();
+    let (_, held) = rx.stitcher.stitched.as_ref().unwrap();
+    assert_eq!(stitched, held.as_ptr());
+
+    // and a batch ends with it, the one after comes with the next batch
+    let batch = rx
+        .pop_batch_ref(usize::MAX)
+        .map(<[u8]>::to_vec)
+        .collect::<Vec<_>>();
+    assert_eq!(batch, [msg(pushed - 1)]);
+    let batch = rx
+        .pop_batch_ref(usize::MAX)
+        .map(<[u8]>::to_vec)
+        .collect::<Vec<_>>();
+    assert_eq!(batch, [msg(pushed)]);
+
+    // heads whose tails were reclaimed along with them don't pile up
+    let head = |qpage_no| Position {
+        qpage_no,
+        read_byte: 0,
+    };
+    rx.stitcher.heads.insert(head(0), vec![0]);
+    rx.stitcher.heads.insert(head(1), vec![1]);
+    rx.stitcher
+        .forget_reclaimed(rx.diskring_info.clone().get_inner());
+    assert_eq!(rx.stitcher.heads.len(), 2);
+
+    tx.seal_page().unwrap();
+    tx.seal_page().unwrap();
+    rx.stitcher
+        .forget_reclaimed(rx.diskring_info.clone().get_inner());
+    assert_eq!(rx.stitcher.heads.keys().collect::<Vec<_>>(), [&head(1)]);
 
     std::fs::remove_dir_all(test_dir_path).unwrap();
 }